@@ -0,0 +1,46 @@
+//! Benchmarks loading generated profiles of increasing size
+//!
+//! ```text
+//! cargo bench --bench load
+//! ```
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use differential_dashboard::{
+    demo::{demo_profile, DemoConfig},
+    loader::{load_profile, validate},
+};
+
+fn load(c: &mut Criterion) {
+    let mut group = c.benchmark_group("load");
+
+    for &rounds in &[10, 100, 1_000] {
+        let config = DemoConfig {
+            workers: 8,
+            rounds,
+            ..DemoConfig::default()
+        };
+        let data = demo_profile(&config);
+        let content = serde_json::to_vec(&data).unwrap();
+        let events = data.timeline_events.len();
+        group.throughput(Throughput::Elements(events as u64));
+
+        group.bench_with_input(BenchmarkId::new("validate", events), &data, |b, data| {
+            b.iter_batched(
+                || data.clone(),
+                |mut data| validate(&mut data),
+                criterion::BatchSize::LargeInput,
+            )
+        });
+
+        group.bench_with_input(
+            BenchmarkId::new("load_profile", events),
+            &content,
+            |b, content| b.iter(|| load_profile("demo.json".to_owned(), content, |_| {}).unwrap()),
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, load);
+criterion_main!(benches);
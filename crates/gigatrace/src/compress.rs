@@ -0,0 +1,303 @@
+use crate::trace::{
+    BlockIndex, BlockPool, BlockStore, MemoryUsage, Nanos, PackedNanos, TraceBlock, TraceEvent,
+};
+use core::{hash::Hash, mem};
+use std::{borrow::Cow, collections::HashMap};
+
+/// A block encoded as a sequence of varints, each event is stored as the
+/// delta from the previous event's timestamp and its duration, followed by
+/// the index of its kind when the pool uses a kind dictionary
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CompressedBlock {
+    start_time: Nanos,
+    /// Where the block's kinds start when they're stored inline
+    first_kind: u32,
+    len: u8,
+    bytes: Box<[u8]>,
+}
+
+impl CompressedBlock {
+    pub fn start_time(&self) -> Nanos {
+        self.start_time
+    }
+
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn encoded_len(&self) -> usize {
+        self.bytes.len()
+    }
+}
+
+/// How the kinds of a pool's events are stored
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum KindEncoding<K> {
+    /// Kinds repeat, so events store the index of their kind within the dictionary
+    Dictionary(Vec<K>),
+    /// Kinds are mostly unique so a dictionary would only add to their size,
+    /// every event's kind is stored as it is, ordered by block
+    Inline(Vec<K>),
+}
+
+/// A `BlockStore` holding compressed blocks which are decoded as they're
+/// accessed. It's built from a finished `BlockPool` and can't be added to
+/// afterwards. Block indices are the same as the pool it was built from, so
+/// existing tracks and indices can be used with it
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CompressedBlockPool<K> {
+    blocks: Vec<CompressedBlock>,
+    kinds: KindEncoding<K>,
+}
+
+impl<K> CompressedBlockPool<K>
+where
+    K: Default + Clone + Eq + Hash,
+{
+    pub fn from_pool(pool: &BlockPool<K>) -> Self {
+        let mut dictionary = Vec::new();
+        let mut lookup = HashMap::new();
+        let mut events = 0;
+        for event in pool.blocks.iter().flat_map(TraceBlock::events) {
+            events += 1;
+            lookup.entry(event.kind.clone()).or_insert_with(|| {
+                dictionary.push(event.kind.clone());
+                dictionary.len() as u64 - 1
+            });
+        }
+
+        // A dictionary entry costs at least as much as storing its kind
+        // inline, so it only pays off once kinds repeat
+        let dictionary = dictionary.len() * 2 <= events;
+
+        let mut inline = Vec::new();
+        let blocks = pool
+            .blocks
+            .iter()
+            .map(|block| {
+                let start_time = block.start_time();
+                let mut bytes = Vec::with_capacity(block.len() * 4);
+
+                let mut last_timestamp = start_time;
+                for event in block.events() {
+                    let timestamp = event.timestamp.unpack();
+                    write_varint(&mut bytes, timestamp - last_timestamp);
+                    write_varint(&mut bytes, event.duration.unpack());
+                    if dictionary {
+                        write_varint(&mut bytes, lookup[&event.kind]);
+                    }
+
+                    last_timestamp = timestamp;
+                }
+
+                let first_kind = inline.len() as u32;
+                if !dictionary {
+                    inline.extend(block.events().iter().map(|event| event.kind.clone()));
+                }
+
+                CompressedBlock {
+                    start_time,
+                    first_kind,
+                    len: block.len() as u8,
+                    bytes: bytes.into_boxed_slice(),
+                }
+            })
+            .collect();
+
+        let kinds = if dictionary {
+            let mut kinds = vec![K::default(); lookup.len()];
+            for (kind, idx) in lookup {
+                kinds[idx as usize] = kind;
+            }
+
+            KindEncoding::Dictionary(kinds)
+        } else {
+            inline.shrink_to_fit();
+            KindEncoding::Inline(inline)
+        };
+
+        Self { blocks, kinds }
+    }
+}
+
+impl<K> CompressedBlockPool<K>
+where
+    K: Default + Clone,
+{
+    pub fn decode(&self, block: BlockIndex) -> TraceBlock<K> {
+        let block = &self.blocks[block as usize];
+
+        let mut decoded = TraceBlock::new();
+        let (mut bytes, mut timestamp) = (&*block.bytes, block.start_time);
+        for idx in 0..block.len() {
+            timestamp += read_varint(&mut bytes);
+            let duration = read_varint(&mut bytes);
+            let kind = match &self.kinds {
+                KindEncoding::Dictionary(kinds) => kinds[read_varint(&mut bytes) as usize].clone(),
+                KindEncoding::Inline(kinds) => kinds[block.first_kind as usize + idx].clone(),
+            };
+
+            decoded.push(TraceEvent {
+                kind,
+                timestamp: PackedNanos::new(timestamp),
+                duration: PackedNanos::new(duration),
+            });
+        }
+
+        decoded
+    }
+
+    /// Whether events store an index into a dictionary of kinds rather than their kind
+    pub fn uses_dictionary(&self) -> bool {
+        matches!(self.kinds, KindEncoding::Dictionary(_))
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+}
+
+impl<K> BlockStore<K> for CompressedBlockPool<K>
+where
+    K: Default + Clone,
+{
+    fn block(&self, block: BlockIndex) -> Cow<'_, TraceBlock<K>> {
+        Cow::Owned(self.decode(block))
+    }
+
+    fn start_time(&self, block: BlockIndex) -> Nanos {
+        self.blocks[block as usize].start_time
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        let encoded: usize = self.blocks.iter().map(CompressedBlock::encoded_len).sum();
+        let kinds = match &self.kinds {
+            KindEncoding::Dictionary(kinds) | KindEncoding::Inline(kinds) => {
+                kinds.capacity() * mem::size_of::<K>()
+            }
+        };
+
+        MemoryUsage {
+            blocks: self.blocks.len(),
+            events: self.blocks.iter().map(CompressedBlock::len).sum(),
+            bytes: self.blocks.capacity() * mem::size_of::<CompressedBlock>() + encoded + kinds,
+        }
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(bytes: &mut &[u8]) -> u64 {
+    let (mut value, mut shift) = (0, 0);
+
+    loop {
+        let byte = bytes[0];
+        *bytes = &bytes[1..];
+
+        value |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CompressedBlockPool;
+    use crate::trace::{BlockPool, BlockStore, PackedNanos, TraceEvent, Track};
+    use core::mem;
+    use fastrand::Rng;
+
+    /// Builds a track of `n` events, `kind` picks the kind of each from its index
+    fn track_with_kinds<F>(n: usize, mut kind: F) -> (Track<u64>, BlockPool<u64>)
+    where
+        F: FnMut(&Rng, u64) -> u64,
+    {
+        let (mut track, mut pool) = (Track::new(), BlockPool::new());
+        let rng = Rng::with_seed(0xDEAD_BEEF);
+
+        let mut timestamp = 0;
+        for idx in 0..n as u64 {
+            timestamp += rng.u64(0..10_000);
+
+            track
+                .push(
+                    &mut pool,
+                    TraceEvent {
+                        kind: kind(&rng, idx),
+                        timestamp: PackedNanos::new(timestamp),
+                        duration: PackedNanos::new(rng.u64(0..50_000)),
+                    },
+                )
+                .unwrap();
+        }
+
+        (track, pool)
+    }
+
+    fn assert_round_trips(track: &Track<u64>, pool: &BlockPool<u64>) {
+        let compressed = CompressedBlockPool::from_pool(pool);
+        assert_eq!(compressed.len(), pool.blocks.len());
+
+        for &block in track.block_locations() {
+            let original = &pool.blocks[block as usize];
+            assert_eq!(compressed.block(block).events(), original.events());
+            assert_eq!(
+                BlockStore::start_time(&compressed, block),
+                original.start_time(),
+            );
+        }
+    }
+
+    #[test]
+    fn repeated_kinds_use_a_dictionary() {
+        let (track, pool) = track_with_kinds(1_000, |rng, _| rng.u64(0..16));
+        assert_round_trips(&track, &pool);
+
+        let compressed = CompressedBlockPool::from_pool(&pool);
+        assert!(compressed.uses_dictionary());
+
+        let (usage, compressed_usage) = (pool.memory_usage(), compressed.memory_usage());
+        assert_eq!(compressed_usage.events, 1_000);
+        assert!(
+            compressed_usage.bytes * 2 < usage.bytes,
+            "{:?} isn't half of {:?}",
+            compressed_usage,
+            usage,
+        );
+    }
+
+    #[test]
+    fn unique_kinds_are_stored_inline() {
+        let (track, pool) = track_with_kinds(1_000, |_, idx| idx);
+        assert_round_trips(&track, &pool);
+
+        let compressed = CompressedBlockPool::from_pool(&pool);
+        assert!(!compressed.uses_dictionary());
+
+        // Without a dictionary each event only costs its kind on top of its
+        // encoded time and duration
+        let (usage, compressed_usage) = (pool.memory_usage(), compressed.memory_usage());
+        assert_eq!(compressed_usage.events, 1_000);
+        assert!(compressed_usage.bytes < usage.bytes);
+        assert!(compressed_usage.bytes_per_event() < mem::size_of::<u64>() as f64 + 8.0);
+    }
+}
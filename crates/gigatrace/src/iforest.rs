@@ -1,10 +1,11 @@
 use crate::{
     index::{Aggregate, TrackIndex},
-    trace::{BlockPool, TraceBlock, Track},
+    trace::{BlockStore, TraceBlock, Track},
 };
 use core::{marker::PhantomData, ops::Range};
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(transparent)]
 pub struct IForestIndex<K, A> {
     pub values: Vec<A>,
@@ -106,13 +107,13 @@ impl<K, A> Default for IForestIndex<K, A> {
 
 impl<K, A> TrackIndex<K, A> for IForestIndex<K, A>
 where
-    K: Default,
+    K: Default + Clone,
     A: Aggregate<K> + Clone,
 {
-    fn build(track: &Track<K>, pool: &BlockPool<K>) -> IForestIndex<K, A> {
+    fn build<P: BlockStore<K>>(track: &Track<K>, pool: &P) -> IForestIndex<K, A> {
         let mut forest = IForestIndex::new();
-        for &idx in &track.block_locations {
-            forest.push(&pool.blocks[idx as usize]);
+        for &idx in track.block_locations() {
+            forest.push(&pool.block(idx));
         }
 
         // TODO in parallel
@@ -1,4 +1,4 @@
-use crate::trace::{BlockPool, Nanos, TraceBlock, TraceEvent, Track};
+use crate::trace::{BlockStore, Nanos, TraceBlock, TraceEvent, Track};
 use core::fmt::Debug;
 
 pub trait Aggregate<K> {
@@ -29,13 +29,14 @@ pub trait Aggregate<K> {
     }
 }
 
-pub trait TrackIndex<K: Default, A: Aggregate<K>> {
-    fn build(track: &Track<K>, pool: &BlockPool<K>) -> Self;
+pub trait TrackIndex<K: Default + Clone, A: Aggregate<K>> {
+    fn build<P: BlockStore<K>>(track: &Track<K>, pool: &P) -> Self;
 }
 
 // === Concrete aggregations
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(transparent)]
 pub struct LongestEvent<K>(pub Option<TraceEvent<K>>);
 
@@ -96,6 +97,54 @@ impl<K: Default + Clone + Debug> Aggregate<K> for LongestEvent<K> {
 //     }
 // }
 
+/// How many events there are, how long they ran for altogether and the longest of them
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EventStats<K> {
+    pub count: u64,
+    /// The sum of every event's duration
+    pub busy: Nanos,
+    pub longest: LongestEvent<K>,
+}
+
+impl<K: Default + Clone + Debug> Aggregate<K> for EventStats<K> {
+    fn empty() -> Self {
+        Self {
+            count: 0,
+            busy: 0,
+            longest: LongestEvent::empty(),
+        }
+    }
+
+    fn from_event(event: &TraceEvent<K>) -> Self {
+        Self {
+            count: 1,
+            busy: event.duration.unpack(),
+            longest: LongestEvent::from_event(event),
+        }
+    }
+
+    fn join(&self, other: &Self) -> Self {
+        Self {
+            count: self.count + other.count,
+            busy: self.busy + other.busy,
+            longest: self.longest.join(&other.longest),
+        }
+    }
+
+    fn from_block(block: &TraceBlock<K>) -> Self {
+        Self {
+            count: block.len() as u64,
+            busy: block
+                .events()
+                .iter()
+                .map(|event| event.duration.unpack())
+                .sum(),
+            longest: LongestEvent::from_block(block),
+        }
+    }
+}
+
 /// For debugging
 #[derive(Debug, Clone)]
 #[repr(transparent)]
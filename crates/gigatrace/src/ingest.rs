@@ -0,0 +1,204 @@
+use crate::{
+    iforest::IForestIndex,
+    index::Aggregate,
+    trace::{BlockPool, Nanos, TraceBlock, TraceEvent, Track},
+};
+use core::{
+    cmp::{Ordering, Reverse},
+    fmt::{self, Display},
+};
+use std::{collections::BinaryHeap, error::Error};
+
+/// Returned when an event arrives after the watermark has already passed it
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LateEvent {
+    pub timestamp: Nanos,
+    pub watermark: Nanos,
+}
+
+impl Display for LateEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "event at {} arrived after the watermark had reached {}",
+            self.timestamp, self.watermark,
+        )
+    }
+}
+
+impl Error for LateEvent {}
+
+/// The smallest `max_delay` that lets `events` be ingested in the order they're given
+pub fn max_delay<K>(events: &[TraceEvent<K>]) -> Nanos {
+    let mut latest = 0;
+    events.iter().fold(0, |delay, event| {
+        let timestamp = event.timestamp.unpack();
+        latest = latest.max(timestamp);
+        delay.max(latest - timestamp)
+    })
+}
+
+/// An index that sealed blocks are added to as they're ingested
+pub trait IngestIndex<K: Default> {
+    fn push_block(&mut self, block: &TraceBlock<K>);
+}
+
+impl<K, A> IngestIndex<K> for IForestIndex<K, A>
+where
+    K: Default,
+    A: Aggregate<K> + Clone,
+{
+    fn push_block(&mut self, block: &TraceBlock<K>) {
+        self.push(block);
+    }
+}
+
+/// Lets a track be ingested into several indices at once
+impl<K, I, J> IngestIndex<K> for (I, J)
+where
+    K: Default,
+    I: IngestIndex<K>,
+    J: IngestIndex<K>,
+{
+    fn push_block(&mut self, block: &TraceBlock<K>) {
+        self.0.push_block(block);
+        self.1.push_block(block);
+    }
+}
+
+/// Buffers events for a `Track` that can arrive up to `max_delay` out of order.
+///
+/// Events are held back until the watermark (the latest timestamp seen minus
+/// `max_delay`) passes them, at which point they're sealed into the track in
+/// order. Blocks are only added to the index once they're full so that
+/// queries never see an index entry for a block that's still growing
+#[derive(Debug)]
+pub struct TrackIngest<K> {
+    pending: BinaryHeap<Reverse<ByTimestamp<K>>>,
+    max_delay: Nanos,
+    latest: Nanos,
+    watermark: Nanos,
+    indexed_blocks: usize,
+}
+
+impl<K> TrackIngest<K>
+where
+    K: Default,
+{
+    pub fn new(max_delay: Nanos) -> Self {
+        Self {
+            pending: BinaryHeap::new(),
+            max_delay,
+            latest: 0,
+            watermark: 0,
+            indexed_blocks: 0,
+        }
+    }
+
+    /// Everything before the watermark has been sealed into the track
+    pub fn watermark(&self) -> Nanos {
+        self.watermark
+    }
+
+    /// The number of buffered events that haven't been sealed yet
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn push(&mut self, event: TraceEvent<K>) -> Result<(), LateEvent> {
+        let timestamp = event.timestamp.unpack();
+        if timestamp < self.watermark {
+            return Err(LateEvent {
+                timestamp,
+                watermark: self.watermark,
+            });
+        }
+
+        self.latest = self.latest.max(timestamp);
+        self.pending.push(Reverse(ByTimestamp(event)));
+
+        Ok(())
+    }
+
+    /// Seals every event that can no longer be reordered into `track`
+    pub fn advance<I>(&mut self, track: &mut Track<K>, index: &mut I, pool: &mut BlockPool<K>)
+    where
+        I: IngestIndex<K>,
+    {
+        let watermark = self.latest.saturating_sub(self.max_delay);
+        self.seal_until(watermark, track, pool);
+        self.watermark = self.watermark.max(watermark);
+
+        // The last block can still be appended to unless it's full
+        let sealed_blocks = match track.block_locations().last() {
+            Some(&last) if !pool.blocks[last as usize].is_full() => {
+                track.block_locations().len() - 1
+            }
+            _ => track.block_locations().len(),
+        };
+        self.index_blocks(sealed_blocks, track, index, pool);
+    }
+
+    /// Seals all buffered events and indexes the track's trailing block,
+    /// no more events can be added afterwards
+    pub fn finish<I>(mut self, track: &mut Track<K>, index: &mut I, pool: &mut BlockPool<K>)
+    where
+        I: IngestIndex<K>,
+    {
+        self.seal_until(Nanos::MAX, track, pool);
+        self.index_blocks(track.block_locations().len(), track, index, pool);
+    }
+
+    fn seal_until(&mut self, watermark: Nanos, track: &mut Track<K>, pool: &mut BlockPool<K>) {
+        while let Some(Reverse(ByTimestamp(event))) = self.pending.peek() {
+            if event.timestamp.unpack() > watermark {
+                break;
+            }
+
+            let Reverse(ByTimestamp(event)) = self.pending.pop().unwrap();
+            track
+                .push(pool, event)
+                .expect("events are sealed in timestamp order");
+        }
+    }
+
+    fn index_blocks<I>(
+        &mut self,
+        sealed_blocks: usize,
+        track: &Track<K>,
+        index: &mut I,
+        pool: &BlockPool<K>,
+    ) where
+        I: IngestIndex<K>,
+    {
+        for &block in &track.block_locations()[self.indexed_blocks..sealed_blocks] {
+            index.push_block(&pool.blocks[block as usize]);
+        }
+        self.indexed_blocks = self.indexed_blocks.max(sealed_blocks);
+    }
+}
+
+/// Orders events only by their timestamps
+#[derive(Debug)]
+#[repr(transparent)]
+struct ByTimestamp<K>(TraceEvent<K>);
+
+impl<K> PartialEq for ByTimestamp<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.timestamp == other.0.timestamp
+    }
+}
+
+impl<K> Eq for ByTimestamp<K> {}
+
+impl<K> PartialOrd for ByTimestamp<K> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K> Ord for ByTimestamp<K> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.timestamp.cmp(&other.0.timestamp)
+    }
+}
@@ -1,17 +1,26 @@
+pub mod compress;
 pub mod iforest;
 pub mod index;
+pub mod ingest;
+pub mod payload;
+pub mod render;
 pub mod trace;
 
 use crate::{
+    compress::CompressedBlockPool,
     iforest::IForestIndex,
-    index::{Aggregate, LongestEvent},
-    trace::{BlockIndex, BlockPool, Nanos, Track},
+    index::{Aggregate, EventStats, LongestEvent, TrackIndex},
+    ingest::{LateEvent, TrackIngest},
+    payload::{Payload, PayloadId, PayloadPool, StringTable},
+    trace::{
+        BlockIndex, BlockPool, BlockStore, Nanos, TraceClock, TraceEvent, Track, EVENTS_PER_BLOCK,
+    },
 };
-use core::{convert::identity, mem, ops::Range};
+use core::{convert::identity, fmt::Debug, hash::Hash, mem, ops::Range};
 
 // TODO: Preallocate
-pub fn aggregate_by_steps<K, A, F>(
-    pool: &BlockPool<K>,
+pub fn aggregate_by_steps<K, A, F, P>(
+    pool: &P,
     block_locations: &[BlockIndex],
     index: &IForestIndex<K, A>,
     time_span: Range<Nanos>,
@@ -19,9 +28,10 @@ pub fn aggregate_by_steps<K, A, F>(
     event_filter: F,
 ) -> Vec<A>
 where
-    K: Default,
+    K: Default + Clone,
     A: Aggregate<K> + PartialEq + Clone,
     F: Fn(&A) -> bool,
+    P: BlockStore<K>,
 {
     // TODO: Preallocate
     let mut out = vec![];
@@ -37,7 +47,7 @@ where
 
         // == Skip to last block with a start_time before target_time
         let search_result = block_locations[block_idx..]
-            .binary_search_by_key(&target_time, |&idx| pool.blocks[idx as usize].start_time())
+            .binary_search_by_key(&target_time, |&idx| pool.start_time(idx))
             .unwrap_or_else(identity);
 
         if search_result > 1 {
@@ -49,7 +59,7 @@ where
             block_idx += skip;
         }
 
-        let block = &pool.blocks[block_locations[block_idx] as usize];
+        let block = pool.block(block_locations[block_idx]);
         for event in block.events() {
             let event_time = event.timestamp.unpack();
 
@@ -82,17 +92,18 @@ where
 }
 
 // TODO: Preallocate
-pub fn aggregate_by_steps_unindexed<K, A, F>(
-    pool: &BlockPool<K>,
+pub fn aggregate_by_steps_unindexed<K, A, F, P>(
+    pool: &P,
     block_locs: &[BlockIndex],
     time_span: Range<Nanos>,
     time_step: u64,
     event_filter: F,
 ) -> Vec<A>
 where
-    K: Default,
+    K: Default + Clone,
     A: Aggregate<K> + PartialEq,
     F: Fn(&A) -> bool,
+    P: BlockStore<K>,
 {
     // TODO: Preallocate
     let mut out = vec![];
@@ -101,7 +112,7 @@ where
     let mut combined = A::empty();
 
     'outer: for &block_idx in block_locs {
-        let block = &pool.blocks[block_idx as usize];
+        let block = pool.block(block_idx);
 
         for event in block.events() {
             let event_time = event.timestamp.unpack();
@@ -132,25 +143,270 @@ where
     out
 }
 
+/// Like `aggregate_by_steps_unindexed` but only aggregates events passing
+/// `event_filter`, which the index can't answer since it depends on more than
+/// each step's combined events. Scanning starts `lookback` before the span and
+/// earlier events are left out, so `lookback` should be the longest event's
+/// duration for every event reaching into the span to be included
+pub fn aggregate_filtered_by_steps<K, A, F, P>(
+    pool: &P,
+    block_locations: &[BlockIndex],
+    time_span: Range<Nanos>,
+    time_step: u64,
+    lookback: Nanos,
+    event_filter: F,
+) -> Vec<A>
+where
+    K: Default + Clone,
+    A: Aggregate<K> + PartialEq,
+    F: Fn(&TraceEvent<K>) -> bool,
+    P: BlockStore<K>,
+{
+    let scan_from = time_span.start.saturating_sub(lookback);
+    // The last block starting before `scan_from` can still have events after it
+    let first = block_locations
+        .partition_point(|&idx| pool.start_time(idx) <= scan_from)
+        .saturating_sub(1);
+
+    let mut out = vec![];
+    let mut target_time = time_span.start;
+    let mut combined = A::empty();
+
+    'outer: for &block_idx in &block_locations[first..] {
+        let block = pool.block(block_idx);
+
+        for event in block.events() {
+            let event_time = event.timestamp.unpack();
+            if event_time < scan_from || !event_filter(event) {
+                continue;
+            }
+
+            while event_time >= target_time {
+                // Only produce the aggregate if it's actual data
+                let produced = mem::replace(&mut combined, A::empty());
+                if !produced.is_empty() {
+                    out.push(produced);
+                }
+
+                if target_time >= time_span.end {
+                    break 'outer;
+                }
+
+                target_time += time_step;
+            }
+
+            combined = A::join(&combined, &A::from_event(event));
+        }
+    }
+
+    // Only produce the aggregate if it's actual data
+    if !combined.is_empty() {
+        out.push(combined);
+    }
+
+    out
+}
+
+/// Aggregates the events of a track that start within `time_span`. Blocks that
+/// lie entirely within it are aggregated by the index, so only the blocks at
+/// either end of the span are scanned
+pub fn aggregate_range<K, A, P>(
+    pool: &P,
+    block_locations: &[BlockIndex],
+    index: &IForestIndex<K, A>,
+    time_span: Range<Nanos>,
+) -> A
+where
+    K: Default + Clone,
+    A: Aggregate<K> + Clone,
+    P: BlockStore<K>,
+{
+    // Blocks `first..last` start within the span
+    let first = block_locations.partition_point(|&idx| pool.start_time(idx) < time_span.start);
+    let last = block_locations.partition_point(|&idx| pool.start_time(idx) < time_span.end);
+
+    let scan = |block_idx: usize| {
+        pool.block(block_locations[block_idx])
+            .events()
+            .iter()
+            .filter(|event| time_span.contains(&event.timestamp.unpack()))
+            .fold(A::empty(), |combined, event| {
+                A::join(&combined, &A::from_event(event))
+            })
+    };
+
+    // The block before the span can have events within it
+    let mut combined = if first > 0 {
+        scan(first - 1)
+    } else {
+        A::empty()
+    };
+
+    if last > first {
+        // Every block but the last ends before the next one starts, so within the span
+        combined = A::join(&combined, &index.range_query(first..last - 1));
+        combined = A::join(&combined, &scan(last - 1));
+    }
+
+    combined
+}
+
+/// Calls `f` with every event of a track that starts within `time_span`
+fn for_each_within<K, P, F>(
+    pool: &P,
+    block_locations: &[BlockIndex],
+    time_span: Range<Nanos>,
+    mut f: F,
+) where
+    K: Default + Clone,
+    P: BlockStore<K>,
+    F: FnMut(&TraceEvent<K>),
+{
+    // The block before the first one starting within the span can have events within it
+    let first = block_locations
+        .partition_point(|&idx| pool.start_time(idx) < time_span.start)
+        .saturating_sub(1);
+
+    for &idx in &block_locations[first..] {
+        if pool.start_time(idx) >= time_span.end {
+            break;
+        }
+
+        pool.block(idx)
+            .events()
+            .iter()
+            .filter(|event| time_span.contains(&event.timestamp.unpack()))
+            .for_each(&mut f);
+    }
+}
+
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TrackInfo<K> {
     pub track: Track<K>,
     pub zoom_index: IForestIndex<K, LongestEvent<K>>,
+    pub stats_index: IForestIndex<K, EventStats<K>>,
 }
 
 impl<K> TrackInfo<K> {
-    pub const fn new(track: Track<K>, zoom_index: IForestIndex<K, LongestEvent<K>>) -> Self {
-        Self { track, zoom_index }
+    pub const fn new(
+        track: Track<K>,
+        zoom_index: IForestIndex<K, LongestEvent<K>>,
+        stats_index: IForestIndex<K, EventStats<K>>,
+    ) -> Self {
+        Self {
+            track,
+            zoom_index,
+            stats_index,
+        }
+    }
+
+    /// Indexes the track
+    pub fn build<P>(track: Track<K>, pool: &P) -> Self
+    where
+        K: Default + Clone + Debug,
+        P: BlockStore<K>,
+    {
+        let zoom_index = IForestIndex::build(&track, pool);
+        let stats_index = IForestIndex::build(&track, pool);
+
+        Self::new(track, zoom_index, stats_index)
+    }
+
+    /// Sorts and indexes events that arrive up to `max_delay` out of order,
+    /// they're sealed into the track a block's worth at a time. Events arriving
+    /// later than that are left out and returned along with their index in `events`
+    pub fn ingest<I>(
+        pool: &mut BlockPool<K>,
+        max_delay: Nanos,
+        events: I,
+    ) -> (Self, Vec<(usize, LateEvent)>)
+    where
+        K: Default + Clone + Debug,
+        I: IntoIterator<Item = TraceEvent<K>>,
+    {
+        let mut track = Track::new();
+        let mut indices = (IForestIndex::new(), IForestIndex::new());
+        let mut ingest = TrackIngest::new(max_delay);
+        let mut late = Vec::new();
+
+        for (idx, event) in events.into_iter().enumerate() {
+            if let Err(error) = ingest.push(event) {
+                late.push((idx, error));
+            }
+            if (idx + 1) % EVENTS_PER_BLOCK == 0 {
+                ingest.advance(&mut track, &mut indices, pool);
+            }
+        }
+        ingest.finish(&mut track, &mut indices, pool);
+
+        let (zoom_index, stats_index) = indices;
+        (Self::new(track, zoom_index, stats_index), late)
+    }
+
+    /// Aggregates the statistics of the events that start within `time_span`
+    pub fn stats<P>(&self, pool: &P, time_span: Range<Nanos>) -> EventStats<K>
+    where
+        K: Default + Clone + Debug,
+        P: BlockStore<K>,
+    {
+        aggregate_range(
+            pool,
+            self.track.block_locations(),
+            &self.stats_index,
+            time_span,
+        )
+    }
+
+    /// How long the track's events overlap `time_span`, events running into or
+    /// out of it only count for the time they spend within it. Events that start
+    /// far enough from its end to finish within it are aggregated by the index,
+    /// only those within the longest event's duration of either edge are scanned
+    pub fn busy_within<P>(&self, pool: &P, time_span: Range<Nanos>) -> Nanos
+    where
+        K: Default + Clone + Debug,
+        P: BlockStore<K>,
+    {
+        let blocks = self.track.block_locations();
+        let longest = self
+            .stats_index
+            .range_query(0..blocks.len())
+            .longest
+            .0
+            .map_or(0, |event| event.duration.unpack());
+
+        // Events starting before `inner_end` can't run past the span's end
+        let inner_end = time_span.end.saturating_sub(longest).max(time_span.start);
+        let mut busy = self.stats(pool, time_span.start..inner_end).busy;
+
+        let mut clip = |event: &TraceEvent<K>| {
+            let start = event.timestamp.unpack();
+            let end = start + event.duration.unpack();
+            busy += end
+                .min(time_span.end)
+                .saturating_sub(start.max(time_span.start));
+        };
+        let before = time_span.start.saturating_sub(longest)..time_span.start;
+        for_each_within(pool, blocks, before, &mut clip);
+        for_each_within(pool, blocks, inner_end..time_span.end, &mut clip);
+
+        busy
     }
 }
 
+/// A trace's tracks along with the pool their blocks are stored in. Traces are
+/// built within a `BlockPool` and can then be compressed
 #[derive(Debug)]
-pub struct Trace<K>
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Trace<K, P = BlockPool<K>>
 where
     K: Default,
 {
-    pub pool: BlockPool<K>,
+    pub pool: P,
     pub tracks: Vec<TrackInfo<K>>,
+    pub strings: StringTable,
+    pub payloads: PayloadPool,
+    pub clock: TraceClock,
 }
 
 impl<K> Trace<K>
@@ -158,23 +414,60 @@ where
     K: Default,
 {
     pub fn new() -> Self {
+        Self::with_clock(TraceClock::default())
+    }
+
+    pub fn with_clock(clock: TraceClock) -> Self {
         Trace {
             pool: BlockPool::new(),
             tracks: Vec::new(),
+            strings: StringTable::new(),
+            payloads: PayloadPool::new(),
+            clock,
         }
     }
 
+    /// Compresses the trace's blocks, block indices are kept so its tracks,
+    /// their indices and any other tracks built within the same pool still apply
+    pub fn compress(self) -> Trace<K, CompressedBlockPool<K>>
+    where
+        K: Clone + Eq + Hash,
+    {
+        Trace {
+            pool: CompressedBlockPool::from_pool(&self.pool),
+            tracks: self.tracks,
+            strings: self.strings,
+            payloads: self.payloads,
+            clock: self.clock,
+        }
+    }
+}
+
+impl<K, P> Trace<K, P>
+where
+    K: Default + Clone,
+    P: BlockStore<K>,
+{
+    /// The bounds of the trace in ticks, see `TraceClock` for converting them
     pub fn time_bounds(&self) -> Option<Range<Nanos>> {
         let start = self
             .tracks
             .iter()
-            .filter_map(|track| track.track.start_time(&self.pool))
+            .filter_map(|track| track.track.block_locations().first())
+            .map(|&block| self.pool.start_time(block))
             .min();
 
         let end = self
             .tracks
             .iter()
-            .filter_map(|track| track.track.after_last_time(&self.pool))
+            .filter_map(|track| track.track.block_locations().last())
+            .filter_map(|&block| {
+                let block = self.pool.block(block);
+                block
+                    .events()
+                    .last()
+                    .map(|event| event.timestamp.unpack() + event.duration.unpack())
+            })
             .max();
 
         match (start, end) {
@@ -184,6 +477,17 @@ where
     }
 }
 
+impl<P> Trace<PayloadId, P> {
+    pub fn payload(&self, event: &TraceEvent<PayloadId>) -> Option<Payload<'_>> {
+        self.payloads.get(event.kind)
+    }
+
+    pub fn event_name(&self, event: &TraceEvent<PayloadId>) -> Option<&str> {
+        self.payload(event)
+            .and_then(|payload| self.strings.get(payload.name))
+    }
+}
+
 impl<K> Default for Trace<K>
 where
     K: Default,
@@ -196,55 +500,439 @@ where
 #[cfg(test)]
 mod tests {
     use crate::{
+        compress::CompressedBlockPool,
         iforest::IForestIndex,
-        index::{Aggregate, EventCount, EventSum, LongestEvent, TrackIndex},
-        trace::{BlockIndex, BlockPool, Nanos, PackedNanos, TraceEvent, Track},
+        index::{Aggregate, EventCount, EventStats, EventSum, LongestEvent, TrackIndex},
+        ingest::{self, LateEvent, TrackIngest},
+        trace::{
+            BlockPool, Nanos, OutOfOrder, PackError, PackedNanos, Resolution, TraceClock,
+            TraceEvent, Track, EVENTS_PER_BLOCK,
+        },
+        TrackInfo,
     };
+    use fastrand::Rng;
+
+    fn add_dummy_events(track: &mut Track<u64>, pool: &mut BlockPool<u64>, rng: &Rng, n: usize) {
+        let mut timestamp = track.end_time(pool).unwrap_or(0);
+
+        for kind in 0..n as u64 {
+            timestamp += rng.u64(0..10_000);
+
+            track
+                .push(
+                    pool,
+                    TraceEvent {
+                        kind,
+                        timestamp: PackedNanos::new(timestamp),
+                        duration: PackedNanos::new(rng.u64(0..50_000)),
+                    },
+                )
+                .unwrap();
+        }
+    }
+
+    fn assert_track_invariants(track: &Track<u64>, pool: &BlockPool<u64>) {
+        let locations = track.block_locations();
+
+        for (idx, &block) in locations.iter().enumerate() {
+            let block = &pool.blocks[block as usize];
+            assert!(!block.is_empty(), "block {} is empty", idx);
+
+            if idx + 1 != locations.len() {
+                assert!(block.is_full(), "block {} isn't full", idx);
+            }
+        }
+
+        let timestamps: Vec<Nanos> = track
+            .events(pool)
+            .map(|event| event.timestamp.unpack())
+            .collect();
+        assert!(timestamps.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
 
     #[test]
     fn dummy_trace() {
         let mut pool = BlockPool::new();
         let mut track = Track::new();
-        let rng = Rng::new();
-        track.add_dummy_events(&mut pool, &rng, 300);
-
-        let mut maxes = vec![];
-        for i in &track.block_locations {
-            maxes.push(
-                LongestEvent::from_block(&pool.blocks[*i as usize])
-                    .0
-                    .unwrap()
-                    .duration
-                    .unpack(),
+        let rng = Rng::with_seed(0xDEAD_BEEF);
+        add_dummy_events(&mut track, &mut pool, &rng, 300);
+
+        let index = IForestIndex::<u64, LongestEvent<u64>>::build(&track, &pool);
+        let longest = index
+            .range_query(0..track.block_locations().len())
+            .0
+            .unwrap()
+            .duration
+            .unpack();
+
+        let correct = track
+            .events(&pool)
+            .map(|event| event.duration.unpack())
+            .max()
+            .unwrap();
+        assert_eq!(longest, correct);
+
+        for (idx, &block) in track.block_locations().iter().enumerate() {
+            let from_block = LongestEvent::from_block(&pool.blocks[block as usize]);
+            assert_eq!(index.range_query(idx..idx + 1), from_block);
+        }
+    }
+
+    #[test]
+    fn block_count() {
+        let mut pool = BlockPool::new();
+        let mut track = Track::new();
+        let rng = Rng::with_seed(0xDEAD_BEEF);
+        add_dummy_events(&mut track, &mut pool, &rng, 325);
+
+        let index = IForestIndex::<u64, EventCount>::build(&track, &pool);
+        let EventCount(count) = index.range_query(0..track.block_locations().len());
+        assert_eq!(count, 325);
+    }
+
+    #[test]
+    fn blocks_fill_to_capacity() {
+        let mut pool = BlockPool::new();
+        let mut track = Track::new();
+        let rng = Rng::with_seed(0xDEAD_BEEF);
+        add_dummy_events(&mut track, &mut pool, &rng, EVENTS_PER_BLOCK * 3 + 5);
+
+        assert_eq!(track.block_locations().len(), 4);
+        assert_eq!(pool.blocks.len(), 4);
+        assert_track_invariants(&track, &pool);
+    }
+
+    #[test]
+    fn prop_test_track_invariants() {
+        let rng = Rng::with_seed(0xDEAD_BEEF);
+
+        for _ in 0..100 {
+            let mut pool = BlockPool::new();
+            let mut tracks = vec![Track::new(), Track::new(), Track::new()];
+
+            // Interleave tracks within the same pool
+            for _ in 0..10 {
+                let track = &mut tracks[rng.usize(..3)];
+                add_dummy_events(track, &mut pool, &rng, rng.usize(..40));
+            }
+
+            for track in &tracks {
+                assert_track_invariants(track, &pool);
+            }
+        }
+    }
+
+    #[test]
+    fn out_of_order_push_is_rejected() {
+        let mut pool = BlockPool::new();
+        let mut track = Track::new();
+        let event = |timestamp| TraceEvent {
+            kind: 0,
+            timestamp: PackedNanos::new(timestamp),
+            duration: PackedNanos::new(0),
+        };
+
+        track.push(&mut pool, event(10)).unwrap();
+        track.push(&mut pool, event(10)).unwrap();
+        assert_eq!(
+            track.push(&mut pool, event(5)),
+            Err(OutOfOrder {
+                timestamp: 5,
+                last_timestamp: 10,
+            }),
+        );
+        assert_eq!(track.events(&pool).count(), 2);
+    }
+
+    #[test]
+    fn from_unsorted_sorts_events() {
+        let mut pool = BlockPool::new();
+        let rng = Rng::with_seed(0xDEAD_BEEF);
+
+        let events = (0..100).map(|kind| TraceEvent {
+            kind,
+            timestamp: PackedNanos::new(rng.u64(0..1_000)),
+            duration: PackedNanos::new(rng.u64(0..1_000)),
+        });
+        let track = Track::from_unsorted(&mut pool, events);
+
+        assert_eq!(track.events(&pool).count(), 100);
+        assert_track_invariants(&track, &pool);
+    }
+
+    #[test]
+    fn ingest_reorders_within_max_delay() {
+        let rng = Rng::with_seed(0xDEAD_BEEF);
+        let max_delay = 5_000;
+
+        for _ in 0..50 {
+            // Jitter each event's arrival by up to `max_delay`
+            let mut arrivals: Vec<_> = (0..rng.u64(0..400))
+                .map(|kind| {
+                    let timestamp = kind * 1_000 + rng.u64(0..1_000);
+                    let event = TraceEvent {
+                        kind,
+                        timestamp: PackedNanos::new(timestamp),
+                        duration: PackedNanos::new(rng.u64(0..50_000)),
+                    };
+
+                    (timestamp + rng.u64(0..max_delay), event)
+                })
+                .collect();
+            arrivals.sort_by_key(|&(arrival, _)| arrival);
+            let events: Vec<_> = arrivals.into_iter().map(|(_, event)| event).collect();
+
+            let mut pool = BlockPool::new();
+            let mut track = Track::new();
+            let mut index = IForestIndex::<u64, LongestEvent<u64>>::new();
+            let mut ingest = TrackIngest::new(max_delay);
+
+            for (idx, &event) in events.iter().enumerate() {
+                ingest.push(event).unwrap();
+
+                if idx % 7 == 0 {
+                    ingest.advance(&mut track, &mut index, &mut pool);
+                    assert_track_invariants(&track, &pool);
+
+                    // Only full blocks are indexed while ingesting
+                    let full_blocks = track
+                        .block_locations()
+                        .iter()
+                        .filter(|&&block| pool.blocks[block as usize].is_full())
+                        .count();
+                    assert_eq!(index.values.len(), full_blocks * 2);
+                }
+            }
+            ingest.finish(&mut track, &mut index, &mut pool);
+
+            let mut expected_pool = BlockPool::new();
+            let expected = Track::from_unsorted(&mut expected_pool, events);
+            let expected_index =
+                IForestIndex::<u64, LongestEvent<u64>>::build(&expected, &expected_pool);
+
+            assert_track_invariants(&track, &pool);
+            assert_eq!(
+                track
+                    .events(&pool)
+                    .map(|event| event.timestamp)
+                    .collect::<Vec<_>>(),
+                expected
+                    .events(&expected_pool)
+                    .map(|event| event.timestamp)
+                    .collect::<Vec<_>>(),
             );
+            assert_eq!(index.values, expected_index.values);
         }
-        println!("maxes: {:?}", maxes);
+    }
 
-        let index = IForestIndex::<LongestEvent>::build(&track, &pool);
-        let index_vals = index
-            .values
-            .iter()
-            .map(|x| x.0.unwrap().duration.unpack())
-            .collect::<Vec<_>>();
-        println!("index: {:?}", index_vals);
+    #[test]
+    fn ingest_track_info_matches_build() {
+        let rng = Rng::with_seed(0xDEAD_BEEF);
 
-        // assert!(false);
-        // TODO some test
+        for _ in 0..50 {
+            // Batches of sorted events, which are shuffled among each other
+            let mut batches: Vec<Vec<_>> = (0..rng.u64(0..40))
+                .map(|batch| {
+                    (0..rng.u64(0..20))
+                        .map(|idx| TraceEvent {
+                            kind: batch * 20 + idx,
+                            timestamp: PackedNanos::new(batch * 20_000 + idx * 1_000),
+                            duration: PackedNanos::new(rng.u64(0..50_000)),
+                        })
+                        .collect()
+                })
+                .collect();
+            rng.shuffle(&mut batches);
+            let events: Vec<_> = batches.into_iter().flatten().collect();
+
+            let mut pool = BlockPool::new();
+            let max_delay = ingest::max_delay(&events);
+            let (info, late) = TrackInfo::ingest(&mut pool, max_delay, events.clone());
+            assert_eq!(late, []);
+
+            let mut expected_pool = BlockPool::new();
+            let expected = TrackInfo::build(
+                Track::from_unsorted(&mut expected_pool, events),
+                &expected_pool,
+            );
+
+            assert_track_invariants(&info.track, &pool);
+            assert_eq!(
+                info.track.events(&pool).collect::<Vec<_>>(),
+                expected.track.events(&expected_pool).collect::<Vec<_>>(),
+            );
+            assert_eq!(info.zoom_index.values, expected.zoom_index.values);
+            assert_eq!(info.stats_index.values, expected.stats_index.values);
+        }
     }
 
     #[test]
-    fn block_count() {
+    fn ingest_track_info_leaves_out_late_events() {
+        // The last event is more than `max_delay` behind the first block's events
+        let events: Vec<_> = (0..EVENTS_PER_BLOCK as u64)
+            .map(|idx| idx * 1_000 + 1_000)
+            .chain(Some(0))
+            .map(|timestamp| TraceEvent {
+                kind: timestamp,
+                timestamp: PackedNanos::new(timestamp),
+                duration: PackedNanos::new(10),
+            })
+            .collect();
+
+        let mut pool = BlockPool::new();
+        let (info, late) = TrackInfo::ingest(&mut pool, 500, events);
+        assert_eq!(
+            late,
+            [(
+                EVENTS_PER_BLOCK,
+                LateEvent {
+                    timestamp: 0,
+                    watermark: EVENTS_PER_BLOCK as u64 * 1_000 - 500,
+                },
+            )],
+        );
+
+        assert_track_invariants(&info.track, &pool);
+        assert_eq!(info.track.events(&pool).count(), EVENTS_PER_BLOCK);
+    }
+
+    #[test]
+    fn ingest_rejects_late_events() {
+        let mut pool = BlockPool::new();
+        let mut track = Track::new();
+        let mut index = IForestIndex::<u64, EventCount>::new();
+        let mut ingest = TrackIngest::new(100);
+        let event = |timestamp| TraceEvent {
+            kind: 0,
+            timestamp: PackedNanos::new(timestamp),
+            duration: PackedNanos::new(0),
+        };
+
+        ingest.push(event(1_000)).unwrap();
+        ingest.push(event(950)).unwrap();
+        ingest.advance(&mut track, &mut index, &mut pool);
+        assert_eq!(ingest.watermark(), 900);
+        assert_eq!(ingest.pending(), 2);
+
+        ingest.push(event(1_200)).unwrap();
+        ingest.advance(&mut track, &mut index, &mut pool);
+        assert_eq!(ingest.pending(), 1);
+        assert_eq!(
+            ingest.push(event(1_050)),
+            Err(LateEvent {
+                timestamp: 1_050,
+                watermark: 1_100,
+            }),
+        );
+    }
+
+    #[test]
+    fn prop_test_compressed_aggregate_by_steps() {
+        let mut pool = BlockPool::new();
+        let mut track = Track::new();
+        let rng = Rng::with_seed(0xDEAD_BEEF);
+        add_dummy_events(&mut track, &mut pool, &rng, 325);
+
+        let compressed = CompressedBlockPool::from_pool(&pool);
+        let index = IForestIndex::<u64, LongestEvent<u64>>::build(&track, &pool);
+        let compressed_index = IForestIndex::<u64, LongestEvent<u64>>::build(&track, &compressed);
+        assert_eq!(index.values, compressed_index.values);
+
+        let time_bounds = 0..=(track.end_time(&pool).unwrap() + 100_000);
+        for _ in 0..10_000 {
+            let t1 = rng.u64(time_bounds.clone());
+            let t2 = rng.u64(time_bounds.clone());
+            let t_range = if t2 > t1 { t1..t2 } else { t2..t1 };
+            let step = ((t_range.end - t_range.start) / rng.u64(1..10)) + rng.u64(1..100);
+
+            let res1 = crate::aggregate_by_steps(
+                &pool,
+                track.block_locations(),
+                &index,
+                t_range.clone(),
+                step,
+                |_| true,
+            );
+
+            let res2 = crate::aggregate_by_steps(
+                &compressed,
+                track.block_locations(),
+                &index,
+                t_range.clone(),
+                step,
+                |_| true,
+            );
+
+            assert_eq!(res1, res2, "failed for {:?} - {}", t_range, step);
+        }
+    }
+
+    #[test]
+    fn prop_test_aggregate_range() {
+        let mut pool = BlockPool::new();
+        let mut track = Track::new();
+        let rng = Rng::with_seed(0xDEAD_BEEF);
+        add_dummy_events(&mut track, &mut pool, &rng, EVENTS_PER_BLOCK * 20 + 7);
+        let info = TrackInfo::build(track, &pool);
+
+        let time_bounds = 0..=(info.track.end_time(&pool).unwrap() + 100_000);
+        for _ in 0..1_000 {
+            let t1 = rng.u64(time_bounds.clone());
+            let t2 = rng.u64(time_bounds.clone());
+            let t_range = if t2 > t1 { t1..t2 } else { t2..t1 };
+
+            let expected = info
+                .track
+                .events(&pool)
+                .filter(|event| t_range.contains(&event.timestamp.unpack()))
+                .fold(EventStats::empty(), |stats, event| {
+                    stats.join(&EventStats::from_event(event))
+                });
+            let stats = info.stats(&pool, t_range.clone());
+
+            assert_eq!(stats.count, expected.count, "failed for {:?}", t_range);
+            assert_eq!(stats.busy, expected.busy, "failed for {:?}", t_range);
+            assert_eq!(
+                stats.longest.0.map(|event| event.duration),
+                expected.longest.0.map(|event| event.duration),
+                "failed for {:?}",
+                t_range,
+            );
+        }
+    }
+
+    #[test]
+    fn prop_test_busy_within() {
         let mut pool = BlockPool::new();
         let mut track = Track::new();
-        let rng = Rng::new();
-        track.add_dummy_events(&mut pool, &rng, 325);
+        let rng = Rng::with_seed(0xDEAD_BEEF);
+        add_dummy_events(&mut track, &mut pool, &rng, EVENTS_PER_BLOCK * 20 + 7);
+        let info = TrackInfo::build(track, &pool);
 
-        let index = IForestIndex::<EventCount>::build(&track, &pool);
-        let index_vals = index.values.iter().map(|x| x.0).collect::<Vec<_>>();
-        println!("index: {:?}", index_vals);
+        let time_bounds = 0..=(info.track.end_time(&pool).unwrap() + 100_000);
+        for _ in 0..1_000 {
+            let t1 = rng.u64(time_bounds.clone());
+            let t2 = rng.u64(time_bounds.clone());
+            let t_range = if t2 > t1 { t1..t2 } else { t2..t1 };
+
+            let expected: Nanos = info
+                .track
+                .events(&pool)
+                .map(|event| {
+                    let start = event.timestamp.unpack();
+                    let end = start + event.duration.unpack();
+                    end.min(t_range.end)
+                        .saturating_sub(start.max(t_range.start))
+                })
+                .sum();
 
-        // assert!(false);
-        // TODO some test
+            assert_eq!(
+                info.busy_within(&pool, t_range.clone()),
+                expected,
+                "failed for {:?}",
+                t_range,
+            );
+        }
     }
 
     #[test]
@@ -253,48 +941,49 @@ mod tests {
         let mut track = Track::new();
         let ev_ts = &[10, 15, 20, 100, 101, 150, 170];
         for t in ev_ts {
-            track.push(
-                &mut pool,
-                TraceEvent {
-                    kind: 0,
-                    timestamp: PackedNanos::new(*t),
-                    duration: PackedNanos::new(0),
-                },
-            );
+            track
+                .push(
+                    &mut pool,
+                    TraceEvent {
+                        kind: 0u64,
+                        timestamp: PackedNanos::new(*t),
+                        duration: PackedNanos::new(0),
+                    },
+                )
+                .unwrap();
         }
 
         let span = 13..150;
-        let res = crate::aggregate_by_steps_unindexed::<EventSum>(
+        let res = crate::aggregate_by_steps_unindexed::<_, EventSum, _, _>(
             &pool,
-            &track.block_locations,
+            track.block_locations(),
             span,
             10,
+            |_| true,
         );
         let res_ts = res.iter().map(|x| x.0).collect::<Vec<_>>();
-        assert_eq!(
-            &res_ts[..],
-            &[10, 35, 0, 0, 0, 0, 0, 0, 0, 201, 0, 0, 0, 0, 150]
-        );
+
+        // Steps without any events aren't produced
+        assert_eq!(&res_ts[..], &[10, 35, 201, 150]);
     }
 
     #[test]
     fn prop_test_range_query() {
         let mut pool = BlockPool::new();
         let mut track = Track::new();
-        let rng = Rng::new();
-        track.add_dummy_events(&mut pool, &rng, 325);
+        let rng = Rng::with_seed(0xDEAD_BEEF);
+        add_dummy_events(&mut track, &mut pool, &rng, 325);
 
-        let index = IForestIndex::<EventCount>::build(&track, &pool);
-        // let index_vals = index.vals.iter().map(|x| x.0).collect::<Vec<_>>();
-        // println!("index: {:?}", index_vals);
+        let index = IForestIndex::<u64, EventCount>::build(&track, &pool);
+        let locations = track.block_locations();
 
         for _ in 0..100_000 {
-            let start = rng.usize(..=track.block_locations.len());
-            let end = rng.usize(start..=track.block_locations.len());
+            let start = rng.usize(..=locations.len());
+            let end = rng.usize(start..=locations.len());
             let EventCount(count) = index.range_query(start..end);
-            let correct: usize = track.block_locations[start..end]
+            let correct: Nanos = locations[start..end]
                 .iter()
-                .map(|i| pool.blocks[*i as usize].len as usize)
+                .map(|i| pool.blocks[*i as usize].len() as Nanos)
                 .sum();
             assert_eq!(count, correct, "failed for {}..{}", start, end);
         }
@@ -304,10 +993,10 @@ mod tests {
     fn prop_test_aggregate_by_steps() {
         let mut pool = BlockPool::new();
         let mut track = Track::new();
-        let rng = Rng::new();
-        track.add_dummy_events(&mut pool, &rng, 325);
+        let rng = Rng::with_seed(0xDEAD_BEEF);
+        add_dummy_events(&mut track, &mut pool, &rng, 325);
 
-        let index = IForestIndex::<EventSum>::build(&track, &pool);
+        let index = IForestIndex::<u64, EventSum>::build(&track, &pool);
 
         let time_bounds = 0..=(track.end_time(&pool).unwrap() + 100_000);
         for _ in 0..100_000 {
@@ -315,24 +1004,124 @@ mod tests {
             let t2 = rng.u64(time_bounds.clone());
             let t_range = if t2 > t1 { t1..t2 } else { t2..t1 };
             let range_size = t_range.end - t_range.start;
-            let step = (range_size / rng.u64(1..10)) + rng.u64(0..100);
+            let step = (range_size / rng.u64(1..10)) + rng.u64(1..100);
 
-            let res1 = crate::aggregate_by_steps::<EventSum>(
+            let res1 = crate::aggregate_by_steps::<_, EventSum, _, _>(
                 &pool,
-                &track.block_locations,
+                track.block_locations(),
                 &index,
                 t_range.clone(),
                 step,
+                |_| true,
             );
 
-            let res2 = crate::aggregate_by_steps_unindexed::<EventSum>(
+            let res2 = crate::aggregate_by_steps_unindexed::<_, EventSum, _, _>(
+                &pool,
+                track.block_locations(),
+                t_range.clone(),
+                step,
+                |_| true,
+            );
+
+            assert_eq!(res1, res2, "failed for {:?} - {}", t_range, step);
+        }
+    }
+
+    #[test]
+    fn prop_test_aggregate_filtered_by_steps() {
+        let mut pool = BlockPool::new();
+        let mut track = Track::new();
+        let rng = Rng::with_seed(0xDEAD_BEEF);
+        add_dummy_events(&mut track, &mut pool, &rng, 325);
+
+        let time_bounds = 0..=(track.end_time(&pool).unwrap() + 100_000);
+        for _ in 0..10_000 {
+            let t1 = rng.u64(time_bounds.clone());
+            let t2 = rng.u64(time_bounds.clone());
+            let t_range = if t2 > t1 { t1..t2 } else { t2..t1 };
+            let range_size = t_range.end - t_range.start;
+            let step = (range_size / rng.u64(1..10)) + rng.u64(1..100);
+            let lookback = rng.u64(0..100_000);
+            let divisor = rng.u64(2..5);
+            let filter = |event: &TraceEvent<u64>| !event.kind.is_multiple_of(divisor);
+
+            let res1 = crate::aggregate_filtered_by_steps::<_, EventSum, _, _>(
                 &pool,
-                &track.block_locations,
+                track.block_locations(),
+                t_range.clone(),
+                step,
+                lookback,
+                filter,
+            );
+
+            // The same as aggregating a track of only the events that pass
+            let scan_from = t_range.start.saturating_sub(lookback);
+            let mut filtered_pool = BlockPool::new();
+            let filtered = Track::from_unsorted(
+                &mut filtered_pool,
+                track
+                    .events(&pool)
+                    .filter(|event| event.timestamp.unpack() >= scan_from && filter(event))
+                    .cloned(),
+            );
+            let res2 = crate::aggregate_by_steps_unindexed::<_, EventSum, _, _>(
+                &filtered_pool,
+                filtered.block_locations(),
                 t_range.clone(),
                 step,
+                |_| true,
             );
 
             assert_eq!(res1, res2, "failed for {:?} - {}", t_range, step);
         }
     }
+
+    #[test]
+    fn packed_nanos_overflow() {
+        assert_eq!(
+            PackedNanos::try_new(PackedNanos::MAX).unwrap().unpack(),
+            PackedNanos::MAX
+        );
+        assert_eq!(
+            PackedNanos::try_new(PackedNanos::MAX + 1),
+            Err(PackError::Overflow {
+                value: PackedNanos::MAX + 1,
+            }),
+        );
+    }
+
+    #[test]
+    fn clock_packs_relative_to_epoch() {
+        // Roughly 2021 as nanoseconds since the unix epoch
+        let epoch = 1_617_000_000_000_000_000;
+        let clock = TraceClock::for_span(epoch..epoch + 10_000);
+        assert_eq!(clock.resolution, Resolution::Nanos);
+
+        let packed = clock.pack(epoch + 1234).unwrap();
+        assert_eq!(packed.unpack(), 1234);
+        assert_eq!(clock.unpack(packed), epoch + 1234);
+        assert_eq!(
+            clock.pack(epoch - 1),
+            Err(PackError::BeforeEpoch {
+                timestamp: epoch - 1,
+                epoch,
+            }),
+        );
+        assert!(clock.pack(epoch + PackedNanos::MAX + 1).is_err());
+    }
+
+    #[test]
+    fn clock_switches_to_micros_for_long_spans() {
+        let week = 7 * 24 * 60 * 60 * 1_000_000_000;
+        let clock = TraceClock::for_span(0..week);
+        assert_eq!(clock.resolution, Resolution::Micros);
+
+        let packed = clock.pack(week).unwrap();
+        assert_eq!(clock.unpack(packed), week);
+
+        // Durations round up so they never collapse to nothing
+        let duration = clock.pack_duration(1).unwrap();
+        assert_eq!(clock.unpack_duration(duration), 1_000);
+        assert_eq!(clock.elapsed(clock.ticks(5_500)), 5_000);
+    }
 }
@@ -0,0 +1,231 @@
+use core::ops::{Index, Range};
+use std::collections::HashMap;
+
+/// An interned string within a `StringTable`
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(transparent)]
+pub struct StrId(pub u32);
+
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StringTable {
+    strings: Vec<Box<str>>,
+    lookup: HashMap<Box<str>, StrId>,
+}
+
+impl StringTable {
+    pub fn new() -> Self {
+        Self {
+            strings: Vec::new(),
+            lookup: HashMap::new(),
+        }
+    }
+
+    pub fn intern(&mut self, string: &str) -> StrId {
+        if let Some(&id) = self.lookup.get(string) {
+            return id;
+        }
+
+        let id = StrId(self.strings.len() as u32);
+        self.strings.push(Box::from(string));
+        self.lookup.insert(Box::from(string), id);
+        id
+    }
+
+    pub fn get(&self, id: StrId) -> Option<&str> {
+        self.strings.get(id.0 as usize).map(|string| &**string)
+    }
+
+    pub fn find(&self, string: &str) -> Option<StrId> {
+        self.lookup.get(string).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+impl Index<StrId> for StringTable {
+    type Output = str;
+
+    fn index(&self, id: StrId) -> &str {
+        &self.strings[id.0 as usize]
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ArgValue {
+    U64(u64),
+    I64(i64),
+    F64(f64),
+    Str(StrId),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Arg {
+    pub key: StrId,
+    pub value: ArgValue,
+}
+
+impl Arg {
+    pub const fn new(key: StrId, value: ArgValue) -> Self {
+        Self { key, value }
+    }
+}
+
+/// A reference to an event's payload, used as the `kind` of a `TraceEvent`
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(transparent)]
+pub struct PayloadId(pub u32);
+
+/// A borrowed view of a single payload within a `PayloadPool`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Payload<'a> {
+    pub name: StrId,
+    pub worker: u32,
+    pub args: &'a [Arg],
+}
+
+impl<'a> Payload<'a> {
+    pub fn arg(&self, key: StrId) -> Option<ArgValue> {
+        self.args
+            .iter()
+            .find(|arg| arg.key == key)
+            .map(|arg| arg.value)
+    }
+}
+
+/// Per-event payloads stored column-wise so that the hot `TraceEvent`s
+/// only carry a `PayloadId`
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PayloadPool {
+    names: Vec<StrId>,
+    workers: Vec<u32>,
+    // `arg_offsets[i]..arg_offsets[i + 1]` are the args of payload `i`
+    arg_offsets: Vec<u32>,
+    args: Vec<Arg>,
+}
+
+impl PayloadPool {
+    pub fn new() -> Self {
+        Self {
+            names: Vec::new(),
+            workers: Vec::new(),
+            arg_offsets: vec![0],
+            args: Vec::new(),
+        }
+    }
+
+    pub fn push<I>(&mut self, name: StrId, worker: u32, args: I) -> PayloadId
+    where
+        I: IntoIterator<Item = Arg>,
+    {
+        let id = PayloadId(self.names.len() as u32);
+
+        self.names.push(name);
+        self.workers.push(worker);
+        self.args.extend(args);
+        self.arg_offsets.push(self.args.len() as u32);
+
+        id
+    }
+
+    pub fn get(&self, id: PayloadId) -> Option<Payload<'_>> {
+        let idx = id.0 as usize;
+        if idx >= self.names.len() {
+            return None;
+        }
+
+        Some(Payload {
+            name: self.names[idx],
+            worker: self.workers[idx],
+            args: &self.args[self.arg_range(idx)],
+        })
+    }
+
+    pub fn name(&self, id: PayloadId) -> StrId {
+        self.names[id.0 as usize]
+    }
+
+    pub fn worker(&self, id: PayloadId) -> u32 {
+        self.workers[id.0 as usize]
+    }
+
+    pub fn args(&self, id: PayloadId) -> &[Arg] {
+        &self.args[self.arg_range(id.0 as usize)]
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    fn arg_range(&self, idx: usize) -> Range<usize> {
+        self.arg_offsets[idx] as usize..self.arg_offsets[idx + 1] as usize
+    }
+}
+
+impl Default for PayloadPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Arg, ArgValue, PayloadId, PayloadPool, StringTable};
+
+    #[test]
+    fn interning_dedups() {
+        let mut strings = StringTable::new();
+        let join = strings.intern("join");
+        let map = strings.intern("map");
+
+        assert_eq!(strings.intern("join"), join);
+        assert_ne!(join, map);
+        assert_eq!(&strings[map], "map");
+        assert_eq!(strings.find("join"), Some(join));
+        assert_eq!(strings.find("reduce"), None);
+        assert_eq!(strings.len(), 2);
+    }
+
+    #[test]
+    fn payload_args_round_trip() {
+        let mut strings = StringTable::new();
+        let mut payloads = PayloadPool::new();
+        let (name, key) = (strings.intern("join"), strings.intern("event_id"));
+
+        let first = payloads.push(name, 0, vec![Arg::new(key, ArgValue::U64(10))]);
+        let second = payloads.push(name, 3, None);
+        let third = payloads.push(
+            name,
+            1,
+            vec![
+                Arg::new(key, ArgValue::U64(12)),
+                Arg::new(name, ArgValue::I64(-1)),
+            ],
+        );
+
+        assert_eq!(payloads.len(), 3);
+        assert_eq!(
+            payloads.get(first).unwrap().arg(key),
+            Some(ArgValue::U64(10))
+        );
+        assert!(payloads.args(second).is_empty());
+        assert_eq!(payloads.worker(second), 3);
+        assert_eq!(payloads.args(third).len(), 2);
+        assert_eq!(payloads.get(PayloadId(3)), None);
+    }
+}
@@ -0,0 +1,576 @@
+//! A renderer-agnostic timeline layout. Traces are laid out into a list of
+//! `DrawCommand`s which any `Renderer` can draw, so the web and native front
+//! ends share everything except the final drawing calls
+
+mod svg;
+
+pub use svg::SvgRenderer;
+
+use crate::{
+    index::LongestEvent,
+    trace::{BlockStore, Nanos, TraceEvent},
+    Trace,
+};
+use core::{f64::consts::FRAC_PI_4, fmt, ops::Range};
+use std::fmt::Debug;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Point {
+    pub const fn new(x: f64, y: f64) -> Self {
+        Self { x, y }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rect {
+    pub const fn new(x: f64, y: f64, width: f64, height: f64) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn contains(&self, point: Point) -> bool {
+        point.x >= self.x
+            && point.x <= self.x + self.width
+            && point.y >= self.y
+            && point.y <= self.y + self.height
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub const BLACK: Self = Self::rgb8(0x00, 0x00, 0x00);
+    pub const WHITE: Self = Self::rgb8(0xFF, 0xFF, 0xFF);
+
+    pub const fn rgb8(r: u8, g: u8, b: u8) -> Self {
+        Self::rgba8(r, g, b, 0xFF)
+    }
+
+    pub const fn rgba8(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+}
+
+/// Formats the color as css
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.a == 0xFF {
+            write!(f, "#{:02X}{:02X}{:02X}", self.r, self.g, self.b)
+        } else {
+            write!(
+                f,
+                "rgba({}, {}, {}, {:.3})",
+                self.r,
+                self.g,
+                self.b,
+                self.a as f64 / 255.0,
+            )
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextAlign {
+    Start,
+    Center,
+    End,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrawCommand {
+    FillRect {
+        rect: Rect,
+        /// The radius of the rect's rounded corners
+        radius: f64,
+        color: Color,
+    },
+    Line {
+        from: Point,
+        to: Point,
+        color: Color,
+        width: f64,
+        /// The lengths of the dashes and the gaps between them
+        dash: Option<(f64, f64)>,
+    },
+    /// Text positioned by the top of its line
+    Text {
+        text: String,
+        at: Point,
+        size: f64,
+        color: Color,
+        align: TextAlign,
+        /// Clockwise rotation around `at` in radians
+        angle: f64,
+    },
+}
+
+/// Something that can draw `DrawCommand`s
+pub trait Renderer {
+    type Error;
+
+    fn draw(&mut self, command: &DrawCommand) -> Result<(), Self::Error>;
+
+    fn render(&mut self, commands: &[DrawCommand]) -> Result<(), Self::Error> {
+        for command in commands {
+            self.draw(command)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Maps times within a view to x positions
+#[derive(Debug, Clone, Copy)]
+pub struct ViewMap {
+    start: f64,
+    scale: f64,
+}
+
+impl ViewMap {
+    pub fn new(range: &Range<Nanos>, width: f64) -> Self {
+        Self {
+            start: range.start as f64,
+            scale: width / (range.end.saturating_sub(range.start).max(1) as f64),
+        }
+    }
+
+    pub fn to_x(&self, time: Nanos) -> f64 {
+        ((time as f64) - self.start) * self.scale
+    }
+
+    pub fn to_width(&self, duration: Nanos) -> f64 {
+        duration as f64 * self.scale
+    }
+
+    pub fn to_ns(&self, x: f64) -> f64 {
+        self.start + (x / self.scale)
+    }
+}
+
+/// Buckets a view into steps of at least two pixels so that zoomed out
+/// views only draw the longest event of each step
+#[derive(Debug, Clone, Copy)]
+pub struct ViewQuant {
+    pub time_step: Nanos,
+}
+
+impl ViewQuant {
+    pub fn new(range: &Range<Nanos>, width: f64) -> Self {
+        let ns_per_px = range.end.saturating_sub(range.start) / (width.max(1.0) as u64);
+        let min_event_px = 2;
+        let step = 1u64
+            .checked_shl(64 - (ns_per_px * min_event_px).leading_zeros())
+            .unwrap_or(u64::MAX);
+
+        Self {
+            time_step: u64::max(1, step),
+        }
+    }
+
+    pub fn round_down(&self, x: Nanos) -> Nanos {
+        x - (x % self.time_step)
+    }
+
+    pub fn quantize(&self, range: &Range<Nanos>) -> Range<Nanos> {
+        self.round_down(range.start)..self.round_down(range.end).saturating_add(self.time_step)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimelineStyle {
+    pub background: Color,
+    pub axis: Color,
+    pub tick: Color,
+    pub grid: Color,
+    pub event: Color,
+    pub text: Color,
+    pub font_size: f64,
+    pub label_size: f64,
+    /// The size of collapsed lanes' labels
+    pub small_label_size: f64,
+    pub radius: f64,
+}
+
+impl Default for TimelineStyle {
+    fn default() -> Self {
+        Self {
+            background: Color::rgb8(0xF7, 0xF7, 0xF7),
+            axis: Color::BLACK,
+            tick: Color::rgb8(0x30, 0x30, 0x30),
+            grid: Color::rgb8(0xE6, 0xE6, 0xE6),
+            event: Color::rgb8(0x95, 0xCC, 0xE8),
+            text: Color::BLACK,
+            font_size: 16.0,
+            label_size: 14.0,
+            small_label_size: 10.0,
+            radius: 3.0,
+        }
+    }
+}
+
+/// A track's position on the timeline
+#[derive(Debug, Clone, PartialEq)]
+pub struct LaneRow {
+    /// The track's index within the trace
+    pub track: usize,
+    /// The top of the row relative to the top of the graph
+    pub y: f64,
+    pub height: f64,
+    pub label: String,
+    /// Collapsed rows are too short for their events' labels
+    pub collapsed: bool,
+}
+
+/// Where the timeline's parts go, in pixels
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimelineLayout {
+    pub width: f64,
+    pub height: f64,
+    /// The area events are drawn in, lane labels go to its left and time
+    /// labels go below it
+    pub graph: Rect,
+    pub style: TimelineStyle,
+}
+
+/// An event that was drawn, used for hit testing
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventBox<K> {
+    pub track: usize,
+    pub rect: Rect,
+    pub event: TraceEvent<K>,
+}
+
+/// Decides which events are drawn, checked against every event in view
+pub type EventFilter<'a, K> = &'a dyn Fn(&TraceEvent<K>) -> bool;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scene<K> {
+    pub commands: Vec<DrawCommand>,
+    pub events: Vec<EventBox<K>>,
+}
+
+/// The number of time steps between labeled ticks on the time axis
+const STEPS_PER_TICK: u64 = 25;
+
+/// The rough width of a character relative to the font's size, used to
+/// decide whether a label fits within its event
+const CHAR_WIDTH: f64 = 0.6;
+
+impl TimelineLayout {
+    /// Lays out the part of `trace` within `view` (in ticks) and `label` names
+    /// the events that have room for one. Each step draws its longest event
+    /// matching `event_filter`, or every event if it's `None`. `longest_filter`
+    /// is checked against each step's longest event instead, which is only right
+    /// for filters that pass every event longer than one they pass (like a minimum
+    /// duration) but lets the zoom index skip over blocks
+    pub fn layout<K, P, F, L>(
+        &self,
+        trace: &Trace<K, P>,
+        rows: &[LaneRow],
+        view: &Range<Nanos>,
+        longest_filter: F,
+        event_filter: Option<EventFilter<'_, K>>,
+        label: L,
+    ) -> Scene<K>
+    where
+        K: Default + Clone + Debug + PartialEq,
+        P: BlockStore<K>,
+        F: Fn(&TraceEvent<K>) -> bool,
+        L: Fn(&TraceEvent<K>) -> String,
+    {
+        let mut scene = Scene {
+            commands: Vec::new(),
+            events: Vec::new(),
+        };
+
+        let map = ViewMap::new(view, self.graph.width);
+        let quant = ViewQuant::new(view, self.graph.width);
+
+        self.layout_axes(&mut scene.commands, trace, view, &map, &quant);
+        self.layout_rows(&mut scene.commands, rows);
+
+        for row in rows {
+            let track = match trace.tracks.get(row.track) {
+                Some(track) => track,
+                None => continue,
+            };
+
+            let visible_events: Vec<LongestEvent<K>> = match event_filter {
+                Some(event_filter) => {
+                    let blocks = track.track.block_locations();
+                    let lookback = track
+                        .zoom_index
+                        .range_query(0..blocks.len())
+                        .0
+                        .map_or(0, |event| event.duration.unpack());
+
+                    crate::aggregate_filtered_by_steps(
+                        &trace.pool,
+                        blocks,
+                        quant.quantize(view),
+                        quant.time_step,
+                        lookback,
+                        |event| event_filter(event) && longest_filter(event),
+                    )
+                }
+                None => crate::aggregate_by_steps(
+                    &trace.pool,
+                    track.track.block_locations(),
+                    &track.zoom_index,
+                    quant.quantize(view),
+                    quant.time_step,
+                    |event: &LongestEvent<K>| event.0.as_ref().is_some_and(&longest_filter),
+                ),
+            };
+
+            for event in visible_events.into_iter().filter_map(|event| event.0) {
+                let timestamp = event.timestamp.unpack();
+                let duration = event.duration.unpack();
+
+                // Events shorter than a step are widened to fill it
+                let (start, duration) = if duration > quant.time_step {
+                    (timestamp, duration)
+                } else {
+                    (quant.round_down(timestamp), quant.time_step)
+                };
+
+                // Clip events to the graph so they don't cover the lane labels
+                let left = map.to_x(start).max(0.0);
+                let right = (map.to_x(start) + map.to_width(duration)).min(self.graph.width);
+                if right <= left {
+                    continue;
+                }
+
+                let rect = Rect::new(
+                    self.graph.x + left,
+                    self.graph.y + row.y + 1.0,
+                    right - left,
+                    row.height - 2.0,
+                );
+                scene.commands.push(DrawCommand::FillRect {
+                    rect,
+                    radius: self.style.radius,
+                    color: self.style.event,
+                });
+
+                if !row.collapsed {
+                    let text = label(&event);
+                    let text_width =
+                        text.chars().count() as f64 * self.style.label_size * CHAR_WIDTH;
+
+                    if text_width + 10.0 <= rect.width {
+                        scene.commands.push(DrawCommand::Text {
+                            text,
+                            at: Point::new(
+                                rect.x + 5.0,
+                                rect.y + (rect.height - self.style.label_size) / 2.0,
+                            ),
+                            size: self.style.label_size,
+                            color: self.style.text,
+                            align: TextAlign::Start,
+                            angle: 0.0,
+                        });
+                    }
+                }
+
+                scene.events.push(EventBox {
+                    track: row.track,
+                    rect,
+                    event,
+                });
+            }
+        }
+
+        scene
+    }
+
+    fn layout_axes<K, P>(
+        &self,
+        commands: &mut Vec<DrawCommand>,
+        trace: &Trace<K, P>,
+        view: &Range<Nanos>,
+        map: &ViewMap,
+        quant: &ViewQuant,
+    ) where
+        K: Default + Clone,
+        P: BlockStore<K>,
+    {
+        let style = &self.style;
+        let graph = self.graph;
+        let bottom = graph.y + graph.height;
+
+        commands.push(DrawCommand::FillRect {
+            rect: Rect::new(0.0, 0.0, self.width, self.height),
+            radius: 0.0,
+            color: style.background,
+        });
+
+        // Ticks are labeled with the time since the trace started
+        let trace_start = trace.time_bounds().map_or(0, |bounds| bounds.start);
+
+        // Grid lines and tick marks, the first tick is skipped since it'd
+        // land on the vertical axis
+        let tick_step = quant.time_step.saturating_mul(STEPS_PER_TICK);
+        let first_tick = quant.round_down(view.start).saturating_add(tick_step);
+        let ticks = (0..)
+            .map(|idx: u64| first_tick.saturating_add(idx.saturating_mul(tick_step)))
+            .take_while(|&time| time < view.end && time != u64::MAX);
+
+        for time in ticks {
+            let x = graph.x + map.to_x(time);
+
+            commands.push(DrawCommand::Line {
+                from: Point::new(x, graph.y),
+                to: Point::new(x, bottom),
+                color: style.grid,
+                width: 1.0,
+                dash: Some((2.0, 4.0)),
+            });
+            commands.push(DrawCommand::Line {
+                from: Point::new(x, bottom),
+                to: Point::new(x, bottom + 7.0),
+                color: style.tick,
+                width: 2.0,
+                dash: None,
+            });
+
+            // Rotate the labels so they don't overlap
+            commands.push(DrawCommand::Text {
+                text: format_duration(trace.clock.elapsed(time.saturating_sub(trace_start))),
+                at: Point::new(x, bottom + 12.0),
+                size: style.font_size,
+                color: style.tick,
+                align: TextAlign::Start,
+                angle: FRAC_PI_4,
+            });
+        }
+
+        // The axes themselves
+        commands.push(DrawCommand::Line {
+            from: Point::new(graph.x, graph.y),
+            to: Point::new(graph.x, bottom),
+            color: style.axis,
+            width: 2.0,
+            dash: None,
+        });
+        commands.push(DrawCommand::Line {
+            from: Point::new(graph.x, bottom),
+            to: Point::new(graph.x + graph.width + 20.0, bottom),
+            color: style.axis,
+            width: 2.0,
+            dash: None,
+        });
+    }
+
+    fn layout_rows(&self, commands: &mut Vec<DrawCommand>, rows: &[LaneRow]) {
+        let style = &self.style;
+        let graph = self.graph;
+
+        for (idx, row) in rows.iter().enumerate() {
+            let bottom = graph.y + row.y + row.height;
+
+            if idx + 1 != rows.len() {
+                commands.push(DrawCommand::Line {
+                    from: Point::new(graph.x, bottom),
+                    to: Point::new(graph.x - 5.0, bottom),
+                    color: style.axis,
+                    width: 2.0,
+                    dash: None,
+                });
+            }
+
+            let size = if row.collapsed {
+                style.small_label_size
+            } else {
+                style.label_size
+            };
+            commands.push(DrawCommand::Text {
+                text: row.label.clone(),
+                at: Point::new(graph.x - 8.0, graph.y + row.y + (row.height - size) / 2.0),
+                size,
+                color: style.text,
+                align: TextAlign::End,
+                angle: 0.0,
+            });
+        }
+    }
+}
+
+/// Formats a number of nanoseconds with the largest unit that keeps it above one
+pub fn format_duration(nanos: Nanos) -> String {
+    const UNITS: [(Nanos, &str); 4] = [
+        (1_000_000_000, "s"),
+        (1_000_000, "ms"),
+        (1_000, "us"),
+        (1, "ns"),
+    ];
+
+    let (scale, unit) = UNITS
+        .iter()
+        .copied()
+        .find(|&(scale, _)| nanos >= scale)
+        .unwrap_or((1, "ns"));
+
+    if scale == 1 {
+        format!("{}ns", nanos)
+    } else {
+        let value = nanos as f64 / scale as f64;
+        let value = format!("{:.2}", value);
+        format!(
+            "{}{}",
+            value.trim_end_matches('0').trim_end_matches('.'),
+            unit
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_duration, ViewMap, ViewQuant};
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(format_duration(0), "0ns");
+        assert_eq!(format_duration(999), "999ns");
+        assert_eq!(format_duration(1_500), "1.5us");
+        assert_eq!(format_duration(2_000_000), "2ms");
+        assert_eq!(format_duration(61_234_000_000), "61.23s");
+    }
+
+    #[test]
+    fn view_maps_round_trip() {
+        let view = 1_000..11_000;
+        let map = ViewMap::new(&view, 500.0);
+        assert_eq!(map.to_x(1_000), 0.0);
+        assert_eq!(map.to_x(11_000), 500.0);
+        assert_eq!(map.to_width(2_000), 100.0);
+        assert_eq!(map.to_ns(250.0), 6_000.0);
+
+        // Steps are powers of two covering at least two pixels
+        let quant = ViewQuant::new(&view, 500.0);
+        assert_eq!(quant.time_step, 64);
+        assert_eq!(quant.quantize(&view), 960..11_008);
+    }
+}
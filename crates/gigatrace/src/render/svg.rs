@@ -0,0 +1,379 @@
+use crate::render::{DrawCommand, Renderer, TextAlign};
+use core::fmt::{self, Write};
+
+/// Renders draw commands to a standalone svg document
+#[derive(Debug, Clone)]
+pub struct SvgRenderer {
+    svg: String,
+}
+
+impl SvgRenderer {
+    pub fn new(width: f64, height: f64) -> Self {
+        let mut svg = String::new();
+        write!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif">"#,
+            w = Num(width),
+            h = Num(height),
+        )
+        .expect("writing to a string can't fail");
+        svg.push('\n');
+
+        Self { svg }
+    }
+
+    /// Closes the document and returns it
+    pub fn finish(mut self) -> String {
+        self.svg.push_str("</svg>\n");
+        self.svg
+    }
+}
+
+impl Renderer for SvgRenderer {
+    type Error = fmt::Error;
+
+    fn draw(&mut self, command: &DrawCommand) -> fmt::Result {
+        let svg = &mut self.svg;
+
+        match command {
+            DrawCommand::FillRect {
+                rect,
+                radius,
+                color,
+            } => {
+                write!(
+                    svg,
+                    r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}""#,
+                    Num(rect.x),
+                    Num(rect.y),
+                    Num(rect.width),
+                    Num(rect.height),
+                    color,
+                )?;
+
+                let radius = radius.min(rect.width / 2.0).min(rect.height / 2.0);
+                if radius > 0.0 {
+                    write!(svg, r#" rx="{}""#, Num(radius))?;
+                }
+                svg.push_str("/>\n");
+            }
+
+            DrawCommand::Line {
+                from,
+                to,
+                color,
+                width,
+                dash,
+            } => {
+                write!(
+                    svg,
+                    r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-width="{}""#,
+                    Num(from.x),
+                    Num(from.y),
+                    Num(to.x),
+                    Num(to.y),
+                    color,
+                    Num(*width),
+                )?;
+
+                if let Some((dash, gap)) = dash {
+                    write!(svg, r#" stroke-dasharray="{} {}""#, Num(*dash), Num(*gap))?;
+                }
+                svg.push_str("/>\n");
+            }
+
+            DrawCommand::Text {
+                text,
+                at,
+                size,
+                color,
+                align,
+                angle,
+            } => {
+                let anchor = match align {
+                    TextAlign::Start => "start",
+                    TextAlign::Center => "middle",
+                    TextAlign::End => "end",
+                };
+
+                write!(
+                    svg,
+                    r#"<text x="{}" y="{}" font-size="{}" fill="{}" text-anchor="{}" dominant-baseline="hanging""#,
+                    Num(at.x),
+                    Num(at.y),
+                    Num(*size),
+                    color,
+                    anchor,
+                )?;
+
+                if *angle != 0.0 {
+                    write!(
+                        svg,
+                        r#" transform="rotate({} {} {})""#,
+                        Num(angle.to_degrees()),
+                        Num(at.x),
+                        Num(at.y),
+                    )?;
+                }
+
+                svg.push('>');
+                escape(svg, text);
+                svg.push_str("</text>\n");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats a number with at most two decimal places, keeping documents small and stable
+struct Num(f64);
+
+impl fmt::Display for Num {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rounded = (self.0 * 100.0).round() / 100.0;
+
+        // Avoid printing `-0`
+        if rounded == 0.0 {
+            f.write_str("0")
+        } else {
+            write!(f, "{}", rounded)
+        }
+    }
+}
+
+fn escape(svg: &mut String, text: &str) {
+    for char in text.chars() {
+        match char {
+            '&' => svg.push_str("&amp;"),
+            '<' => svg.push_str("&lt;"),
+            '>' => svg.push_str("&gt;"),
+            '"' => svg.push_str("&quot;"),
+            '\'' => svg.push_str("&apos;"),
+            char => svg.push(char),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SvgRenderer;
+    use crate::{
+        render::{
+            format_duration, Color, DrawCommand, LaneRow, Point, Rect, Renderer, TextAlign,
+            TimelineLayout, TimelineStyle,
+        },
+        trace::{PackedNanos, Resolution, TraceClock, TraceEvent, Track},
+        Trace, TrackInfo,
+    };
+
+    /// Two tracks of evenly spaced events with varying durations
+    fn trace() -> Trace<u64> {
+        let mut trace = Trace::new();
+
+        for track_idx in 0..2u64 {
+            let events = (0..40u64).map(|idx| TraceEvent {
+                kind: track_idx * 100 + idx,
+                timestamp: PackedNanos::new(idx * 25_000 + track_idx * 5_000),
+                duration: PackedNanos::new(1_000 + (idx % 5) * 4_000),
+            });
+
+            let track = Track::from_unsorted(&mut trace.pool, events);
+            let info = TrackInfo::build(track, &trace.pool);
+            trace.tracks.push(info);
+        }
+
+        trace
+    }
+
+    fn layout(width: f64) -> TimelineLayout {
+        TimelineLayout {
+            width,
+            height: 200.0,
+            graph: Rect::new(120.0, 5.0, width - 160.0, 64.0),
+            style: TimelineStyle::default(),
+        }
+    }
+
+    fn rows() -> Vec<LaneRow> {
+        vec![
+            LaneRow {
+                track: 0,
+                y: 0.0,
+                height: 27.0,
+                label: "Operator 1: Map".to_owned(),
+                collapsed: false,
+            },
+            LaneRow {
+                track: 1,
+                y: 27.0,
+                height: 10.0,
+                label: "Parked".to_owned(),
+                collapsed: true,
+            },
+            LaneRow {
+                track: 0,
+                y: 37.0,
+                height: 27.0,
+                label: "Operator 1 & <friends>".to_owned(),
+                collapsed: false,
+            },
+        ]
+    }
+
+    fn render(width: f64, view: std::ops::Range<u64>) -> String {
+        let scene = layout(width).layout(
+            &trace(),
+            &rows(),
+            &view,
+            |_| true,
+            Some(&|event: &TraceEvent<u64>| event.kind % 10 != 9),
+            |event| format_duration(event.duration.unpack()),
+        );
+
+        let mut svg = SvgRenderer::new(width, 200.0);
+        svg.render(&scene.commands).unwrap();
+        svg.finish()
+    }
+
+    #[test]
+    fn whole_trace() {
+        insta::assert_snapshot!(render(800.0, 0..1_000_000));
+    }
+
+    #[test]
+    fn zoomed_in() {
+        insta::assert_snapshot!(render(800.0, 100_000..180_000));
+    }
+
+    #[test]
+    fn zoomed_out() {
+        // Events are merged into steps when there are more than fit
+        insta::assert_snapshot!(render(300.0, 0..1_000_000));
+    }
+
+    #[test]
+    fn hit_boxes_match_drawn_events() {
+        let scene = layout(800.0).layout(
+            &trace(),
+            &rows(),
+            &(100_000..180_000),
+            |_| true,
+            None,
+            |_| String::new(),
+        );
+
+        let rects: Vec<_> = scene
+            .commands
+            .iter()
+            .filter_map(|command| match command {
+                DrawCommand::FillRect { rect, radius, .. } if *radius > 0.0 => Some(*rect),
+                _ => None,
+            })
+            .collect();
+        let boxes: Vec<_> = scene.events.iter().map(|event| event.rect).collect();
+        assert_eq!(rects, boxes);
+
+        let hit = scene.events.iter().find(|event| {
+            event
+                .rect
+                .contains(Point::new(event.rect.x + 1.0, event.rect.y + 1.0))
+        });
+        assert!(hit.is_some());
+    }
+
+    #[test]
+    fn filters_events_within_a_step() {
+        // Two workers' events fall into the same step, the longer one's worker
+        // is filtered out so the shorter one has to be drawn instead
+        let mut trace = Trace::new();
+        let events = vec![
+            TraceEvent {
+                kind: 0,
+                timestamp: PackedNanos::new(10_000),
+                duration: PackedNanos::new(100),
+            },
+            TraceEvent {
+                kind: 1,
+                timestamp: PackedNanos::new(10_050),
+                duration: PackedNanos::new(400),
+            },
+        ];
+        let track = Track::from_unsorted(&mut trace.pool, events);
+        let info = TrackInfo::build(track, &trace.pool);
+        trace.tracks.push(info);
+
+        let scene = layout(800.0).layout(
+            &trace,
+            &rows()[..1],
+            &(0..1_000_000),
+            |_| true,
+            Some(&|event: &TraceEvent<u64>| event.kind != 1),
+            |_| String::new(),
+        );
+
+        let kinds: Vec<_> = scene.events.iter().map(|event| event.event.kind).collect();
+        assert_eq!(kinds, vec![0]);
+    }
+
+    #[test]
+    fn labels_time_since_the_trace_started() {
+        // Timestamps are microseconds since the epoch, the trace starts well after it
+        let clock = TraceClock::new(1_600_000_000_000_000_000, Resolution::Micros);
+        let mut trace = Trace::with_clock(clock);
+        let events = (0..40u64).map(|idx| TraceEvent {
+            kind: idx,
+            timestamp: PackedNanos::new(5_000_000 + idx * 25_000),
+            duration: PackedNanos::new(1_000),
+        });
+        let track = Track::from_unsorted(&mut trace.pool, events);
+        let info = TrackInfo::build(track, &trace.pool);
+        trace.tracks.push(info);
+
+        let view = trace.time_bounds().unwrap();
+        let scene = layout(800.0).layout(
+            &trace,
+            &rows()[..1],
+            &view,
+            |_| true,
+            None,
+            |_| String::new(),
+        );
+
+        let labels: Vec<_> = scene
+            .commands
+            .iter()
+            .filter_map(|command| match command {
+                DrawCommand::Text { text, angle, .. } if *angle != 0.0 => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        // Rather than the time since the epoch
+        assert_eq!(labels.first(), Some(&"99.52ms"));
+        assert_eq!(labels.last(), Some(&"918.72ms"));
+    }
+
+    #[test]
+    fn escapes_and_rotates_text() {
+        let mut svg = SvgRenderer::new(10.0, 10.0);
+        svg.draw(&DrawCommand::Text {
+            text: "a < b & \"c\"".to_owned(),
+            at: Point::new(1.0, 2.5),
+            size: 12.0,
+            color: Color::rgba8(0xFF, 0, 0, 0x80),
+            align: TextAlign::Center,
+            angle: std::f64::consts::FRAC_PI_2,
+        })
+        .unwrap();
+
+        assert_eq!(
+            svg.finish(),
+            concat!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10" viewBox="0 0 10 10" font-family="sans-serif">"#,
+                "\n",
+                r#"<text x="1" y="2.5" font-size="12" fill="rgba(255, 0, 0, 0.502)" text-anchor="middle" dominant-baseline="hanging" transform="rotate(90 1 2.5)">a &lt; b &amp; &quot;c&quot;</text>"#,
+                "\n</svg>\n",
+            ),
+        );
+    }
+}
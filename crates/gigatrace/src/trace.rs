@@ -3,22 +3,68 @@ use core::{
     fmt::{self, Debug, Display},
     hash::{Hash, Hasher},
     marker::PhantomData,
+    mem,
+    ops::Range,
 };
+use std::{borrow::Cow, error::Error};
 use tinyvec::ArrayVec;
 
 pub type Nanos = u64;
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PackError {
+    /// The timestamp came before the epoch of the trace
+    BeforeEpoch { timestamp: Nanos, epoch: Nanos },
+    /// The value doesn't fit within the 48 bits of a `PackedNanos`
+    Overflow { value: Nanos },
+}
+
+impl Display for PackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::BeforeEpoch { timestamp, epoch } => write!(
+                f,
+                "timestamp {} is before the trace epoch of {}",
+                timestamp, epoch,
+            ),
+            Self::Overflow { value } => write!(
+                f,
+                "{} doesn't fit within 48 bits (the maximum is {})",
+                value,
+                PackedNanos::MAX,
+            ),
+        }
+    }
+}
+
+impl Error for PackError {}
+
 #[derive(Copy, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(transparent)]
 pub struct PackedNanos([u8; 6]);
 
 impl PackedNanos {
+    /// The largest value that can be packed, a little over 3 days worth of nanoseconds
+    pub const MAX: Nanos = (1 << 48) - 1;
+
+    /// Packs `ts`, only the lower 48 bits are kept so `try_new` should be used
+    /// for anything that could overflow
     pub const fn new(ts: Nanos) -> Self {
+        debug_assert!(ts <= Self::MAX, "packed timestamp overflowed 48 bits");
+
         let b = ts.to_le_bytes();
-        // assert!(b[6] == 0 && b[7] == 0);
         PackedNanos([b[0], b[1], b[2], b[3], b[4], b[5]])
     }
 
+    pub const fn try_new(ts: Nanos) -> Result<Self, PackError> {
+        if ts <= Self::MAX {
+            Ok(Self::new(ts))
+        } else {
+            Err(PackError::Overflow { value: ts })
+        }
+    }
+
     #[allow(clippy::many_single_char_names)]
     pub const fn unpack(self) -> Nanos {
         let [a, b, c, d, e, f] = self.0;
@@ -48,7 +94,7 @@ impl Eq for PackedNanos {}
 
 impl PartialOrd for PackedNanos {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.unpack().partial_cmp(&other.unpack())
+        Some(self.cmp(other))
     }
 }
 
@@ -64,7 +110,91 @@ impl Hash for PackedNanos {
     }
 }
 
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Resolution {
+    #[default]
+    Nanos,
+    Micros,
+}
+
+impl Resolution {
+    pub const fn nanos_per_tick(self) -> Nanos {
+        match self {
+            Self::Nanos => 1,
+            Self::Micros => 1_000,
+        }
+    }
+}
+
+/// Maps absolute timestamps onto the ticks stored within a trace's
+/// `PackedNanos`, ticks are relative to `epoch` and are `resolution` long
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TraceClock {
+    pub epoch: Nanos,
+    pub resolution: Resolution,
+}
+
+impl TraceClock {
+    pub const fn new(epoch: Nanos, resolution: Resolution) -> Self {
+        Self { epoch, resolution }
+    }
+
+    /// Creates a clock starting at `span.start`, microsecond resolution is used
+    /// if the span is too long to fit within 48 bits of nanoseconds
+    pub fn for_span(span: Range<Nanos>) -> Self {
+        let resolution = if span.end.saturating_sub(span.start) > PackedNanos::MAX {
+            Resolution::Micros
+        } else {
+            Resolution::Nanos
+        };
+
+        Self::new(span.start, resolution)
+    }
+
+    pub fn pack(&self, timestamp: Nanos) -> Result<PackedNanos, PackError> {
+        let relative = timestamp
+            .checked_sub(self.epoch)
+            .ok_or(PackError::BeforeEpoch {
+                timestamp,
+                epoch: self.epoch,
+            })?;
+
+        PackedNanos::try_new(self.ticks(relative))
+    }
+
+    /// Durations are rounded up so that short events don't vanish at coarser resolutions
+    pub fn pack_duration(&self, duration: Nanos) -> Result<PackedNanos, PackError> {
+        PackedNanos::try_new(duration.div_ceil(self.resolution.nanos_per_tick()))
+    }
+
+    pub fn unpack(&self, timestamp: PackedNanos) -> Nanos {
+        self.absolute(timestamp.unpack())
+    }
+
+    pub fn unpack_duration(&self, duration: PackedNanos) -> Nanos {
+        self.elapsed(duration.unpack())
+    }
+
+    /// Converts a number of nanoseconds into ticks, rounding down
+    pub const fn ticks(&self, nanos: Nanos) -> Nanos {
+        nanos / self.resolution.nanos_per_tick()
+    }
+
+    /// Converts a number of ticks into nanoseconds
+    pub const fn elapsed(&self, ticks: Nanos) -> Nanos {
+        ticks.saturating_mul(self.resolution.nanos_per_tick())
+    }
+
+    /// Converts a tick offset into an absolute timestamp
+    pub const fn absolute(&self, ticks: Nanos) -> Nanos {
+        self.epoch.saturating_add(self.elapsed(ticks))
+    }
+}
+
 #[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TraceEvent<K> {
     pub kind: K,
     pub timestamp: PackedNanos,
@@ -73,9 +203,29 @@ pub struct TraceEvent<K> {
 
 pub type BlockIndex = u32;
 
-const EVENTS_PER_BLOCK: usize = 16;
+pub const EVENTS_PER_BLOCK: usize = 16;
 
-#[derive(Debug)]
+/// Returned when an event is pushed with a timestamp before the previous event's
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct OutOfOrder {
+    pub timestamp: Nanos,
+    pub last_timestamp: Nanos,
+}
+
+impl Display for OutOfOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "event at {} was pushed after an event at {}",
+            self.timestamp, self.last_timestamp,
+        )
+    }
+}
+
+impl Error for OutOfOrder {}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(transparent)]
 pub struct TraceBlock<K>
 where
@@ -103,11 +253,17 @@ where
     }
 
     pub fn is_full(&self) -> bool {
-        !self.is_empty()
+        self.events.len() == EVENTS_PER_BLOCK
     }
 
+    /// Panics if the block is full or if `ev` is before the block's last event
     pub fn push(&mut self, ev: TraceEvent<K>) {
         assert!(!self.is_full());
+        assert!(self
+            .events
+            .last()
+            .is_none_or(|last| last.timestamp <= ev.timestamp));
+
         self.events.push(ev);
     }
 
@@ -118,7 +274,16 @@ where
     /// Returns 0 if block is empty, `Track` has a useful invariant that
     /// blocks are never empty.
     pub fn start_time(&self) -> Nanos {
-        self.events[0].timestamp.unpack()
+        self.events
+            .first()
+            .map_or(0, |event| event.timestamp.unpack())
+    }
+
+    /// Returns 0 if block is empty
+    pub fn end_time(&self) -> Nanos {
+        self.events
+            .last()
+            .map_or(0, |event| event.timestamp.unpack())
     }
 }
 
@@ -131,7 +296,41 @@ where
     }
 }
 
+/// Storage statistics for a pool of blocks
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    pub blocks: usize,
+    pub events: usize,
+    pub bytes: usize,
+}
+
+impl MemoryUsage {
+    pub fn bytes_per_event(&self) -> f64 {
+        if self.events == 0 {
+            0.0
+        } else {
+            self.bytes as f64 / self.events as f64
+        }
+    }
+}
+
+/// Storage that blocks can be read out of, blocks are borrowed if they're
+/// stored as-is or decoded on demand if they aren't
+pub trait BlockStore<K>
+where
+    K: Default + Clone,
+{
+    fn block(&self, block: BlockIndex) -> Cow<'_, TraceBlock<K>>;
+
+    fn start_time(&self, block: BlockIndex) -> Nanos {
+        self.block(block).start_time()
+    }
+
+    fn memory_usage(&self) -> MemoryUsage;
+}
+
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(transparent)]
 pub struct BlockPool<K>
 where
@@ -153,6 +352,31 @@ where
         self.blocks.push(TraceBlock::new());
         i as BlockIndex
     }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            blocks: self.blocks.len(),
+            events: self.blocks.iter().map(TraceBlock::len).sum(),
+            bytes: self.blocks.capacity() * mem::size_of::<TraceBlock<K>>(),
+        }
+    }
+}
+
+impl<K> BlockStore<K> for BlockPool<K>
+where
+    K: Default + Clone,
+{
+    fn block(&self, block: BlockIndex) -> Cow<'_, TraceBlock<K>> {
+        Cow::Borrowed(&self.blocks[block as usize])
+    }
+
+    fn start_time(&self, block: BlockIndex) -> Nanos {
+        self.blocks[block as usize].start_time()
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        BlockPool::memory_usage(self)
+    }
 }
 
 impl<K> Default for BlockPool<K>
@@ -164,10 +388,13 @@ where
     }
 }
 
+/// A series of time-ordered events, stored as blocks within a `BlockPool`.
+/// Every block of a track is non-empty and every block but the last is full
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(transparent)]
 pub struct Track<K> {
-    pub block_locations: Vec<BlockIndex>,
+    block_locations: Vec<BlockIndex>,
     __kind: PhantomData<K>,
 }
 
@@ -178,19 +405,56 @@ impl<K> Track<K> {
             __kind: PhantomData,
         }
     }
+
+    pub fn block_locations(&self) -> &[BlockIndex] {
+        &self.block_locations
+    }
 }
 
 impl<K> Track<K>
 where
     K: Default,
 {
+    /// Builds a track from events in any order
+    pub fn from_unsorted<I>(pool: &mut BlockPool<K>, events: I) -> Self
+    where
+        I: IntoIterator<Item = TraceEvent<K>>,
+    {
+        let mut events: Vec<_> = events.into_iter().collect();
+        events.sort_by_key(|event| event.timestamp);
+
+        let mut track = Self::new();
+        for event in events {
+            track.push_unchecked(pool, event);
+        }
+
+        track
+    }
+
     fn new_block(&mut self, pool: &mut BlockPool<K>) -> BlockIndex {
         let i = pool.alloc();
         self.block_locations.push(i);
         i
     }
 
-    pub fn push(&mut self, pool: &mut BlockPool<K>, ev: TraceEvent<K>) {
+    /// Appends an event to the track, events must be pushed in order of their timestamps
+    pub fn push(&mut self, pool: &mut BlockPool<K>, ev: TraceEvent<K>) -> Result<(), OutOfOrder> {
+        if let Some(last_timestamp) = self.end_time(pool) {
+            let timestamp = ev.timestamp.unpack();
+
+            if timestamp < last_timestamp {
+                return Err(OutOfOrder {
+                    timestamp,
+                    last_timestamp,
+                });
+            }
+        }
+
+        self.push_unchecked(pool, ev);
+        Ok(())
+    }
+
+    fn push_unchecked(&mut self, pool: &mut BlockPool<K>, ev: TraceEvent<K>) {
         let last = match self.block_locations.last() {
             None => self.new_block(pool),
             Some(&i) if pool.blocks[i as usize].is_full() => self.new_block(pool),
@@ -202,15 +466,14 @@ where
 
     pub fn start_time(&self, pool: &BlockPool<K>) -> Option<Nanos> {
         self.block_locations
-            .get(0)
+            .first()
             .map(|i| pool.blocks[*i as usize].start_time())
     }
 
     pub fn end_time(&self, pool: &BlockPool<K>) -> Option<Nanos> {
         self.block_locations
             .last()
-            .and_then(|i| pool.blocks[*i as usize].events().last())
-            .map(|x| x.timestamp.unpack())
+            .map(|i| pool.blocks[*i as usize].end_time())
     }
 
     pub fn after_last_time(&self, pool: &BlockPool<K>) -> Option<Nanos> {
@@ -230,6 +493,22 @@ where
     }
 }
 
+impl<K> Track<K>
+where
+    K: Default + Clone,
+{
+    /// The track's events within any `BlockStore`, blocks are decoded as they're reached
+    pub fn events_in<'a, P>(&'a self, pool: &'a P) -> impl Iterator<Item = TraceEvent<K>> + 'a
+    where
+        P: BlockStore<K>,
+    {
+        self.block_locations.iter().flat_map(move |&idx| {
+            let block = pool.block(idx);
+            (0..block.len()).map(move |event| block.events()[event].clone())
+        })
+    }
+}
+
 impl<K> Default for Track<K>
 where
     K: Default,
@@ -0,0 +1,79 @@
+use differential_dashboard::{data::Node, loader::LoadedProfile};
+use druid::{
+    widget::{Flex, Label, SizedBox, ViewSwitcher},
+    Color, FontDescriptor, FontFamily, FontWeight, Widget, WidgetExt,
+};
+use std::sync::Arc;
+
+/// The widths of the table's columns, the first one takes up the remaining space
+const COLUMNS: [(&str, f64); 6] = [
+    ("Operator", 0.0),
+    ("Address", 120.0),
+    ("Invocations", 100.0),
+    ("Total time", 120.0),
+    ("Average time", 120.0),
+    ("Max time", 120.0),
+];
+
+/// Every operator in the profile along with its activation statistics
+pub fn operator_table() -> impl Widget<Option<Arc<LoadedProfile>>> {
+    ViewSwitcher::new(
+        |profile: &Option<Arc<LoadedProfile>>, _env| profile.clone(),
+        |profile, _data, _env| match profile {
+            Some(profile) => {
+                let mut table = Flex::column().with_child(header());
+                for node in &profile.nodes {
+                    table.add_child(row(node));
+                }
+
+                table.padding(8.0).boxed()
+            }
+
+            None => Label::new("Open a profile to see its operators")
+                .with_text_color(Color::grey(0.5))
+                .padding(8.0)
+                .boxed(),
+        },
+    )
+}
+
+fn header() -> impl Widget<Option<Arc<LoadedProfile>>> {
+    let font = FontDescriptor::new(FontFamily::SYSTEM_UI).with_weight(FontWeight::BOLD);
+    cells(
+        COLUMNS.iter().map(|(name, _)| (*name).to_owned()),
+        |label| label.with_font(font.clone()),
+    )
+}
+
+fn row(node: &Node) -> impl Widget<Option<Arc<LoadedProfile>>> {
+    let values = vec![
+        node.name.clone(),
+        format!("{:?}", node.addr),
+        node.invocations.to_string(),
+        node.total_activation_time.clone(),
+        node.average_activation_time.clone(),
+        node.max_activation_time.clone(),
+    ];
+
+    cells(values.into_iter(), |label| label)
+}
+
+fn cells<T, I, F>(values: I, style: F) -> Flex<T>
+where
+    T: druid::Data,
+    I: Iterator<Item = String>,
+    F: Fn(Label<T>) -> Label<T>,
+{
+    let mut row = Flex::row();
+    for (value, (_, width)) in values.zip(COLUMNS.iter()) {
+        let label = style(Label::new(value));
+
+        if *width > 0.0 {
+            row.add_child(SizedBox::new(label).width(*width));
+        } else {
+            row.add_flex_child(label.expand_width(), 1.0);
+        }
+    }
+
+    row
+}
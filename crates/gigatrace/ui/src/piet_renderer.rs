@@ -0,0 +1,93 @@
+use druid::{
+    kurbo::{Line, RoundedRect},
+    piet::{Error, FontFamily, RenderContext, StrokeStyle, Text, TextLayout, TextLayoutBuilder},
+    Affine, Color, Point,
+};
+use gigatrace::render::{self, DrawCommand, Renderer, TextAlign};
+
+/// Draws timeline draw commands with piet, so in druid widgets
+pub struct PietRenderer<'a, R> {
+    ctx: &'a mut R,
+}
+
+impl<'a, R: RenderContext> PietRenderer<'a, R> {
+    pub fn new(ctx: &'a mut R) -> Self {
+        Self { ctx }
+    }
+}
+
+impl<R: RenderContext> Renderer for PietRenderer<'_, R> {
+    type Error = Error;
+
+    fn draw(&mut self, command: &DrawCommand) -> Result<(), Error> {
+        let ctx = &mut *self.ctx;
+
+        match command {
+            DrawCommand::FillRect {
+                rect,
+                radius,
+                color,
+            } => {
+                let rect = RoundedRect::new(
+                    rect.x,
+                    rect.y,
+                    rect.x + rect.width,
+                    rect.y + rect.height,
+                    *radius,
+                );
+                ctx.fill(rect, &color_of(*color));
+            }
+
+            DrawCommand::Line {
+                from,
+                to,
+                color,
+                width,
+                dash,
+            } => {
+                let line = Line::new((from.x, from.y), (to.x, to.y));
+                let style = match dash {
+                    Some((dash, gap)) => StrokeStyle::new().dash_pattern(&[*dash, *gap]),
+                    None => StrokeStyle::new(),
+                };
+
+                ctx.stroke_styled(line, &color_of(*color), *width, &style);
+            }
+
+            DrawCommand::Text {
+                text,
+                at,
+                size,
+                color,
+                align,
+                angle,
+            } => {
+                let layout = ctx
+                    .text()
+                    .new_text_layout(text.clone())
+                    .font(FontFamily::SANS_SERIF, *size)
+                    .text_color(color_of(*color))
+                    .build()?;
+
+                // Piet draws text from its top left corner
+                let width = layout.size().width;
+                let x = match align {
+                    TextAlign::Start => 0.0,
+                    TextAlign::Center => -width / 2.0,
+                    TextAlign::End => -width,
+                };
+
+                ctx.save()?;
+                ctx.transform(Affine::translate((at.x, at.y)) * Affine::rotate(*angle));
+                ctx.draw_text(&layout, Point::new(x, 0.0));
+                ctx.restore()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn color_of(color: render::Color) -> Color {
+    Color::rgba8(color.r, color.g, color.b, color.a)
+}
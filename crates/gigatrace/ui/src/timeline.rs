@@ -0,0 +1,252 @@
+use crate::piet_renderer::PietRenderer;
+use differential_dashboard::{
+    loader::LoadedProfile,
+    view::{event_tooltip, format_duration, lane_rows, timeline_layout},
+};
+use druid::{
+    kurbo::{Rect, Size},
+    piet::{FontFamily, Text, TextLayout, TextLayoutBuilder},
+    widget::prelude::*,
+    Color, Point,
+};
+use gigatrace::{
+    payload::PayloadId,
+    render::{self, EventBox, LaneRow, Renderer, TimelineLayout, ViewMap},
+    trace::Nanos,
+};
+use std::{ops::Range, sync::Arc};
+
+/// The profile's lanes along with where the user has zoomed and panned to
+pub struct TimelineWidget {
+    view_range: Range<Nanos>,
+    rows: Vec<LaneRow>,
+    /// The events drawn by the last paint, used for hovering
+    events: Vec<EventBox<PayloadId>>,
+    hover: Option<(Point, String)>,
+    /// The last position of the mouse while dragging
+    drag: Option<f64>,
+}
+
+impl TimelineWidget {
+    pub fn new() -> Self {
+        Self {
+            view_range: 0..1000,
+            rows: Vec::new(),
+            events: Vec::new(),
+            hover: None,
+            drag: None,
+        }
+    }
+
+    fn reset(&mut self, profile: Option<&LoadedProfile>) {
+        self.view_range = profile
+            .and_then(|profile| profile.trace.time_bounds())
+            .unwrap_or(0..1000);
+        self.rows = profile.map_or_else(Vec::new, |profile| lane_rows(&profile.lanes));
+        self.events.clear();
+        self.hover = None;
+    }
+
+    fn layout(&self, width: f64) -> TimelineLayout {
+        let graph_height = self.rows.last().map_or(0.0, |row| row.y + row.height);
+        timeline_layout(width, graph_height)
+    }
+
+    fn zoom(&mut self, zoom_factor: f64, at_x: f64, width: f64) {
+        let graph = self.layout(width).graph;
+        let delta_time = self.view_range.end - self.view_range.start;
+        let new_delta_time = ((delta_time as f64) * zoom_factor).max(1000.0);
+        let view = ViewMap::new(&self.view_range, graph.width);
+        let at_x = at_x - graph.x;
+        let zoom_time = view.to_ns(at_x);
+        let r = at_x / graph.width;
+        let new_start = (zoom_time - new_delta_time * r).max(0.0);
+        let new_end = new_start + new_delta_time;
+        self.view_range = (new_start as u64)..(new_end as u64);
+    }
+
+    fn pan(&mut self, delta_x: f64, width: f64) {
+        let graph = self.layout(width).graph;
+        let delta_time = self.view_range.end - self.view_range.start;
+        let shift = (delta_x / graph.width * delta_time as f64) as i64;
+
+        let start = (self.view_range.start as i64 - shift).max(0) as u64;
+        self.view_range = start..start + delta_time;
+    }
+
+    fn zoom_ratio(delta: f64) -> f64 {
+        let wheel_zoom_speed = -0.02;
+        let sign = if delta.is_sign_positive() { 1.0 } else { -1.0 };
+        let delta_log = sign * (delta.abs() + 1.0).log2();
+        1.0 - (delta_log * wheel_zoom_speed)
+    }
+
+    fn update_hover(&mut self, pos: Point, profile: &LoadedProfile) -> bool {
+        let hovered = self
+            .events
+            .iter()
+            .find(|event| event.rect.contains(render::Point::new(pos.x, pos.y)))
+            .map(|event| (pos, event_tooltip(&profile.trace, &event.event)));
+
+        let changed = hovered != self.hover;
+        self.hover = hovered;
+        changed
+    }
+
+    fn paint_tooltip(ctx: &mut PaintCtx, at: Point, tooltip: &str) {
+        let layout = match ctx
+            .text()
+            .new_text_layout(tooltip.to_owned())
+            .font(FontFamily::SANS_SERIF, 14.0)
+            .text_color(Color::BLACK)
+            .build()
+        {
+            Ok(layout) => layout,
+            Err(error) => {
+                eprintln!("failed to lay out a tooltip: {}", error);
+                return;
+            }
+        };
+
+        let size = layout.size();
+        let x = (at.x + 5.0)
+            .min(ctx.size().width - size.width - 10.0)
+            .max(5.0);
+        let origin = Point::new(x, at.y + 15.0);
+        let background = Rect::from_origin_size(origin, size)
+            .inflate(5.0, 3.0)
+            .to_rounded_rect(3.0);
+
+        ctx.fill(background, &Color::WHITE);
+        ctx.stroke(background, &Color::grey(0.6), 1.0);
+        ctx.draw_text(&layout, origin);
+    }
+}
+
+impl Widget<Option<Arc<LoadedProfile>>> for TimelineWidget {
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut Option<Arc<LoadedProfile>>,
+        _env: &Env,
+    ) {
+        let profile = match data {
+            Some(profile) => profile,
+            None => return,
+        };
+        let width = ctx.size().width;
+
+        match event {
+            Event::Wheel(mouse) => {
+                if mouse.mods.shift() {
+                    let delta = if mouse.wheel_delta.x != 0.0 {
+                        mouse.wheel_delta.x
+                    } else {
+                        mouse.wheel_delta.y
+                    };
+                    self.pan(-delta, width);
+                } else {
+                    let factor = Self::zoom_ratio(mouse.wheel_delta.y);
+                    self.zoom(factor, mouse.pos.x, width);
+                }
+
+                self.hover = None;
+                ctx.request_paint();
+                ctx.set_handled();
+            }
+
+            Event::MouseDown(mouse) => {
+                ctx.set_active(true);
+                self.drag = Some(mouse.pos.x);
+                self.hover = None;
+                ctx.request_paint();
+            }
+
+            Event::MouseMove(mouse) => {
+                if let Some(last_x) = self.drag {
+                    self.pan(mouse.pos.x - last_x, width);
+                    self.drag = Some(mouse.pos.x);
+                    ctx.request_paint();
+                } else if self.update_hover(mouse.pos, profile) {
+                    ctx.request_paint();
+                }
+            }
+
+            Event::MouseUp(_) => {
+                ctx.set_active(false);
+                self.drag = None;
+            }
+
+            _ => {}
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &Option<Arc<LoadedProfile>>,
+        _env: &Env,
+    ) {
+        match event {
+            LifeCycle::WidgetAdded => self.reset(data.as_deref()),
+            LifeCycle::HotChanged(false) => {
+                if self.hover.take().is_some() {
+                    ctx.request_paint();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &Option<Arc<LoadedProfile>>,
+        data: &Option<Arc<LoadedProfile>>,
+        _env: &Env,
+    ) {
+        if !old_data.same(data) {
+            self.reset(data.as_deref());
+            ctx.request_layout();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _data: &Option<Arc<LoadedProfile>>,
+        _env: &Env,
+    ) -> Size {
+        let width = bc.max().width;
+        bc.constrain(Size::new(width, self.layout(width).height))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &Option<Arc<LoadedProfile>>, _env: &Env) {
+        let profile = match data {
+            Some(profile) => profile,
+            None => return,
+        };
+
+        let clock = profile.trace.clock;
+        let scene = self.layout(ctx.size().width).layout(
+            &profile.trace,
+            &self.rows,
+            &self.view_range,
+            |_| true,
+            None,
+            |event| format_duration(clock.unpack_duration(event.duration)),
+        );
+
+        if let Err(error) = PietRenderer::new(ctx.render_ctx).render(&scene.commands) {
+            eprintln!("failed to draw the timeline: {}", error);
+        }
+        self.events = scene.events;
+
+        if let Some((at, tooltip)) = &self.hover {
+            Self::paint_tooltip(ctx, *at, tooltip);
+        }
+    }
+}
@@ -0,0 +1,43 @@
+//! Runs a query against a profile from the command line
+//!
+//! ```text
+//! query <profile.json> "events | group by worker | aggregate count()" [--csv]
+//! ```
+
+use anyhow::{bail, Context, Result};
+use differential_dashboard::{loader, query};
+use std::{env, fs};
+
+fn main() -> Result<()> {
+    let mut csv = false;
+    let mut args = Vec::new();
+    for arg in env::args().skip(1) {
+        if arg == "--csv" {
+            csv = true;
+        } else {
+            args.push(arg);
+        }
+    }
+
+    let (profile, query) = match args.as_slice() {
+        [profile, query] => (profile, query),
+        _ => bail!("usage: query <profile.json> <query> [--csv]"),
+    };
+
+    let contents =
+        fs::read(profile).with_context(|| format!("failed to read profile {}", profile))?;
+    // Loaded the same way as the dashboard, broken profiles are repaired rather than rejected
+    let loaded = loader::load_profile(profile.clone(), &contents, |_| {})?;
+    for problem in &loaded.problems {
+        eprintln!("{}: {}", profile, problem);
+    }
+
+    let table = query::run(query, &loaded.events, &loaded.nodes)?;
+    if csv {
+        print!("{}", table.to_csv());
+    } else {
+        print!("{}", table);
+    }
+
+    Ok(())
+}
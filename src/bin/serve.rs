@@ -0,0 +1,238 @@
+//! Serves a directory of profiles so that the dashboard can load them with `?profile=<url>`
+//!
+//! ```text
+//! serve <profile directory> [--port 8001] [--dashboard http://localhost:8000]
+//! ```
+//!
+//! `GET /` lists the available profiles and `GET /<name>` returns one of them,
+//! every response allows cross-origin requests since the dashboard is served separately
+
+use anyhow::{bail, Context, Result};
+use std::{
+    env, fs,
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+};
+
+struct Config {
+    directory: PathBuf,
+    port: u16,
+    dashboard: String,
+}
+
+fn main() -> Result<()> {
+    let config = parse_args()?;
+
+    let listener = TcpListener::bind(("127.0.0.1", config.port))
+        .with_context(|| format!("failed to listen on port {}", config.port))?;
+    let address = format!("http://localhost:{}", config.port);
+
+    println!(
+        "serving profiles from {} at {}",
+        config.directory.display(),
+        address,
+    );
+    for name in list_profiles(&config.directory)? {
+        println!("    {}/?profile={}/{}", config.dashboard, address, name);
+    }
+
+    let directory = Arc::new(config.directory);
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let directory = directory.clone();
+
+                thread::spawn(move || {
+                    if let Err(error) = handle(stream, &directory) {
+                        eprintln!("failed to handle request: {:#}", error);
+                    }
+                });
+            }
+
+            Err(error) => eprintln!("failed to accept connection: {}", error),
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_args() -> Result<Config> {
+    let usage =
+        "usage: serve <profile directory> [--port 8001] [--dashboard http://localhost:8000]";
+
+    let (mut directory, mut port, mut dashboard) = (None, 8001, "http://localhost:8000".to_owned());
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--port" => {
+                let value = args.next().context(usage)?;
+                port = value
+                    .parse()
+                    .with_context(|| format!("invalid port '{}'", value))?;
+            }
+            "--dashboard" => dashboard = args.next().context(usage)?,
+            _ if directory.is_none() => directory = Some(PathBuf::from(arg)),
+            _ => bail!(usage),
+        }
+    }
+
+    let directory = directory.context(usage)?;
+    if !directory.is_dir() {
+        bail!("{} isn't a directory", directory.display());
+    }
+
+    Ok(Config {
+        directory,
+        port,
+        dashboard: dashboard.trim_end_matches('/').to_owned(),
+    })
+}
+
+fn list_profiles(directory: &Path) -> Result<Vec<String>> {
+    let mut profiles = Vec::new();
+    for entry in fs::read_dir(directory)
+        .with_context(|| format!("failed to read {}", directory.display()))?
+    {
+        let path = entry?.path();
+
+        if is_profile(&path) {
+            if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+                profiles.push(name.to_owned());
+            }
+        }
+    }
+
+    profiles.sort();
+    Ok(profiles)
+}
+
+fn handle(stream: TcpStream, directory: &Path) -> Result<()> {
+    let mut request = String::new();
+    let mut reader = BufReader::new(&stream);
+    reader.read_line(&mut request)?;
+
+    // Skip the headers, nothing here needs them
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request.split_whitespace();
+    let (method, path) = (
+        parts.next().unwrap_or_default(),
+        parts.next().unwrap_or("/"),
+    );
+    let path = path.split('?').next().unwrap_or_default();
+
+    let mut stream = &stream;
+    match (method, path) {
+        ("OPTIONS", _) => respond(&mut stream, "204 No Content", "text/plain", &[]),
+
+        ("GET", "/") => {
+            let profiles = serde_json::to_vec(&list_profiles(directory)?)?;
+            respond(&mut stream, "200 OK", "application/json", &profiles)
+        }
+
+        ("GET", path) => match percent_decode(path.trim_start_matches('/'))
+            .and_then(|name| profile_path(directory, &name))
+        {
+            Some(path) => {
+                let profile = fs::read(&path)
+                    .with_context(|| format!("failed to read {}", path.display()))?;
+                respond(&mut stream, "200 OK", "application/json", &profile)
+            }
+            None => respond(
+                &mut stream,
+                "404 Not Found",
+                "text/plain",
+                b"no such profile",
+            ),
+        },
+
+        _ => respond(
+            &mut stream,
+            "405 Method Not Allowed",
+            "text/plain",
+            b"only GET requests are supported",
+        ),
+    }
+}
+
+/// Only plain file names of profiles within the directory are served, the
+/// same ones that are listed
+fn profile_path(directory: &Path, name: &str) -> Option<PathBuf> {
+    let is_plain = !name.is_empty() && !name.starts_with('.') && !name.contains(&['/', '\\'][..]);
+
+    Some(directory.join(name)).filter(|path| is_plain && is_profile(path))
+}
+
+fn is_profile(path: &Path) -> bool {
+    let is_json = path
+        .extension()
+        .is_some_and(|extension| extension == "json" || extension == "json5");
+
+    is_json && path.is_file()
+}
+
+/// Decodes the `%XX` escapes of a url's path, returns `None` if any are
+/// malformed or the decoded path isn't utf-8
+fn percent_decode(path: &str) -> Option<String> {
+    let mut decoded = Vec::with_capacity(path.len());
+    let mut bytes = path.bytes();
+
+    while let Some(byte) = bytes.next() {
+        if byte == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            decoded.push(byte);
+        }
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+fn respond<W: Write>(stream: &mut W, status: &str, content_type: &str, body: &[u8]) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\n\
+         Content-Type: {}\r\n\
+         Content-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\n\
+         Access-Control-Allow-Methods: GET, OPTIONS\r\n\
+         Connection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len(),
+    )?;
+    stream.write_all(body)?;
+    stream.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::percent_decode;
+
+    #[test]
+    fn decodes_escapes() {
+        assert_eq!(
+            percent_decode("my%20profile.json").unwrap(),
+            "my profile.json"
+        );
+        assert_eq!(percent_decode("caf%C3%A9.json").unwrap(), "café.json");
+        assert_eq!(percent_decode("..%2Fsecret").unwrap(), "../secret");
+        assert_eq!(percent_decode("plain.json").unwrap(), "plain.json");
+
+        assert_eq!(percent_decode("bad%2"), None);
+        assert_eq!(percent_decode("bad%zz"), None);
+        assert_eq!(percent_decode("bad%FF"), None);
+    }
+}
@@ -0,0 +1,189 @@
+use crate::{
+    data::{Node, WorkerTimelineEvent},
+    download::download,
+    query::{self, Table},
+};
+use std::rc::Rc;
+use yew::{html, ChangeData, Component, ComponentLink, Html, Properties, ShouldRender};
+
+/// The most rows that will be rendered, exports always contain the full table
+const MAX_DISPLAYED_ROWS: usize = 500;
+
+#[derive(Debug, Clone, Properties)]
+pub struct ConsoleProps {
+    pub events: Rc<[WorkerTimelineEvent]>,
+    pub nodes: Rc<[Node]>,
+}
+
+#[derive(Debug)]
+pub enum Message {
+    Run(String),
+    Sort(usize),
+    Export,
+}
+
+/// A panel for running queries against the loaded profile
+#[derive(Debug)]
+pub struct Console {
+    link: ComponentLink<Self>,
+    properties: ConsoleProps,
+    query: String,
+    table: Option<Table>,
+    error: Option<String>,
+    /// The column the table is sorted by and whether it's descending
+    sorted_by: Option<(usize, bool)>,
+}
+
+impl Console {
+    fn run(&mut self) {
+        self.sorted_by = None;
+
+        if self.query.trim().is_empty() {
+            self.table = None;
+            self.error = None;
+            return;
+        }
+
+        match query::run(&self.query, &self.properties.events, &self.properties.nodes) {
+            Ok(table) => {
+                self.table = Some(table);
+                self.error = None;
+            }
+
+            Err(error) => {
+                self.table = None;
+                self.error = Some(format!("{:#}", error));
+            }
+        }
+    }
+
+    fn view_table(&self, table: &Table) -> Html {
+        let headers = table.columns.iter().enumerate().map(|(idx, column)| {
+            let arrow = match self.sorted_by {
+                Some((sorted, true)) if sorted == idx => " ▼",
+                Some((sorted, false)) if sorted == idx => " ▲",
+                _ => "",
+            };
+
+            html! {
+                <th onclick=self.link.callback(move |_| Message::Sort(idx))>
+                    { format!("{}{}", column, arrow) }
+                </th>
+            }
+        });
+
+        let rows = table.rows.iter().take(MAX_DISPLAYED_ROWS).map(|row| {
+            html! {
+                <tr>{ for row.iter().map(|value| html! { <td>{ value }</td> }) }</tr>
+            }
+        });
+
+        let summary = if table.rows.len() > MAX_DISPLAYED_ROWS {
+            format!(
+                "Showing {} of {} rows",
+                MAX_DISPLAYED_ROWS,
+                table.rows.len(),
+            )
+        } else {
+            format!("{} rows", table.rows.len())
+        };
+
+        html! {
+            <>
+                <p>
+                    { summary }
+                    <button onclick=self.link.callback(|_| Message::Export)>{ "Export CSV" }</button>
+                </p>
+                <table class="query-results">
+                    <thead><tr>{ for headers }</tr></thead>
+                    <tbody>{ for rows }</tbody>
+                </table>
+            </>
+        }
+    }
+}
+
+impl Component for Console {
+    type Message = Message;
+    type Properties = ConsoleProps;
+
+    fn create(properties: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self {
+            link,
+            properties,
+            query: String::new(),
+            table: None,
+            error: None,
+            sorted_by: None,
+        }
+    }
+
+    fn update(&mut self, message: Self::Message) -> ShouldRender {
+        match message {
+            Message::Run(query) => {
+                self.query = query;
+                self.run();
+                true
+            }
+
+            Message::Sort(column) => {
+                let descending =
+                    !matches!(self.sorted_by, Some((sorted, true)) if sorted == column);
+                if let Some(table) = self.table.as_mut() {
+                    table.sort_by(column, descending);
+                    self.sorted_by = Some((column, descending));
+                }
+
+                true
+            }
+
+            Message::Export => {
+                if let Some(table) = self.table.as_ref() {
+                    if let Err(error) = download("query.csv", "text/csv", &table.to_csv()) {
+                        self.error = Some(format!("failed to export query results: {:#}", error));
+                        return true;
+                    }
+                }
+
+                false
+            }
+        }
+    }
+
+    fn change(&mut self, properties: Self::Properties) -> ShouldRender {
+        let unchanged = Rc::ptr_eq(&self.properties.events, &properties.events)
+            && Rc::ptr_eq(&self.properties.nodes, &properties.nodes);
+        self.properties = properties;
+        if unchanged {
+            return false;
+        }
+
+        // Re-run the current query against the new profile
+        self.run();
+
+        true
+    }
+
+    fn view(&self) -> Html {
+        let results = match (self.table.as_ref(), self.error.as_ref()) {
+            (_, Some(error)) => html! { <p class="query-error">{ error }</p> },
+            (Some(table), None) => self.view_table(table),
+            (None, None) => html! {},
+        };
+
+        html! {
+            <div id="query-console">
+                <input
+                    type="text"
+                    placeholder="events | where duration > 5ms | group by worker | aggregate count(), sum(duration)"
+                    value=self.query.clone()
+                    onchange=self.link.callback(|change: ChangeData| match change {
+                        ChangeData::Value(query) => Message::Run(query),
+                        _ => unreachable!(),
+                    })
+                />
+                { results }
+            </div>
+        }
+    }
+}
@@ -80,6 +80,49 @@ pub struct WorkerTimelineEvent {
     pub duration: u64,
     /// The number of events that have been collapsed within the current timeline event
     pub collapsed_events: usize,
+    /// What a message or progress event sent, older profiles don't record it
+    #[serde(default)]
+    pub traffic: Option<Traffic>,
+    /// The batches a merge combined, older profiles don't record it
+    #[serde(default)]
+    pub merge: Option<MergeInfo>,
+}
+
+/// The records a message or progress event sent and the workers it was sent between
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+pub struct Traffic {
+    /// The channel the records were sent over, messages are sent over
+    /// the `Edge` with the same `channel_id`
+    pub channel_id: usize,
+    pub source_worker: usize,
+    pub target_worker: usize,
+    /// The number of records or progress updates that were sent
+    pub records: usize,
+    /// The size of what was sent, unknown for channels that aren't serialized
+    pub bytes: Option<usize>,
+}
+
+/// The batches of an arrangement's spine that a merge combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+pub struct MergeInfo {
+    /// The spine layer the merged batches belong to, batches at
+    /// scale `n` hold up to `2^n` updates
+    pub scale: usize,
+    /// The number of updates in each of the merged batches
+    pub length1: usize,
+    pub length2: usize,
+    /// The number of updates in the merged batch, `None` if the
+    /// merge was still in progress when the event ended
+    pub complete: Option<usize>,
+    /// The units of work the merge has performed so far
+    pub effort: usize,
+}
+
+impl MergeInfo {
+    /// The number of updates in both of the merged batches
+    pub const fn inputs(&self) -> usize {
+        self.length1 + self.length2
+    }
 }
 
 impl WorkerTimelineEvent {
@@ -159,6 +202,36 @@ pub enum TimelineEvent {
 }
 
 impl TimelineEvent {
+    pub const fn operator_id(&self) -> Option<usize> {
+        match *self {
+            Self::OperatorActivation { operator_id, .. } | Self::Merge { operator_id, .. } => {
+                Some(operator_id)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn operator_name(&self) -> Option<&str> {
+        match self {
+            Self::OperatorActivation { operator_name, .. } | Self::Merge { operator_name, .. } => {
+                Some(operator_name)
+            }
+            _ => None,
+        }
+    }
+
+    pub const fn kind(&self) -> EventKind {
+        match self {
+            Self::OperatorActivation { .. } => EventKind::Operator,
+            Self::Merge { .. } => EventKind::Merge,
+            Self::Progress => EventKind::Progress,
+            Self::Message => EventKind::Message,
+            Self::Application => EventKind::Application,
+            Self::Input => EventKind::Input,
+            Self::Parked => EventKind::Parked,
+        }
+    }
+
     fn rank(&self) -> u128 {
         let mut bytes = 0u128.to_ne_bytes();
 
@@ -190,6 +263,48 @@ impl Ord for TimelineEvent {
     }
 }
 
+/// The variant of a `TimelineEvent` without any of its data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EventKind {
+    Operator,
+    Merge,
+    Progress,
+    Message,
+    Application,
+    Input,
+    Parked,
+}
+
+impl EventKind {
+    pub const ALL: [Self; 7] = [
+        Self::Operator,
+        Self::Merge,
+        Self::Progress,
+        Self::Message,
+        Self::Application,
+        Self::Input,
+        Self::Parked,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Operator => "operator",
+            Self::Merge => "merge",
+            Self::Progress => "progress",
+            Self::Message => "message",
+            Self::Application => "application",
+            Self::Input => "input",
+            Self::Parked => "parked",
+        }
+    }
+}
+
+impl Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
 impl Display for TimelineEvent {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -0,0 +1,611 @@
+//! Seeded synthetic profiles for demos, benchmarks and tests
+
+use crate::data::{
+    ActivationDuration, Edge, EdgeKind, EventId, MergeInfo, Node, ProfilingData, Subgraph,
+    TimelineEvent, Traffic, WorkerTimelineEvent,
+};
+use humantime::Duration as HumanDuration;
+use std::{collections::HashMap, ops::Range, time::Duration};
+
+/// The inferno palette timely's profiler colors operators with, from least to most time
+const PALETTE: [&str; 10] = [
+    "#000004", "#170B3A", "#420A67", "#6B176E", "#932567", "#BB3654", "#DC5139", "#F3761A",
+    "#FCA40A", "#F6D644",
+];
+
+const OPERATORS: [&str; 12] = [
+    "Map",
+    "Filter",
+    "FlatMap",
+    "Concatenate",
+    "ArrangeBySelf",
+    "ArrangeByKey",
+    "Join",
+    "Reduce",
+    "Distinct",
+    "Count",
+    "AsCollection",
+    "Inspect",
+];
+
+const REGIONS: [&str; 4] = ["Iterate", "Region", "Subgraph", "Scope"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DemoConfig {
+    /// Profiles generated with the same seed and config are identical
+    pub seed: u64,
+    pub workers: usize,
+    /// The number of operators within every scope, one of which is a nested region
+    pub operators: usize,
+    /// How deeply regions are nested within the dataflow
+    pub depth: usize,
+    /// The number of times each worker steps through the dataflow
+    pub rounds: usize,
+}
+
+impl Default for DemoConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0xD1FF_DA7A,
+            workers: 4,
+            operators: 6,
+            depth: 2,
+            rounds: 50,
+        }
+    }
+}
+
+/// Generates a profile of a dataflow with nested regions running on every worker,
+/// with operator activations, merges, progress, messages and parks
+pub fn demo_profile(config: &DemoConfig) -> ProfilingData {
+    let mut rng = Rng::new(config.seed);
+    let mut graph = Graph::default();
+    graph.build_scope(&mut rng, config, vec![0], config.depth);
+
+    // Progress is exchanged over a channel of its own
+    let progress_channel = graph.next_channel;
+
+    let mut events = EventBuilder::default();
+    for worker in 0..config.workers {
+        // Workers start up at slightly different times
+        let mut time = rng.range(1_000..50_000);
+
+        for _ in 0..config.rounds {
+            time += rng.range(200..2_000);
+            events.push(&mut rng, worker, TimelineEvent::Input, time, 500..5_000);
+            time = events.last_end() + rng.range(100..1_000);
+
+            for operator in &graph.operators {
+                if !rng.chance(0.7) {
+                    continue;
+                }
+
+                let cost = operator.cost;
+                let activation = TimelineEvent::OperatorActivation {
+                    operator_id: operator.id,
+                    operator_name: operator.name.clone(),
+                };
+                events.push(&mut rng, worker, activation, time, cost / 2..cost * 3 / 2);
+                time = events.last_end() + rng.range(100..2_000);
+
+                if operator.arranges && rng.chance(0.3) {
+                    let merge = TimelineEvent::Merge {
+                        operator_id: operator.id,
+                        operator_name: operator.name.clone(),
+                    };
+                    events.push(&mut rng, worker, merge, time, cost..cost * 4);
+                    events.merged(&mut rng);
+                    time = events.last_end() + rng.range(100..1_000);
+                }
+
+                if rng.chance(0.25) && !graph.edges.is_empty() {
+                    events.push(&mut rng, worker, TimelineEvent::Message, time, 200..3_000);
+
+                    let channel_id = rng.pick(&graph.edges).channel_id;
+                    let records = rng.range(1..2_000) as usize;
+                    events.sent(&mut rng, config, channel_id, records, 16);
+                    time = events.last_end() + rng.range(100..500);
+                }
+            }
+
+            events.push(
+                &mut rng,
+                worker,
+                TimelineEvent::Progress,
+                time,
+                1_000..20_000,
+            );
+            let updates = rng.range(1..50) as usize;
+            events.sent(&mut rng, config, progress_channel, updates, 24);
+            time = events.last_end() + rng.range(100..1_000);
+
+            if rng.chance(0.1) {
+                events.push(
+                    &mut rng,
+                    worker,
+                    TimelineEvent::Application,
+                    time,
+                    1_000..50_000,
+                );
+                time = events.last_end() + rng.range(100..1_000);
+            }
+
+            events.push(
+                &mut rng,
+                worker,
+                TimelineEvent::Parked,
+                time,
+                10_000..2_000_000,
+            );
+            time = events.last_end();
+        }
+    }
+
+    let (nodes, subgraphs) = graph.summarize(&events.events);
+
+    ProfilingData {
+        nodes,
+        subgraphs,
+        edges: graph.edges,
+        palette_colors: PALETTE.iter().map(|&color| color.to_owned()).collect(),
+        timeline_events: events.events,
+    }
+}
+
+/// The default demo profile as the dashboard loads it, shared by tests of
+/// everything derived while loading
+#[cfg(test)]
+pub fn loaded_demo() -> crate::loader::LoadedProfile {
+    let data = demo_profile(&DemoConfig::default());
+    crate::loader::index_profile(
+        "demo.json".to_owned(),
+        data,
+        Vec::new(),
+        crate::loader::Ingest::Batch,
+        |_| {},
+    )
+}
+
+#[derive(Debug)]
+struct Operator {
+    id: usize,
+    addr: Vec<usize>,
+    name: String,
+    /// The usual duration of one of its activations
+    cost: u64,
+    arranges: bool,
+    max_arrangement_size: Option<usize>,
+}
+
+#[derive(Debug)]
+struct Region {
+    id: usize,
+    addr: Vec<usize>,
+    name: String,
+}
+
+#[derive(Debug, Default)]
+struct Graph {
+    /// Operators in the order data flows through them
+    operators: Vec<Operator>,
+    regions: Vec<Region>,
+    edges: Vec<Edge>,
+    next_id: usize,
+    next_channel: usize,
+}
+
+impl Graph {
+    fn next_id(&mut self) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Builds the scope at `addr`, returning the addresses of the operators
+    /// data enters and leaves it through
+    fn build_scope(
+        &mut self,
+        rng: &mut Rng,
+        config: &DemoConfig,
+        addr: Vec<usize>,
+        depth: usize,
+    ) -> (Vec<usize>, Vec<usize>) {
+        let id = self.next_id();
+        let name = if addr.len() == 1 {
+            "Dataflow".to_owned()
+        } else {
+            rng.pick(&REGIONS).to_string()
+        };
+        self.regions.push(Region {
+            id,
+            addr: addr.clone(),
+            name,
+        });
+
+        let is_root = addr.len() == 1;
+        let count = config.operators.max(2);
+        let region = (depth > 0).then(|| count / 2 + 1);
+
+        let mut ends: Option<(Vec<usize>, Vec<usize>)> = None;
+        for local in 1..=count {
+            let mut child = addr.clone();
+            child.push(local);
+
+            let (first, last) = if region == Some(local) {
+                self.build_scope(rng, config, child, depth - 1)
+            } else {
+                let name = match local {
+                    1 if is_root => "Input",
+                    _ if is_root && local == count => "Probe",
+                    // Every scope arranges something so that there's merges
+                    _ if local + 1 == count => "ArrangeByKey",
+                    _ => rng.pick(&OPERATORS),
+                };
+                let arranges = name.starts_with("Arrange");
+
+                let id = self.next_id();
+                self.operators.push(Operator {
+                    id,
+                    addr: child.clone(),
+                    name: name.to_owned(),
+                    cost: rng.range(1_000..500_000),
+                    arranges,
+                    max_arrangement_size: if arranges {
+                        Some(rng.range(100..100_000) as usize)
+                    } else {
+                        None
+                    },
+                });
+
+                (child.clone(), child)
+            };
+
+            ends = Some(match ends {
+                None => (first, last),
+                Some((scope_first, previous)) => {
+                    // Edges into or out of nested regions cross scopes
+                    let edge_kind =
+                        if previous.len() == first.len() && first.len() == addr.len() + 1 {
+                            EdgeKind::Normal
+                        } else {
+                            EdgeKind::Crossing
+                        };
+
+                    self.edges.push(Edge {
+                        src: previous,
+                        dest: first,
+                        channel_id: self.next_channel,
+                        edge_kind,
+                    });
+                    self.next_channel += 1;
+
+                    (scope_first, last)
+                }
+            });
+        }
+
+        ends.expect("scopes have at least two operators")
+    }
+
+    /// Fills in the activation statistics of every operator and region
+    fn summarize(&self, events: &[WorkerTimelineEvent]) -> (Vec<Node>, Vec<Subgraph>) {
+        let mut activations: HashMap<usize, Vec<ActivationDuration>> = HashMap::new();
+        for event in events {
+            if let TimelineEvent::OperatorActivation { operator_id, .. } = event.event {
+                activations
+                    .entry(operator_id)
+                    .or_default()
+                    .push(ActivationDuration {
+                        activation_time: event.duration,
+                        activated_at: event.start_time,
+                    });
+            }
+        }
+
+        let max_total = activations
+            .values()
+            .map(|durations| Stats::of(durations).total)
+            .max()
+            .unwrap_or(0);
+
+        let nodes = self
+            .operators
+            .iter()
+            .map(|operator| {
+                let durations = activations.remove(&operator.id).unwrap_or_default();
+                let stats = Stats::of(&durations);
+                let (fill_color, text_color) = colors(stats.total, max_total);
+
+                Node {
+                    id: operator.id,
+                    addr: operator.addr.clone(),
+                    name: operator.name.clone(),
+                    max_activation_time: format_nanos(stats.max),
+                    min_activation_time: format_nanos(stats.min),
+                    average_activation_time: format_nanos(stats.average()),
+                    total_activation_time: format_nanos(stats.total),
+                    invocations: stats.count,
+                    fill_color,
+                    text_color,
+                    activation_durations: durations,
+                    max_arrangement_size: operator.max_arrangement_size,
+                    min_arrangement_size: operator.max_arrangement_size.map(|size| size / 10),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // Regions run whenever any of the operators within them do
+        let subgraphs = self
+            .regions
+            .iter()
+            .map(|region| {
+                let stats = Stats::of(
+                    nodes
+                        .iter()
+                        .filter(|node| node.addr.starts_with(&region.addr))
+                        .flat_map(|node| node.activation_durations.iter()),
+                );
+                let (fill_color, text_color) = colors(stats.total, max_total);
+
+                Subgraph {
+                    id: region.id,
+                    addr: region.addr.clone(),
+                    name: region.name.clone(),
+                    max_activation_time: format_nanos(stats.max),
+                    mix_activation_time: format_nanos(stats.min),
+                    average_activation_time: format_nanos(stats.average()),
+                    total_activation_time: format_nanos(stats.total),
+                    invocations: stats.count,
+                    fill_color,
+                    text_color,
+                }
+            })
+            .collect();
+
+        (nodes, subgraphs)
+    }
+}
+
+#[derive(Debug, Default)]
+struct EventBuilder {
+    events: Vec<WorkerTimelineEvent>,
+}
+
+impl EventBuilder {
+    fn push(
+        &mut self,
+        rng: &mut Rng,
+        worker: usize,
+        event: TimelineEvent,
+        start_time: u64,
+        duration: Range<u64>,
+    ) {
+        // Every event gets its own id, like the ones differential logs
+        let event_id = self.events.len() as EventId;
+        let duration = rng.range(duration.start..duration.end.max(duration.start + 1));
+
+        self.events.push(WorkerTimelineEvent {
+            event_id,
+            worker,
+            event,
+            start_time,
+            duration,
+            collapsed_events: 1,
+            traffic: None,
+            merge: None,
+        });
+    }
+
+    /// Records what the last event sent to a random worker, `record_size` is in bytes
+    fn sent(
+        &mut self,
+        rng: &mut Rng,
+        config: &DemoConfig,
+        channel_id: usize,
+        records: usize,
+        record_size: usize,
+    ) {
+        let target_worker = rng.range(0..config.workers as u64) as usize;
+
+        if let Some(event) = self.events.last_mut() {
+            event.traffic = Some(Traffic {
+                channel_id,
+                source_worker: event.worker,
+                target_worker,
+                records,
+                bytes: Some(records * record_size),
+            });
+        }
+    }
+
+    /// Records the batches the last event merged, at a random layer of the spine
+    fn merged(&mut self, rng: &mut Rng) {
+        let scale = rng.range(0..16) as usize;
+        let capacity = 1 << scale;
+        let length1 = rng.range(capacity / 2 + 1..capacity + 1) as usize;
+        let length2 = rng.range(capacity / 2 + 1..capacity + 1) as usize;
+        let inputs = (length1 + length2) as u64;
+
+        // Some merges are still running when their event ends, the
+        // finished ones consolidate away a share of their updates
+        let (complete, effort) = if rng.chance(0.8) {
+            (Some(rng.range(inputs / 2..inputs + 1) as usize), inputs)
+        } else {
+            (None, rng.range(1..inputs))
+        };
+
+        if let Some(event) = self.events.last_mut() {
+            event.merge = Some(MergeInfo {
+                scale,
+                length1,
+                length2,
+                complete,
+                effort: effort as usize,
+            });
+        }
+    }
+
+    fn last_end(&self) -> u64 {
+        self.events.last().map_or(0, WorkerTimelineEvent::end_time)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Stats {
+    count: usize,
+    total: u64,
+    min: u64,
+    max: u64,
+}
+
+impl Stats {
+    fn of<'a, I>(durations: I) -> Self
+    where
+        I: IntoIterator<Item = &'a ActivationDuration>,
+    {
+        durations
+            .into_iter()
+            .fold(Self::default(), |stats, duration| Self {
+                count: stats.count + 1,
+                total: stats.total + duration.activation_time,
+                min: if stats.count == 0 {
+                    duration.activation_time
+                } else {
+                    stats.min.min(duration.activation_time)
+                },
+                max: stats.max.max(duration.activation_time),
+            })
+    }
+
+    fn average(&self) -> u64 {
+        self.total.checked_div(self.count as u64).unwrap_or(0)
+    }
+}
+
+fn format_nanos(nanos: u64) -> String {
+    HumanDuration::from(Duration::from_nanos(nanos)).to_string()
+}
+
+/// Picks the fill and text colors of an operator by its share of the most time any operator spent
+fn colors(total: u64, max_total: u64) -> (String, String) {
+    let idx = if max_total == 0 {
+        0
+    } else {
+        // Regions can take longer than any of their operators
+        let share = (total as f64 / max_total as f64).min(1.0);
+        (share * (PALETTE.len() - 1) as f64).round() as usize
+    };
+    let text = if idx < PALETTE.len() / 2 {
+        "#EEEEEE"
+    } else {
+        "#0E1111"
+    };
+
+    (PALETTE[idx].to_owned(), text.to_owned())
+}
+
+/// SplitMix64, small and stable across versions so that seeds always produce the same profile
+#[derive(Debug, Clone)]
+struct Rng(u64);
+
+impl Rng {
+    const fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn range(&mut self, range: Range<u64>) -> u64 {
+        range.start + self.next() % (range.end - range.start)
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        ((self.next() >> 11) as f64 / (1u64 << 53) as f64) < probability
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.range(0..items.len() as u64) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{demo_profile, DemoConfig};
+    use crate::{data::TimelineEvent, loader::validate};
+
+    #[test]
+    fn seeds_are_reproducible() {
+        let config = DemoConfig::default();
+        assert_eq!(demo_profile(&config), demo_profile(&config));
+
+        let reseeded = DemoConfig {
+            seed: config.seed + 1,
+            ..config
+        };
+        assert_ne!(demo_profile(&config), demo_profile(&reseeded));
+    }
+
+    #[test]
+    fn profiles_are_valid() {
+        let mut data = demo_profile(&DemoConfig::default());
+        assert_eq!(validate(&mut data), Vec::new());
+
+        let workers: Vec<_> = (0..4)
+            .map(|worker| {
+                data.timeline_events
+                    .iter()
+                    .any(|event| event.worker == worker)
+            })
+            .collect();
+        assert_eq!(workers, [true; 4]);
+
+        // Three nested scopes, each with a region in place of one of their operators
+        assert_eq!(data.subgraphs.len(), 3);
+        assert_eq!(data.nodes.len(), 5 + 5 + 6);
+        assert!(data
+            .subgraphs
+            .iter()
+            .any(|subgraph| subgraph.addr.len() == 3));
+
+        for kind in &[
+            TimelineEvent::Parked,
+            TimelineEvent::Progress,
+            TimelineEvent::Input,
+        ] {
+            assert!(data
+                .timeline_events
+                .iter()
+                .any(|event| &event.event == kind));
+        }
+        assert!(data
+            .timeline_events
+            .iter()
+            .any(|event| matches!(event.event, TimelineEvent::Merge { .. })));
+        assert!(data.timeline_events.iter().all(|event| {
+            matches!(event.event, TimelineEvent::Merge { .. }) == event.merge.is_some()
+        }));
+    }
+
+    #[test]
+    fn scales_with_its_config() {
+        let small = demo_profile(&DemoConfig {
+            workers: 1,
+            rounds: 10,
+            ..DemoConfig::default()
+        });
+        let large = demo_profile(&DemoConfig {
+            workers: 8,
+            rounds: 100,
+            ..DemoConfig::default()
+        });
+
+        assert!(small.timeline_events.len() * 40 < large.timeline_events.len());
+    }
+}
@@ -0,0 +1,72 @@
+use anyhow::{anyhow, Context, Result};
+use js_sys::Array;
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use web_sys::{Blob, BlobPropertyBag, HtmlAnchorElement, Url};
+
+/// Offers `contents` to the user as a file download named `file_name`
+pub fn download(file_name: &str, mime_type: &str, contents: &str) -> Result<()> {
+    let parts = Array::of1(&JsValue::from_str(contents));
+    let blob =
+        Blob::new_with_str_sequence_and_options(&parts, BlobPropertyBag::new().type_(mime_type))
+            .map_err(js_error)
+            .context("failed to create download blob")?;
+
+    download_blob(file_name, &blob)
+}
+
+pub fn download_blob(file_name: &str, blob: &Blob) -> Result<()> {
+    let url = Url::create_object_url_with_blob(blob)
+        .map_err(js_error)
+        .context("failed to create download url")?;
+
+    let downloaded = download_url(file_name, &url);
+
+    // The download starts after the click's been handled, revoking the url
+    // before then can cancel it
+    let revoke = Closure::once_into_js(move || {
+        if let Err(error) = Url::revoke_object_url(&url) {
+            tracing::warn!("failed to revoke download url: {:?}", error);
+        }
+    });
+    web_sys::window()
+        .context("failed to get the window")?
+        .set_timeout_with_callback(revoke.unchecked_ref())
+        .map_err(js_error)?;
+
+    downloaded
+}
+
+/// Offers the contents of `url`, an object or data url, as a file download named `file_name`
+pub fn download_url(file_name: &str, url: &str) -> Result<()> {
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .context("failed to get the document")?;
+    let body = document.body().context("the document has no body")?;
+
+    let anchor: HtmlAnchorElement = document
+        .create_element("a")
+        .map_err(js_error)?
+        .dyn_into()
+        .map_err(|_| anyhow!("created element wasn't an anchor"))?;
+
+    anchor.set_href(url);
+    anchor.set_download(file_name);
+
+    // Some browsers ignore clicks on anchors that aren't part of the document
+    body.append_child(&anchor).map_err(js_error)?;
+    anchor.click();
+    body.remove_child(&anchor).map_err(js_error)?;
+
+    Ok(())
+}
+
+/// Embeds an svg document as an image's source
+pub fn svg_url(svg: &str) -> String {
+    let encoded: String = js_sys::encode_uri_component(svg).into();
+    format!("data:image/svg+xml,{}", encoded)
+}
+
+/// Converts a javascript exception into an error
+pub fn js_error(error: JsValue) -> anyhow::Error {
+    anyhow!("{:?}", error)
+}
@@ -0,0 +1,458 @@
+use crate::data::{EventKind, TimelineEvent, WorkerTimelineEvent};
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::{collections::BTreeSet, ops::Range, rc::Rc};
+use yew::{html, Callback, Component, ComponentLink, Html, InputData, Properties, ShouldRender};
+
+/// A set of filters over timeline events, an event is shown only
+/// if it passes every enabled filter
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    /// The kinds of events to show, `None` shows every kind
+    pub kinds: Option<BTreeSet<EventKind>>,
+    /// Only shows operators and merges with a matching name
+    pub operator_name: Option<Regex>,
+    /// Only shows operators and merges with these ids, set by
+    /// focusing on a scope in the flame graph
+    pub operators: Option<BTreeSet<usize>>,
+    /// The workers to show events from, `None` shows every worker
+    pub workers: Option<BTreeSet<usize>>,
+    pub min_duration: Option<u64>,
+    pub max_duration: Option<u64>,
+    /// Only shows events overlapping this window of absolute timestamps
+    pub time_window: Option<Range<u64>>,
+}
+
+impl EventFilter {
+    pub fn is_empty(&self) -> bool {
+        self.kinds.is_none()
+            && self.operator_name.is_none()
+            && self.operators.is_none()
+            && self.workers.is_none()
+            && self.min_duration.is_none()
+            && self.max_duration.is_none()
+            && self.time_window.is_none()
+    }
+
+    pub fn matches(&self, event: &WorkerTimelineEvent) -> bool {
+        self.matches_lane(&event.event)
+            && self.matches_span(event.worker, event.start_time, event.duration)
+    }
+
+    /// Whether any events from the given lane can pass the filter
+    pub fn matches_lane(&self, lane: &TimelineEvent) -> bool {
+        let kind_matches = self
+            .kinds
+            .as_ref()
+            .is_none_or(|kinds| kinds.contains(&lane.kind()));
+
+        let name_matches = self.operator_name.as_ref().is_none_or(|pattern| {
+            lane.operator_name()
+                .is_some_and(|name| pattern.is_match(name))
+        });
+
+        let operator_matches = self
+            .operators
+            .as_ref()
+            .is_none_or(|operators| lane.operator_id().is_some_and(|id| operators.contains(&id)));
+
+        kind_matches && name_matches && operator_matches
+    }
+
+    /// Whether `matches_span` can hide an event while passing a longer one from
+    /// the same lane, so it has to be checked on every event rather than on the
+    /// longest ones the timeline's index keeps
+    pub fn filters_each_event(&self) -> bool {
+        self.workers.is_some() || self.max_duration.is_some() || self.time_window.is_some()
+    }
+
+    /// Checks the parts of the filter that differ between events in the same lane
+    pub fn matches_span(&self, worker: usize, start_time: u64, duration: u64) -> bool {
+        let end_time = start_time + duration;
+
+        self.workers
+            .as_ref()
+            .is_none_or(|workers| workers.contains(&worker))
+            && self.min_duration.is_none_or(|min| duration >= min)
+            && self.max_duration.is_none_or(|max| duration <= max)
+            && self
+                .time_window
+                .as_ref()
+                .is_none_or(|window| start_time < window.end && end_time >= window.start)
+    }
+}
+
+#[derive(Debug, Clone, Properties)]
+pub struct FilterPanelProps {
+    pub events: Rc<[WorkerTimelineEvent]>,
+    pub onchange: Callback<EventFilter>,
+}
+
+#[derive(Debug)]
+pub enum Message {
+    ToggleKind(EventKind),
+    ToggleWorker(usize),
+    OperatorName(String),
+    MinDuration(String),
+    MaxDuration(String),
+    WindowStart(String),
+    WindowEnd(String),
+    Reset,
+}
+
+/// The raw contents of the filter panel's inputs
+#[derive(Debug, Clone, Default)]
+struct FilterInputs {
+    hidden_kinds: BTreeSet<EventKind>,
+    hidden_workers: BTreeSet<usize>,
+    operator_name: String,
+    min_duration: String,
+    max_duration: String,
+    window_start: String,
+    window_end: String,
+}
+
+/// A panel for toggling the filters applied to the timeline
+#[derive(Debug)]
+pub struct FilterPanel {
+    link: ComponentLink<Self>,
+    properties: FilterPanelProps,
+    inputs: FilterInputs,
+    workers: BTreeSet<usize>,
+    /// The earliest event's start time, time windows are relative to it
+    start_time: u64,
+    error: Option<String>,
+}
+
+impl FilterPanel {
+    fn build_filter(&self) -> Result<EventFilter> {
+        let inputs = &self.inputs;
+
+        let kinds = if inputs.hidden_kinds.is_empty() {
+            None
+        } else {
+            Some(
+                EventKind::ALL
+                    .iter()
+                    .copied()
+                    .filter(|kind| !inputs.hidden_kinds.contains(kind))
+                    .collect(),
+            )
+        };
+
+        let workers = if inputs.hidden_workers.is_empty() {
+            None
+        } else {
+            Some(
+                self.workers
+                    .difference(&inputs.hidden_workers)
+                    .copied()
+                    .collect(),
+            )
+        };
+
+        let operator_name = non_empty(&inputs.operator_name)
+            .map(|pattern| {
+                Regex::new(&format!("(?i){}", pattern))
+                    .with_context(|| format!("invalid operator name pattern '{}'", pattern))
+            })
+            .transpose()?;
+
+        let window_start = parse_millis(&inputs.window_start)?;
+        let window_end = parse_millis(&inputs.window_end)?;
+        let time_window = if window_start.is_some() || window_end.is_some() {
+            let start = self.start_time + window_start.unwrap_or(0);
+            let end = window_end.map_or(u64::MAX, |end| self.start_time + end);
+
+            Some(start..end)
+        } else {
+            None
+        };
+
+        Ok(EventFilter {
+            kinds,
+            operator_name,
+            operators: None,
+            workers,
+            min_duration: parse_duration(&inputs.min_duration)?,
+            max_duration: parse_duration(&inputs.max_duration)?,
+            time_window,
+        })
+    }
+
+    fn view_text(
+        &self,
+        label: &str,
+        placeholder: &str,
+        value: &str,
+        message: fn(String) -> Message,
+    ) -> Html {
+        html! {
+            <label>
+                { label }
+                <input
+                    type="text"
+                    placeholder=placeholder
+                    value=value
+                    oninput=self.link.callback(move |input: InputData| message(input.value))
+                />
+            </label>
+        }
+    }
+}
+
+impl Component for FilterPanel {
+    type Message = Message;
+    type Properties = FilterPanelProps;
+
+    fn create(properties: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let workers = properties.events.iter().map(|event| event.worker).collect();
+        let start_time = properties
+            .events
+            .iter()
+            .map(|event| event.start_time)
+            .min()
+            .unwrap_or(0);
+
+        Self {
+            link,
+            properties,
+            inputs: FilterInputs::default(),
+            workers,
+            start_time,
+            error: None,
+        }
+    }
+
+    fn update(&mut self, message: Self::Message) -> ShouldRender {
+        let inputs = &mut self.inputs;
+        match message {
+            Message::ToggleKind(kind) => toggle(&mut inputs.hidden_kinds, kind),
+            Message::ToggleWorker(worker) => toggle(&mut inputs.hidden_workers, worker),
+            Message::OperatorName(pattern) => inputs.operator_name = pattern,
+            Message::MinDuration(duration) => inputs.min_duration = duration,
+            Message::MaxDuration(duration) => inputs.max_duration = duration,
+            Message::WindowStart(start) => inputs.window_start = start,
+            Message::WindowEnd(end) => inputs.window_end = end,
+            Message::Reset => *inputs = FilterInputs::default(),
+        }
+
+        // Invalid inputs keep the last valid filter applied
+        match self.build_filter() {
+            Ok(filter) => {
+                self.error = None;
+                self.properties.onchange.emit(filter);
+            }
+            Err(error) => self.error = Some(format!("{:#}", error)),
+        }
+
+        true
+    }
+
+    fn change(&mut self, properties: Self::Properties) -> ShouldRender {
+        if Rc::ptr_eq(&self.properties.events, &properties.events) {
+            self.properties = properties;
+            return false;
+        }
+
+        self.workers = properties.events.iter().map(|event| event.worker).collect();
+        self.start_time = properties
+            .events
+            .iter()
+            .map(|event| event.start_time)
+            .min()
+            .unwrap_or(0);
+        self.properties = properties;
+
+        true
+    }
+
+    fn view(&self) -> Html {
+        let kinds = EventKind::ALL.iter().map(|&kind| {
+            let checked = !self.inputs.hidden_kinds.contains(&kind);
+
+            html! {
+                <label>
+                    <input
+                        type="checkbox"
+                        checked=checked
+                        onclick=self.link.callback(move |_| Message::ToggleKind(kind))
+                    />
+                    { kind }
+                </label>
+            }
+        });
+
+        let workers = self.workers.iter().map(|&worker| {
+            let checked = !self.inputs.hidden_workers.contains(&worker);
+
+            html! {
+                <label>
+                    <input
+                        type="checkbox"
+                        checked=checked
+                        onclick=self.link.callback(move |_| Message::ToggleWorker(worker))
+                    />
+                    { format!("Worker {}", worker) }
+                </label>
+            }
+        });
+
+        let error = self.error.as_ref().map_or_else(
+            || html! {},
+            |error| html! { <p class="filter-error">{ error }</p> },
+        );
+
+        html! {
+            <div id="filter-panel">
+                <fieldset>
+                    <legend>{ "Event kinds" }</legend>
+                    { for kinds }
+                </fieldset>
+
+                <fieldset>
+                    <legend>{ "Workers" }</legend>
+                    { for workers }
+                </fieldset>
+
+                <fieldset>
+                    <legend>{ "Events" }</legend>
+                    { self.view_text("Operator name", "regex, e.g. join|arrange", &self.inputs.operator_name, Message::OperatorName) }
+                    { self.view_text("Min duration", "e.g. 5ms", &self.inputs.min_duration, Message::MinDuration) }
+                    { self.view_text("Max duration", "e.g. 1s", &self.inputs.max_duration, Message::MaxDuration) }
+                </fieldset>
+
+                <fieldset>
+                    <legend>{ "Time window (ms from start)" }</legend>
+                    { self.view_text("From", "0", &self.inputs.window_start, Message::WindowStart) }
+                    { self.view_text("To", "end", &self.inputs.window_end, Message::WindowEnd) }
+                </fieldset>
+
+                <button onclick=self.link.callback(|_| Message::Reset)>{ "Reset filters" }</button>
+                { error }
+            </div>
+        }
+    }
+}
+
+fn toggle<T: Ord>(set: &mut BTreeSet<T>, value: T) {
+    if !set.remove(&value) {
+        set.insert(value);
+    }
+}
+
+fn non_empty(input: &str) -> Option<&str> {
+    Some(input.trim()).filter(|input| !input.is_empty())
+}
+
+fn parse_duration(input: &str) -> Result<Option<u64>> {
+    non_empty(input)
+        .map(|duration| {
+            humantime::parse_duration(duration)
+                .map(|duration| duration.as_nanos() as u64)
+                .with_context(|| format!("invalid duration '{}'", duration))
+        })
+        .transpose()
+}
+
+fn parse_millis(input: &str) -> Result<Option<u64>> {
+    non_empty(input)
+        .map(|millis| {
+            millis
+                .parse::<f64>()
+                .ok()
+                .filter(|millis| *millis >= 0.0)
+                .map(|millis| (millis * 1_000_000.0) as u64)
+                .with_context(|| format!("invalid time '{}', expected milliseconds", millis))
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EventFilter;
+    use crate::data::{EventKind, TimelineEvent, WorkerTimelineEvent};
+    use regex::Regex;
+
+    fn event(worker: usize, event: TimelineEvent, start_time: u64) -> WorkerTimelineEvent {
+        WorkerTimelineEvent {
+            event_id: 0,
+            worker,
+            event,
+            start_time,
+            duration: 5_000_000,
+            collapsed_events: 0,
+            traffic: None,
+            merge: None,
+        }
+    }
+
+    fn operator(name: &str) -> TimelineEvent {
+        TimelineEvent::OperatorActivation {
+            operator_id: 1,
+            operator_name: name.to_owned(),
+        }
+    }
+
+    #[test]
+    fn empty_filter_matches_everything() {
+        let filter = EventFilter::default();
+
+        assert!(filter.is_empty());
+        assert!(filter.matches(&event(0, TimelineEvent::Parked, 0)));
+        assert!(filter.matches(&event(3, operator("Join"), 100)));
+    }
+
+    #[test]
+    fn filters_compose() {
+        let filter = EventFilter {
+            kinds: Some(
+                [EventKind::Operator, EventKind::Merge]
+                    .iter()
+                    .copied()
+                    .collect(),
+            ),
+            operator_name: Some(Regex::new("(?i)^join").unwrap()),
+            operators: None,
+            workers: Some([1].iter().copied().collect()),
+            min_duration: Some(1_000_000),
+            max_duration: None,
+            time_window: Some(10_000_000..20_000_000),
+        };
+
+        assert!(filter.matches(&event(1, operator("JoinCore"), 12_000_000)));
+        // Overlaps the start of the window
+        assert!(filter.matches(&event(1, operator("Join"), 6_000_000)));
+
+        assert!(!filter.matches(&event(1, TimelineEvent::Parked, 12_000_000)));
+        assert!(!filter.matches(&event(1, operator("Map"), 12_000_000)));
+        assert!(!filter.matches(&event(0, operator("Join"), 12_000_000)));
+        assert!(!filter.matches(&event(1, operator("Join"), 20_000_000)));
+
+        assert!(filter.matches_lane(&operator("join")));
+        assert!(!filter.matches_lane(&TimelineEvent::Progress));
+        assert!(!filter.matches_span(1, 12_000_000, 10));
+    }
+
+    #[test]
+    fn filters_operators_by_id() {
+        let filter = EventFilter {
+            operators: Some([1, 2].iter().copied().collect()),
+            ..EventFilter::default()
+        };
+        let merge = TimelineEvent::Merge {
+            operator_id: 2,
+            operator_name: "Arrange".to_owned(),
+        };
+        let other = TimelineEvent::OperatorActivation {
+            operator_id: 3,
+            operator_name: "Join".to_owned(),
+        };
+
+        assert!(!filter.is_empty());
+        assert!(filter.matches_lane(&operator("Join")));
+        assert!(filter.matches_lane(&merge));
+        assert!(!filter.matches_lane(&other));
+        assert!(!filter.matches_lane(&TimelineEvent::Parked));
+    }
+}
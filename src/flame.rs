@@ -0,0 +1,431 @@
+use crate::{
+    data::{Node, Subgraph},
+    view::format_duration,
+};
+use std::{
+    collections::{BTreeSet, HashMap},
+    rc::Rc,
+};
+use yew::{html, Callback, Component, ComponentLink, Html, Properties, ShouldRender};
+
+/// The height of each level of the flame graph, in pixels
+const ROW_HEIGHT: f64 = 22.0;
+
+/// Frames narrower than this fraction of the focused scope aren't drawn
+const MIN_FRAME_WIDTH: f64 = 0.002;
+
+/// A dataflow, subgraph or operator within the scope hierarchy
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scope {
+    pub addr: Vec<usize>,
+    pub name: String,
+    /// The ids of the operators and subgraphs at this address, scopes
+    /// that the profile doesn't describe have none
+    pub ids: Vec<usize>,
+    pub parent: Option<usize>,
+    /// Ordered by address
+    pub children: Vec<usize>,
+    /// How long the scope's operators were active for altogether, in nanoseconds
+    pub total: u64,
+    pub fill_color: String,
+    pub text_color: String,
+}
+
+/// A frame of the flame graph, its position and width are fractions of the focused scope
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frame {
+    pub scope: usize,
+    /// How far below the focused scope the frame is
+    pub depth: usize,
+    pub x: f64,
+    pub width: f64,
+}
+
+/// Activation times aggregated along the scope hierarchy described by
+/// each operator's and subgraph's address
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScopeTree {
+    /// The first scope holds every dataflow
+    scopes: Vec<Scope>,
+}
+
+impl ScopeTree {
+    pub const ROOT: usize = 0;
+
+    pub fn new(nodes: &[Node], subgraphs: &[Subgraph]) -> Self {
+        let mut tree = Self {
+            scopes: vec![Scope {
+                addr: Vec::new(),
+                name: "All dataflows".to_owned(),
+                ids: Vec::new(),
+                parent: None,
+                children: Vec::new(),
+                total: 0,
+                fill_color: "#e0e0e0".to_owned(),
+                text_color: "#000".to_owned(),
+            }],
+        };
+        let mut addrs = HashMap::new();
+        addrs.insert(Vec::new(), Self::ROOT);
+
+        for subgraph in subgraphs {
+            let scope = tree.scope_at(&mut addrs, &subgraph.addr);
+            tree.describe(
+                scope,
+                subgraph.id,
+                &subgraph.name,
+                &subgraph.fill_color,
+                &subgraph.text_color,
+            );
+        }
+
+        for node in nodes {
+            let scope = tree.scope_at(&mut addrs, &node.addr);
+            tree.describe(
+                scope,
+                node.id,
+                &node.name,
+                &node.fill_color,
+                &node.text_color,
+            );
+
+            tree.scopes[scope].total += node
+                .activation_durations
+                .iter()
+                .map(|duration| duration.activation_time)
+                .sum::<u64>();
+        }
+
+        // Children are always created after their parents
+        for scope in (1..tree.scopes.len()).rev() {
+            let (total, parent) = (tree.scopes[scope].total, tree.scopes[scope].parent);
+            if let Some(parent) = parent {
+                tree.scopes[parent].total += total;
+            }
+        }
+
+        let addrs: Vec<_> = tree.scopes.iter().map(|scope| scope.addr.clone()).collect();
+        for scope in &mut tree.scopes {
+            scope.children.sort_by(|&a, &b| addrs[a].cmp(&addrs[b]));
+        }
+
+        tree
+    }
+
+    /// Gets the scope at `addr`, creating it and any of its missing parents
+    fn scope_at(&mut self, addrs: &mut HashMap<Vec<usize>, usize>, addr: &[usize]) -> usize {
+        if let Some(&scope) = addrs.get(addr) {
+            return scope;
+        }
+
+        let parent = self.scope_at(addrs, &addr[..addr.len() - 1]);
+        let scope = self.scopes.len();
+        self.scopes.push(Scope {
+            addr: addr.to_vec(),
+            name: format!("Scope {:?}", addr),
+            ids: Vec::new(),
+            parent: Some(parent),
+            children: Vec::new(),
+            total: 0,
+            fill_color: "#e0e0e0".to_owned(),
+            text_color: "#000".to_owned(),
+        });
+        self.scopes[parent].children.push(scope);
+        addrs.insert(addr.to_vec(), scope);
+
+        scope
+    }
+
+    /// Names a scope after the first operator or subgraph found at its address
+    fn describe(&mut self, scope: usize, id: usize, name: &str, fill: &str, text: &str) {
+        let scope = &mut self.scopes[scope];
+        if scope.ids.is_empty() {
+            scope.name = name.to_owned();
+            scope.fill_color = fill.to_owned();
+            scope.text_color = text.to_owned();
+        }
+
+        scope.ids.push(id);
+    }
+
+    pub fn get(&self, scope: usize) -> &Scope {
+        &self.scopes[scope]
+    }
+
+    /// The scopes containing `scope`, outermost first
+    pub fn ancestors(&self, scope: usize) -> Vec<usize> {
+        let mut ancestors = Vec::new();
+        let mut current = self.scopes[scope].parent;
+        while let Some(parent) = current {
+            ancestors.push(parent);
+            current = self.scopes[parent].parent;
+        }
+        ancestors.reverse();
+
+        ancestors
+    }
+
+    /// The ids of every operator and subgraph within `scope`, including its own
+    pub fn operators(&self, scope: usize) -> BTreeSet<usize> {
+        let mut operators = BTreeSet::new();
+        let mut stack = vec![scope];
+        while let Some(scope) = stack.pop() {
+            operators.extend(self.scopes[scope].ids.iter().copied());
+            stack.extend(self.scopes[scope].children.iter().copied());
+        }
+
+        operators
+    }
+
+    /// Lays out `focus` and everything within it, each scope is as wide as its
+    /// share of the focused scope's activation time
+    pub fn frames(&self, focus: usize) -> Vec<Frame> {
+        let mut frames = vec![Frame {
+            scope: focus,
+            depth: 0,
+            x: 0.0,
+            width: 1.0,
+        }];
+
+        let total = self.scopes[focus].total as f64;
+        let mut idx = 0;
+        while idx < frames.len() {
+            let frame = frames[idx];
+            let mut x = frame.x;
+
+            for &child in &self.scopes[frame.scope].children {
+                let width = if total > 0.0 {
+                    self.scopes[child].total as f64 / total
+                } else {
+                    0.0
+                };
+
+                if width >= MIN_FRAME_WIDTH {
+                    frames.push(Frame {
+                        scope: child,
+                        depth: frame.depth + 1,
+                        x,
+                        width,
+                    });
+                }
+                x += width;
+            }
+
+            idx += 1;
+        }
+
+        frames
+    }
+}
+
+#[derive(Debug, Clone, Properties)]
+pub struct FlameGraphProps {
+    pub nodes: Rc<[Node]>,
+    pub subgraphs: Rc<[Subgraph]>,
+    /// Called with the operators within the focused scope, or `None` once it's reset
+    pub onfocus: Callback<Option<BTreeSet<usize>>>,
+}
+
+#[derive(Debug)]
+pub enum Message {
+    Focus(usize),
+}
+
+/// An icicle graph of activation time aggregated by scope, clicking on a
+/// scope zooms into it and filters the timeline to its operators
+#[derive(Debug)]
+pub struct FlameGraph {
+    link: ComponentLink<Self>,
+    properties: FlameGraphProps,
+    tree: ScopeTree,
+    focus: usize,
+}
+
+impl FlameGraph {
+    fn view_frame(&self, frame: &Frame) -> Html {
+        let scope = self.tree.get(frame.scope);
+        let focused = self.tree.get(self.focus).total.max(1);
+        let index = frame.scope;
+
+        let title = format!(
+            "{} {:?}: {} ({:.1}%)",
+            scope.name,
+            scope.addr,
+            format_duration(scope.total),
+            scope.total as f64 / focused as f64 * 100.0,
+        );
+        let style = format!(
+            "left: {}%; width: {}%; top: {}px; background: {}; color: {}",
+            frame.x * 100.0,
+            frame.width * 100.0,
+            frame.depth as f64 * ROW_HEIGHT,
+            scope.fill_color,
+            scope.text_color,
+        );
+
+        html! {
+            <div
+                class="flame-frame"
+                title=title
+                style=style
+                onclick=self.link.callback(move |_| Message::Focus(index))
+            >
+                { &scope.name }
+            </div>
+        }
+    }
+}
+
+impl Component for FlameGraph {
+    type Message = Message;
+    type Properties = FlameGraphProps;
+
+    fn create(properties: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let tree = ScopeTree::new(&properties.nodes, &properties.subgraphs);
+
+        Self {
+            link,
+            properties,
+            tree,
+            focus: ScopeTree::ROOT,
+        }
+    }
+
+    fn update(&mut self, message: Self::Message) -> ShouldRender {
+        match message {
+            Message::Focus(scope) => {
+                self.focus = scope;
+
+                let operators = if scope == ScopeTree::ROOT {
+                    None
+                } else {
+                    Some(self.tree.operators(scope))
+                };
+                self.properties.onfocus.emit(operators);
+            }
+        }
+
+        true
+    }
+
+    fn change(&mut self, properties: Self::Properties) -> ShouldRender {
+        let unchanged = Rc::ptr_eq(&self.properties.nodes, &properties.nodes)
+            && Rc::ptr_eq(&self.properties.subgraphs, &properties.subgraphs);
+        self.properties = properties;
+        if unchanged {
+            return false;
+        }
+
+        self.tree = ScopeTree::new(&self.properties.nodes, &self.properties.subgraphs);
+
+        // Operator ids mean nothing to another profile
+        if self.focus != ScopeTree::ROOT {
+            self.focus = ScopeTree::ROOT;
+            self.properties.onfocus.emit(None);
+        }
+
+        true
+    }
+
+    fn view(&self) -> Html {
+        let breadcrumbs = self.tree.ancestors(self.focus).into_iter().map(|scope| {
+            html! {
+                <button onclick=self.link.callback(move |_| Message::Focus(scope))>
+                    { &self.tree.get(scope).name }
+                </button>
+            }
+        });
+
+        let frames = self.tree.frames(self.focus);
+        let depth = frames
+            .iter()
+            .map(|frame| frame.depth + 1)
+            .max()
+            .unwrap_or(1);
+        let height = format!("height: {}px", depth as f64 * ROW_HEIGHT);
+
+        html! {
+            <details id="flame-graph" open=true>
+                <summary>{ "Activation time by scope" }</summary>
+                <p class="flame-breadcrumbs">{ for breadcrumbs }</p>
+                <div class="flame-frames" style=height>
+                    { for frames.iter().map(|frame| self.view_frame(frame)) }
+                </div>
+            </details>
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ScopeTree;
+    use crate::loader::load_profile;
+
+    fn tree() -> ScopeTree {
+        let content = include_bytes!("../test/data/example.json");
+        let profile = load_profile("example.json".to_owned(), content, |_| {}).unwrap();
+
+        ScopeTree::new(&profile.nodes, &profile.subgraphs)
+    }
+
+    fn find(tree: &ScopeTree, addr: &[usize]) -> usize {
+        tree.scopes
+            .iter()
+            .position(|scope| scope.addr == addr)
+            .unwrap()
+    }
+
+    #[test]
+    fn aggregates_up_the_hierarchy() {
+        let tree = tree();
+
+        let dataflow = find(&tree, &[0]);
+        let middle = find(&tree, &[0, 3]);
+        let inner = find(&tree, &[0, 3, 1]);
+
+        assert_eq!(tree.get(ScopeTree::ROOT).children, [dataflow]);
+        assert_eq!(tree.get(dataflow).name, "Dataflow");
+        assert_eq!(tree.get(inner).name, "An inner region");
+        assert_eq!(tree.get(inner).total, 800 + 24_040_600);
+        assert_eq!(
+            tree.get(middle).total,
+            800 + 24_040_600 + 4_700 + 77_079_400 + 75_998_400 + 1_686_727_400 + 107_324_000,
+        );
+        assert_eq!(
+            tree.get(ScopeTree::ROOT).total,
+            tree.get(middle).total + 2_600 + 800 + 78_227_800 + 4_080_800,
+        );
+
+        assert_eq!(tree.ancestors(inner), [ScopeTree::ROOT, dataflow, middle]);
+        assert_eq!(
+            tree.operators(inner).into_iter().collect::<Vec<_>>(),
+            [5, 10, 16],
+        );
+    }
+
+    #[test]
+    fn frames_split_their_parents() {
+        let tree = tree();
+        let middle = find(&tree, &[0, 3]);
+        let frames = tree.frames(middle);
+
+        assert_eq!(frames[0].scope, middle);
+        assert_eq!(frames[0].width, 1.0);
+
+        for frame in &frames[1..] {
+            let parent = tree.get(frame.scope).parent.unwrap();
+            let parent = frames.iter().find(|other| other.scope == parent).unwrap();
+
+            assert_eq!(frame.depth, parent.depth + 1);
+            assert!(frame.x >= parent.x && frame.x + frame.width <= parent.x + parent.width + 1e-9);
+        }
+
+        // The tiny Map operator is too narrow to draw, so its siblings don't quite fill their parent
+        let map = find(&tree, &[0, 3, 2]);
+        assert!(frames.iter().all(|frame| frame.scope != map));
+
+        let children = frames.iter().filter(|frame| frame.depth == 1);
+        let width: f64 = children.map(|frame| frame.width).sum();
+        assert!(width > 0.99 && width < 1.0, "{}", width);
+    }
+}
@@ -0,0 +1,305 @@
+use crate::{
+    data::{EventKind, TimelineEvent},
+    loader::ProfileTrace,
+    view::format_duration,
+};
+use gigatrace::trace::Nanos;
+use serde::{Deserialize, Serialize};
+use std::{fmt, ops::Range, rc::Rc};
+use yew::{html, Component, ComponentLink, Html, Properties, ShouldRender};
+
+/// The number of columns the profile's duration is split into
+pub const HEATMAP_BUCKETS: usize = 100;
+
+/// What a worker can spend its time on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Activity {
+    Operators,
+    Progress,
+    Messages,
+    Parked,
+}
+
+impl Activity {
+    pub const ALL: [Self; 4] = [
+        Self::Operators,
+        Self::Progress,
+        Self::Messages,
+        Self::Parked,
+    ];
+
+    /// The activity events of a kind count towards, if any
+    pub const fn of(kind: EventKind) -> Option<Self> {
+        match kind {
+            EventKind::Operator | EventKind::Merge => Some(Self::Operators),
+            EventKind::Progress => Some(Self::Progress),
+            EventKind::Message => Some(Self::Messages),
+            EventKind::Parked => Some(Self::Parked),
+            EventKind::Application | EventKind::Input => None,
+        }
+    }
+
+    const fn index(self) -> usize {
+        self as usize
+    }
+
+    /// The color of a cell that's entirely spent on the activity
+    const fn rgb(self) -> (u8, u8, u8) {
+        match self {
+            Self::Operators => (46, 125, 50),
+            Self::Progress => (21, 101, 192),
+            Self::Messages => (106, 27, 154),
+            Self::Parked => (198, 40, 40),
+        }
+    }
+}
+
+impl fmt::Display for Activity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Operators => "operator work",
+            Self::Progress => "progress",
+            Self::Messages => "messages",
+            Self::Parked => "parked",
+        })
+    }
+}
+
+/// The fraction of each span of time every worker spent on each activity
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Heatmap {
+    /// The span covered by the heatmap, in ticks
+    pub span: Range<Nanos>,
+    /// How long each bucket is, in nanoseconds
+    pub bucket_duration: Nanos,
+    /// Indexed by worker, then by bucket, then by `Activity`
+    pub workers: Vec<Vec<[f64; 4]>>,
+}
+
+impl Heatmap {
+    /// Splits every event of the trace between the buckets it overlaps, `lanes`
+    /// gives the kind of each of the trace's tracks.
+    ///
+    /// Unlike a selection's utilization this can't be answered by the tracks'
+    /// indices, each lane holds every worker's events so their aggregates mix
+    /// workers together. Indexing each worker's activities separately would
+    /// store every event a second time just to build the heatmap once while
+    /// loading, and a single pass over the events costs no more than indexing them
+    pub fn new(trace: &ProfileTrace, lanes: &[TimelineEvent], buckets: usize) -> Self {
+        let span = trace.time_bounds().unwrap_or(0..0);
+        let width = (span.end - span.start) as f64 / buckets as f64;
+
+        let mut workers: Vec<Vec<[f64; 4]>> = Vec::new();
+        if width > 0.0 {
+            for (track, lane) in trace.tracks.iter().zip(lanes) {
+                let activity = match Activity::of(lane.kind()) {
+                    Some(activity) => activity.index(),
+                    None => continue,
+                };
+
+                for event in track.track.events_in(&trace.pool) {
+                    let worker = trace.payloads.worker(event.kind) as usize;
+                    if worker >= workers.len() {
+                        workers.resize_with(worker + 1, || vec![[0.0; 4]; buckets]);
+                    }
+
+                    let start = (event.timestamp.unpack() - span.start) as f64;
+                    let end = start + event.duration.unpack() as f64;
+                    let first = (start / width) as usize;
+                    let last = ((end / width) as usize).min(buckets - 1);
+
+                    let overlapped = workers[worker][first..=last].iter_mut();
+                    for (bucket, fractions) in (first..).zip(overlapped) {
+                        let (bucket_start, bucket_end) =
+                            (bucket as f64 * width, (bucket + 1) as f64 * width);
+                        let overlap = end.min(bucket_end) - start.max(bucket_start);
+
+                        if overlap > 0.0 {
+                            fractions[activity] += overlap / width;
+                        }
+                    }
+                }
+            }
+        }
+
+        // Subgraphs' activations contain those of their operators
+        for fraction in workers.iter_mut().flatten().flatten() {
+            *fraction = fraction.min(1.0);
+        }
+
+        Self {
+            span,
+            bucket_duration: trace.clock.elapsed(width as Nanos),
+            workers,
+        }
+    }
+
+    pub fn buckets(&self) -> usize {
+        self.workers.first().map_or(0, Vec::len)
+    }
+}
+
+#[derive(Debug, Clone, Properties)]
+pub struct WorkerHeatmapProps {
+    pub heatmap: Rc<Heatmap>,
+}
+
+#[derive(Debug)]
+pub enum Message {
+    Show(Activity),
+}
+
+/// A worker × time grid shaded by how much of each span a worker spent on an activity
+#[derive(Debug)]
+pub struct WorkerHeatmap {
+    link: ComponentLink<Self>,
+    properties: WorkerHeatmapProps,
+    shown: Activity,
+}
+
+impl WorkerHeatmap {
+    fn view_cell(&self, bucket: usize, fractions: &[f64; 4]) -> Html {
+        let (r, g, b) = self.shown.rgb();
+        let style = format!(
+            "background: rgba({}, {}, {}, {:.3})",
+            r,
+            g,
+            b,
+            fractions[self.shown.index()],
+        );
+
+        let start = bucket as u64 * self.properties.heatmap.bucket_duration;
+        let mut title = format!(
+            "{} to {}",
+            format_duration(start),
+            format_duration(start + self.properties.heatmap.bucket_duration),
+        );
+        for activity in &Activity::ALL {
+            title.push_str(&format!(
+                "\n{}: {:.1}%",
+                activity,
+                fractions[activity.index()] * 100.0,
+            ));
+        }
+
+        html! { <div class="heatmap-cell" style=style title=title></div> }
+    }
+}
+
+impl Component for WorkerHeatmap {
+    type Message = Message;
+    type Properties = WorkerHeatmapProps;
+
+    fn create(properties: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self {
+            link,
+            properties,
+            shown: Activity::Parked,
+        }
+    }
+
+    fn update(&mut self, message: Self::Message) -> ShouldRender {
+        match message {
+            Message::Show(activity) => self.shown = activity,
+        }
+
+        true
+    }
+
+    fn change(&mut self, properties: Self::Properties) -> ShouldRender {
+        let changed = !Rc::ptr_eq(&self.properties.heatmap, &properties.heatmap);
+        self.properties = properties;
+
+        changed
+    }
+
+    fn view(&self) -> Html {
+        let heatmap = &self.properties.heatmap;
+
+        let activities = Activity::ALL.iter().map(|&activity| {
+            html! {
+                <label>
+                    <input
+                        type="radio"
+                        name="heatmap-activity"
+                        checked=self.shown == activity
+                        onclick=self.link.callback(move |_| Message::Show(activity))
+                    />
+                    { activity }
+                </label>
+            }
+        });
+
+        let rows = heatmap.workers.iter().enumerate().map(|(worker, buckets)| {
+            html! {
+                <>
+                    <div class="heatmap-worker">{ format!("Worker {}", worker) }</div>
+                    { for buckets.iter().enumerate().map(|(bucket, fractions)| self.view_cell(bucket, fractions)) }
+                </>
+            }
+        });
+
+        let columns = format!(
+            "grid-template-columns: 80px repeat({}, 1fr)",
+            heatmap.buckets(),
+        );
+
+        html! {
+            <details id="worker-heatmap" open=true>
+                <summary>{ "Worker activity" }</summary>
+                <p class="heatmap-activities">{ for activities }</p>
+                <div class="heatmap-grid" style=columns>{ for rows }</div>
+            </details>
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Activity, Heatmap};
+    use crate::{
+        data::TimelineEvent,
+        demo::{loaded_demo, DemoConfig},
+    };
+
+    #[test]
+    fn splits_events_between_buckets() {
+        let profile = loaded_demo();
+        let heatmap = Heatmap::new(&profile.trace, &profile.lanes, 50);
+
+        assert_eq!(heatmap.workers.len(), DemoConfig::default().workers);
+        assert!(heatmap.workers.iter().all(|buckets| buckets.len() == 50));
+        assert!(heatmap
+            .workers
+            .iter()
+            .flatten()
+            .flatten()
+            .all(|fraction| (0.0..=1.0).contains(fraction)));
+
+        // Parks don't overlap, so no time is lost to clamping
+        let clock = profile.trace.clock;
+        let width = (heatmap.span.end - heatmap.span.start) as f64 / 50.0;
+        for (worker, buckets) in heatmap.workers.iter().enumerate() {
+            let parked: f64 = buckets
+                .iter()
+                .map(|fractions| fractions[Activity::Parked as usize] * width)
+                .sum();
+
+            let expected: u64 = profile
+                .events
+                .iter()
+                .filter(|event| event.worker == worker && event.event == TimelineEvent::Parked)
+                .map(|event| event.duration)
+                .sum();
+            let expected = expected as f64 / clock.elapsed(1) as f64;
+
+            assert!(
+                (parked - expected).abs() <= expected * 1e-6 + 1.0,
+                "worker {} was parked for {} ticks rather than {}",
+                worker,
+                parked,
+                expected,
+            );
+        }
+    }
+}
@@ -1,4 +1,4 @@
-#![recursion_limit = "256"]
+#![recursion_limit = "512"]
 
 // TODO: God this needs a refactor so bad, just every bit of it
 // TODO: We need to take advantage of incrementality here so that
@@ -9,34 +9,74 @@
 //       native client, druid looks promising and pretty similar to
 //       the yew setup, so that could be reasonably easy to port to
 
-mod data;
+mod console;
+pub mod data;
+pub mod demo;
+mod download;
+pub mod filter;
+mod flame;
+pub mod heatmap;
+pub mod loader;
+mod notifications;
+pub mod query;
+mod recent;
+pub mod spine;
 mod timeline;
+pub mod traffic;
+pub mod view;
 
 use crate::{
-    data::{ProfilingData, WorkerTimelineEvent},
-    timeline::{constants::NS_TO_MS, Timeline},
+    console::Console,
+    data::{Node, Subgraph, TimelineEvent, WorkerTimelineEvent},
+    demo::{demo_profile, DemoConfig},
+    download::download,
+    filter::{EventFilter, FilterPanel},
+    flame::FlameGraph,
+    heatmap::{Heatmap, WorkerHeatmap},
+    loader::{
+        LoadId, LoadProgress, LoadStage, LoadedProfile, LoaderRequest, LoaderResponse, Problem,
+        ProfileLoader, ProfileTrace,
+    },
+    notifications::{Notification, NotificationCenter},
+    recent::{file_from_bytes, ProfileStore, RecentFile, RecentFiles},
+    spine::{SpinePanel, SpineSummary},
+    timeline::Timeline,
+    traffic::{TrafficPanel, TrafficSummary},
 };
-use anyhow::{Context, Error, Result};
-use std::{cmp::Ordering, rc::Rc};
+use anyhow::{anyhow, Context, Result};
+use gigatrace::{payload::PayloadId, TrackInfo};
+use std::{collections::BTreeSet, ops::Range, rc::Rc};
 use tracing::Level;
 use tracing_wasm::WASMLayerConfigBuilder;
 use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
-use web_sys::{File, HtmlParagraphElement};
+use web_sys::{DragEvent, File, FileList, MouseEvent, UrlSearchParams};
 use yew::{
-    events::{ChangeData, InputData},
+    agent::{Bridge, Bridged, Threaded},
+    events::ChangeData,
+    format::{Binary, Json, Nothing},
     html,
     services::{
-        reader::{FileData, ReaderTask},
+        fetch::{FetchTask, Request, Response},
+        reader::{FileChunk, ReaderTask},
         storage::Area,
-        ReaderService, StorageService,
+        FetchService, ReaderService, StorageService, Task,
     },
-    Component, ComponentLink, Html, NodeRef, ShouldRender,
+    Component, ComponentLink, Html, ShouldRender,
 };
 
+#[cfg(target_arch = "wasm32")]
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
-const LAST_FILE_KEY: &str = "differential-dashboard.last-opened-file";
+const RECENT_FILES_KEY: &str = "differential-dashboard.recent-files";
+
+/// Profiles are read in chunks of this many bytes, so that large
+/// profiles can be parsed as they're read
+const CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// How many nanoseconds out of order the events of streamed profiles can be
+/// logged in, workers flush their logs in batches that overlap by about this much
+const STREAM_MAX_DELAY: u64 = 1_000_000_000;
 
 #[wasm_bindgen]
 pub fn run_app() -> Result<(), JsValue> {
@@ -51,85 +91,861 @@ pub fn run_app() -> Result<(), JsValue> {
     Ok(())
 }
 
+/// Runs the profile loader when the module is started within a web worker,
+/// the page itself has a window while workers don't
+#[wasm_bindgen(start)]
+pub fn start_worker() {
+    if web_sys::window().is_none() {
+        ProfileLoader::register();
+    }
+}
+
+/// A loaded profile, each one gets its own tab
+#[derive(Debug)]
+struct Profile {
+    /// Identifies the tab, names aren't used since reopening a profile replaces its tab
+    id: usize,
+    name: Rc<str>,
+    events: Rc<[WorkerTimelineEvent]>,
+    nodes: Rc<[Node]>,
+    subgraphs: Rc<[Subgraph]>,
+    lanes: Rc<[TimelineEvent]>,
+    trace: Rc<ProfileTrace>,
+    parks: Rc<[TrackInfo<PayloadId>]>,
+    heatmap: Rc<Heatmap>,
+    traffic: Rc<TrafficSummary>,
+    spines: Rc<SpineSummary>,
+    /// The end of the last event in milliseconds
+    duration: f64,
+    problems: Rc<[Problem]>,
+    /// The profile's json with its problems repaired
+    repaired: Option<Rc<str>>,
+}
+
+/// Where a profile being loaded came from
 #[derive(Debug)]
+enum LoadSource {
+    /// A newly opened profile, its file is stored once it's been loaded
+    Opened(File),
+    /// A profile that's still downloading, it's `Opened` once its contents arrive
+    Downloading,
+    /// A recent profile, its file is already stored
+    Recent { size: usize },
+}
+
+/// A profile that's being read, downloaded or loaded by the `ProfileLoader`
+struct PendingLoad {
+    id: LoadId,
+    name: String,
+    source: LoadSource,
+    progress: LoadProgress,
+    /// The read or download of the profile, dropping it cancels them
+    task: Option<Box<dyn Task>>,
+}
+
 struct Dashboard {
     link: ComponentLink<Self>,
-    file_task: Option<(ReaderTask, File)>,
-    events: Rc<[WorkerTimelineEvent]>,
-    alerts: Vec<Alert>,
+    loader: Box<dyn Bridge<ProfileLoader>>,
+    loads: Vec<PendingLoad>,
+    next_load_id: LoadId,
+    tabs: Vec<Profile>,
+    next_tab_id: usize,
+    active_tab: Option<usize>,
+    /// A tab shown below the active one, both share `synced_view`
+    stacked_tab: Option<usize>,
+    synced_view: Option<Range<u64>>,
+    /// Every notification posted so far, shared with the `NotificationCenter`
+    notifications: Rc<Vec<Notification>>,
+    next_notification_id: usize,
     storage: Option<StorageService>,
-    cutoff_display: NodeRef,
-    cutoff_value: u64,
+    filter: Rc<EventFilter>,
+    recent_files: RecentFiles,
+    profiles: Option<ProfileStore>,
 }
 
 impl Dashboard {
     fn dispatch(&mut self, message: Message) -> ShouldRender {
         match message {
             Message::LoadFile { file } => {
-                if let Some((_, file)) = self.file_task.as_ref() {
-                    self.alerts
-                        .push(Alert::LoadFileCanceled { file: file.clone() })
+                let (name, source) = (file.name(), LoadSource::Opened(file.clone()));
+                if let Err(error) = self.read_profile(name.clone(), &file, source) {
+                    self.notify(Notification::error(
+                        format!("Failed to load {}", name),
+                        &error,
+                    ));
                 }
 
-                match self.load_file(file.clone()) {
-                    Ok(task) => {
-                        self.file_task = Some((task, file));
-                        false
+                true
+            }
+
+            Message::LoadFiles { files } => {
+                let mut render = false;
+                for idx in 0..files.length() {
+                    if let Some(file) = files.get(idx) {
+                        render |= self.dispatch(Message::LoadFile { file });
                     }
+                }
 
-                    Err(error) => {
-                        self.alerts.push(Alert::FailedFetch { file, error });
-                        true
+                render
+            }
+
+            Message::FileChunk { id, chunk } => {
+                let load = match self.loads.iter_mut().find(|load| load.id == id) {
+                    Some(load) => load,
+                    None => return false,
+                };
+
+                match chunk {
+                    Some(FileChunk::Started { .. }) => return false,
+
+                    Some(FileChunk::DataChunk { data, progress }) => {
+                        load.progress = LoadProgress::new(LoadStage::Reading, progress as f64);
+
+                        self.loader.send(LoaderRequest::Chunk { id, data });
+                    }
+
+                    Some(FileChunk::Finished) => {
+                        // Parsing keeps up with reading, so it's close to done by now
+                        load.task = None;
+                        load.progress = LoadProgress::new(LoadStage::Parsing, 1.0);
+
+                        self.loader.send(LoaderRequest::Finish { id });
+                    }
+
+                    None => {
+                        let name = load.name.clone();
+                        self.cancel_load(id);
+
+                        self.notify(Notification::warning(format!("Failed to read {}", name)));
                     }
                 }
+
+                true
             }
 
-            Message::FileReady { data } => {
-                // Discard the task once it's completed
-                let _ = self.file_task.take();
+            Message::FetchProfile { url } => {
+                let id = self.next_load_id();
 
-                match data {
-                    Ok(mut data) => {
-                        tracing::info!("loaded file data");
-                        data.timeline_events
-                            .sort_unstable_by_key(|event| event.event.clone());
+                match self.fetch_profile(id, &url) {
+                    Ok(task) => self.loads.push(PendingLoad {
+                        id,
+                        name: url,
+                        source: LoadSource::Downloading,
+                        progress: LoadProgress::new(LoadStage::Reading, 0.0),
+                        task: Some(Box::new(task)),
+                    }),
+
+                    Err(error) => self.notify(Notification::error(
+                        format!("Failed to download {}", url),
+                        &error,
+                    )),
+                }
 
-                        self.events = Rc::from(data.timeline_events);
-                        true
+                true
+            }
+
+            Message::FetchReady { id, content } => {
+                let load = match self.loads.iter_mut().find(|load| load.id == id) {
+                    Some(load) => load,
+                    None => return false,
+                };
+                load.task = None;
+
+                // The contents are kept in a file so they can be stored once they've loaded
+                let file = content
+                    .and_then(|content| Ok((file_from_bytes(&load.name, &content)?, content)));
+
+                match file {
+                    Ok((file, content)) => {
+                        load.source = LoadSource::Opened(file);
+                        load.progress = LoadProgress::new(LoadStage::Parsing, 0.0);
+
+                        self.loader.send(LoaderRequest::Load {
+                            id,
+                            name: load.name.clone(),
+                            content,
+                        });
                     }
 
                     Err(error) => {
-                        self.alerts.push(Alert::generic(error));
-                        true
+                        let url = load.name.clone();
+                        self.take_load(id);
+
+                        self.notify(Notification::error(
+                            format!("Failed to download {}", url),
+                            &error,
+                        ));
+                    }
+                }
+
+                true
+            }
+
+            Message::CancelLoad(id) => {
+                self.cancel_load(id);
+                true
+            }
+
+            Message::ProfileStoreReady(profiles) => match profiles {
+                Ok(profiles) => {
+                    self.profiles = Some(profiles);
+
+                    // Reopen the last profile unless the user's already picked one
+                    let reopen = self
+                        .recent_files
+                        .last_opened()
+                        .filter(|_| self.loads.is_empty() && self.tabs.is_empty())
+                        .map(|file| file.name.clone());
+                    if let Some(name) = reopen {
+                        self.dispatch(Message::OpenRecent { name });
                     }
+
+                    false
+                }
+
+                Err(error) => {
+                    tracing::warn!("recent profiles won't be remembered: {:#}", error);
+                    false
                 }
+            },
+
+            Message::OpenRecent { name } => {
+                let profiles = match self.profiles.as_ref() {
+                    Some(profiles) => profiles,
+                    None => {
+                        self.notify(Notification::warning(
+                            "Recent profiles aren't available yet",
+                        ));
+                        return true;
+                    }
+                };
+
+                let callback = {
+                    let name = name.clone();
+                    self.link.callback(move |file| Message::RecentLoaded {
+                        name: name.clone(),
+                        file,
+                    })
+                };
+
+                if let Err(error) = profiles.get(&name, callback) {
+                    self.notify(Notification::error(
+                        format!("Failed to open {}", name),
+                        &error,
+                    ));
+                    return true;
+                }
+
+                false
             }
 
-            Message::ChangeCutoff(cutoff) => {
-                self.cutoff_value = cutoff;
+            Message::RecentLoaded { name, file } => {
+                let read = file
+                    .and_then(|file| {
+                        file.with_context(|| format!("{} is no longer stored, open it again", name))
+                    })
+                    .and_then(|file| {
+                        let size = file.size() as usize;
+                        self.read_profile(name.clone(), &file, LoadSource::Recent { size })
+                    });
 
-                if let Some(cutoff_display) = self.cutoff_display.cast::<HtmlParagraphElement>() {
-                    // TODO: Buffer this?
-                    cutoff_display.set_inner_text(&format!("Event time cutoff: {}ms", cutoff));
+                if let Err(error) = read {
+                    self.reopen_failed(&name, error.chain().map(ToString::to_string));
                 }
 
                 true
             }
+
+            Message::Loader(response) => {
+                match response {
+                    LoaderResponse::Progress { id, progress } => {
+                        if let Some(load) = self.loads.iter_mut().find(|load| load.id == id) {
+                            load.progress = progress;
+                        }
+                    }
+
+                    LoaderResponse::Loaded { id, profile } => {
+                        if let Some(load) = self.take_load(id) {
+                            match load.source {
+                                LoadSource::Opened(file) => {
+                                    self.remember_profile(&load.name, &file)
+                                }
+                                // Downloads are `Opened` before they start loading
+                                LoadSource::Downloading => {}
+                                LoadSource::Recent { size } => {
+                                    self.recent_files.opened(RecentFile {
+                                        name: load.name,
+                                        size,
+                                    });
+                                    self.save_recent_files();
+                                }
+                            }
+
+                            self.notify(Notification::info(format!(
+                                "Loaded {} ({} events)",
+                                profile.name,
+                                profile.events.len(),
+                            )));
+                            self.open_profile(*profile);
+                        }
+                    }
+
+                    LoaderResponse::Failed { id, error } => {
+                        if let Some(load) = self.take_load(id) {
+                            match load.source {
+                                LoadSource::Opened(_) | LoadSource::Downloading => {
+                                    self.notify(Notification::error_chain(
+                                        format!("Failed to load {}", load.name),
+                                        error,
+                                    ))
+                                }
+                                LoadSource::Recent { .. } => self.reopen_failed(&load.name, error),
+                            }
+                        }
+                    }
+                }
+
+                true
+            }
+
+            Message::DownloadRepaired => {
+                let repaired = self
+                    .active_profile()
+                    .and_then(|profile| Some((profile.name.clone(), profile.repaired.clone()?)));
+
+                if let Some((name, repaired)) = repaired {
+                    let stem = name.strip_suffix(".json").unwrap_or(&name);
+                    let file_name = format!("{}.repaired.json", stem);
+
+                    if let Err(error) = download(&file_name, "application/json", &repaired) {
+                        self.notify(Notification::error(
+                            format!("Failed to download {}", file_name),
+                            &error,
+                        ));
+                        return true;
+                    }
+                }
+
+                false
+            }
+
+            Message::ClearNotifications => {
+                self.notifications = Rc::new(Vec::new());
+                true
+            }
+
+            Message::ClearRecent => {
+                for file in self.recent_files.clear() {
+                    self.forget_profile(&file.name);
+                }
+                self.save_recent_files();
+
+                true
+            }
+
+            Message::LoadDemo => {
+                let config = DemoConfig::default();
+                let name = format!("demo-{:x}.json", config.seed);
+
+                // Serializing the profile can't fail since all of its keys are strings
+                let content = serde_json::to_vec(&demo_profile(&config))
+                    .expect("failed to serialize the demo profile");
+                match file_from_bytes(&name, &content) {
+                    Ok(file) => self.start_load(name, content, LoadSource::Opened(file)),
+                    Err(error) => self.notify(Notification::error(
+                        format!("Failed to load {}", name),
+                        &error,
+                    )),
+                }
+
+                true
+            }
+
+            Message::ChangeFilter(mut filter) => {
+                // The focused scope is set by the flame graph rather than the filter panel
+                filter.operators = self.filter.operators.clone();
+                self.filter = Rc::new(filter);
+                true
+            }
+
+            Message::FocusScope(operators) => {
+                Rc::make_mut(&mut self.filter).operators = operators;
+                true
+            }
+
+            Message::SelectTab(id) => {
+                // Selecting the stacked tab swaps it with the active one
+                if self.stacked_tab == Some(id) {
+                    self.stacked_tab = self.active_tab;
+                }
+                self.active_tab = Some(id);
+
+                true
+            }
+
+            Message::CloseTab(id) => {
+                let idx = match self.tabs.iter().position(|tab| tab.id == id) {
+                    Some(idx) => idx,
+                    None => return false,
+                };
+                self.tabs.remove(idx);
+
+                if self.stacked_tab == Some(id) {
+                    self.unstack();
+                }
+                if self.active_tab == Some(id) {
+                    // Fall back to the neighbouring tab
+                    self.active_tab = self
+                        .tabs
+                        .get(idx)
+                        .or_else(|| self.tabs.last())
+                        .map(|tab| tab.id);
+
+                    if self.active_tab.is_some() && self.active_tab == self.stacked_tab {
+                        self.unstack();
+                    }
+                }
+
+                true
+            }
+
+            Message::StackTab(id) => {
+                self.unstack();
+                self.stacked_tab = id.filter(|&id| Some(id) != self.active_tab);
+
+                true
+            }
+
+            Message::ViewChanged(view) => {
+                if self.stacked_tab.is_some() {
+                    self.synced_view = Some(view);
+                    true
+                } else {
+                    false
+                }
+            }
         }
     }
 
-    fn load_file(&mut self, file: File) -> Result<ReaderTask> {
-        let callback = self.link.callback(move |file_data: FileData| {
-            let data = serde_json::from_slice(&file_data.content).map_err(Into::into);
-            tracing::debug!("Loaded file {:?}\nContent: {:?}", file_data.name, data);
+    fn active_profile(&self) -> Option<&Profile> {
+        self.tabs.iter().find(|tab| Some(tab.id) == self.active_tab)
+    }
+
+    fn unstack(&mut self) {
+        self.stacked_tab = None;
+        self.synced_view = None;
+    }
 
-            Message::FileReady { data }
+    /// Streams the file to the `ProfileLoader` as it's read, it's opened once it's been loaded
+    fn read_profile(&mut self, name: String, file: &File, source: LoadSource) -> Result<()> {
+        let id = self.next_load_id();
+        let task = self.read_file(id, file)?;
+
+        self.loader.send(LoaderRequest::Begin {
+            id,
+            name: name.clone(),
+            max_delay: STREAM_MAX_DELAY,
+        });
+        self.loads.push(PendingLoad {
+            id,
+            name,
+            source,
+            progress: LoadProgress::new(LoadStage::Reading, 0.0),
+            task: Some(Box::new(task)),
         });
 
+        Ok(())
+    }
+
+    fn read_file(&mut self, id: LoadId, file: &File) -> Result<ReaderTask> {
+        let callback = self
+            .link
+            .callback(move |chunk| Message::FileChunk { id, chunk });
+
         ReaderService::new()
-            .read_file(file.clone(), callback)
+            .read_file_by_chunks(file.clone(), callback, CHUNK_SIZE)
             .with_context(|| format!("failed fetching file {}", file.name()))
     }
+
+    fn fetch_profile(&mut self, id: LoadId, url: &str) -> Result<FetchTask> {
+        let request = Request::get(url)
+            .body(Nothing)
+            .with_context(|| format!("invalid profile url {}", url))?;
+
+        let callback = self.link.callback(move |response: Response<Binary>| {
+            let status = response.status();
+            let content = if status.is_success() {
+                response.into_body()
+            } else {
+                Err(anyhow!("the server responded with {}", status))
+            };
+
+            Message::FetchReady { id, content }
+        });
+
+        FetchService::fetch_binary(request, callback)
+            .with_context(|| format!("failed to download {}", url))
+    }
+
+    fn next_load_id(&mut self) -> LoadId {
+        let id = self.next_load_id;
+        self.next_load_id += 1;
+
+        id
+    }
+
+    /// Hands the profile off to the `ProfileLoader`, it's opened once it's been loaded
+    fn start_load(&mut self, name: String, content: Vec<u8>, source: LoadSource) {
+        let id = self.next_load_id();
+
+        self.loader.send(LoaderRequest::Load {
+            id,
+            name: name.clone(),
+            content,
+        });
+        self.loads.push(PendingLoad {
+            id,
+            name,
+            source,
+            progress: LoadProgress::new(LoadStage::Parsing, 0.0),
+            task: None,
+        });
+    }
+
+    /// Posts a notification, stamping it with an id and the current time
+    fn notify(&mut self, mut notification: Notification) {
+        notification.id = self.next_notification_id;
+        notification.time = js_sys::Date::now();
+        self.next_notification_id += 1;
+
+        Rc::make_mut(&mut self.notifications).push(notification);
+    }
+
+    fn take_load(&mut self, id: LoadId) -> Option<PendingLoad> {
+        let idx = self.loads.iter().position(|load| load.id == id)?;
+        Some(self.loads.remove(idx))
+    }
+
+    fn cancel_load(&mut self, id: LoadId) {
+        if let Some(load) = self.take_load(id) {
+            // Dropping the task stops the read or download
+            drop(load.task);
+            self.loader.send(LoaderRequest::Cancel { id });
+        }
+    }
+
+    /// Reports a recent profile that couldn't be reopened and forgets it
+    fn reopen_failed<I>(&mut self, name: &str, causes: I)
+    where
+        I: IntoIterator<Item = String>,
+    {
+        self.notify(Notification::error_chain(
+            format!("Failed to reopen {}", name),
+            causes,
+        ));
+
+        self.forget_profile(name);
+        self.save_recent_files();
+    }
+
+    fn open_profile(&mut self, profile: LoadedProfile) {
+        tracing::info!("loaded file data");
+
+        let LoadedProfile {
+            name,
+            events,
+            nodes,
+            subgraphs,
+            lanes,
+            trace,
+            parks,
+            heatmap,
+            traffic,
+            spines,
+            duration,
+            problems,
+            repaired,
+        } = profile;
+
+        if !problems.is_empty() {
+            self.notify(Notification {
+                details: problems.iter().map(ToString::to_string).collect(),
+                ..Notification::warning(format!(
+                    "{} has {} problems, it's shown as well as it can be",
+                    name,
+                    problems.len(),
+                ))
+            });
+        }
+
+        let (
+            events,
+            nodes,
+            subgraphs,
+            lanes,
+            trace,
+            parks,
+            heatmap,
+            traffic,
+            spines,
+            problems,
+            repaired,
+        ) = (
+            Rc::from(events),
+            Rc::from(nodes),
+            Rc::from(subgraphs),
+            Rc::from(lanes),
+            Rc::new(trace),
+            Rc::from(parks),
+            Rc::new(heatmap),
+            Rc::new(traffic),
+            Rc::new(spines),
+            Rc::from(problems),
+            repaired.map(Rc::from),
+        );
+
+        // Reopening a profile replaces its tab's contents
+        let id = if let Some(tab) = self.tabs.iter_mut().find(|tab| *tab.name == *name) {
+            tab.events = events;
+            tab.nodes = nodes;
+            tab.subgraphs = subgraphs;
+            tab.lanes = lanes;
+            tab.trace = trace;
+            tab.parks = parks;
+            tab.heatmap = heatmap;
+            tab.traffic = traffic;
+            tab.spines = spines;
+            tab.duration = duration;
+            tab.problems = problems;
+            tab.repaired = repaired;
+
+            tab.id
+        } else {
+            let id = self.next_tab_id;
+            self.next_tab_id += 1;
+
+            self.tabs.push(Profile {
+                id,
+                name: Rc::from(name),
+                events,
+                nodes,
+                subgraphs,
+                lanes,
+                trace,
+                parks,
+                heatmap,
+                traffic,
+                spines,
+                duration,
+                problems,
+                repaired,
+            });
+
+            id
+        };
+
+        if self.stacked_tab == Some(id) {
+            self.unstack();
+        }
+        self.active_tab = Some(id);
+    }
+
+    /// Adds the profile to the recent files and stores its contents so it can be reopened
+    fn remember_profile(&mut self, name: &str, file: &File) {
+        let evicted = self.recent_files.opened(RecentFile {
+            name: name.to_owned(),
+            size: file.size() as usize,
+        });
+
+        for file in evicted {
+            self.forget_profile(&file.name);
+        }
+
+        if let Some(profiles) = self.profiles.as_ref() {
+            if let Err(error) = profiles.put(name, file) {
+                tracing::error!("failed to store profile {}: {:#}", name, error);
+            }
+        }
+
+        self.save_recent_files();
+    }
+
+    fn forget_profile(&mut self, name: &str) {
+        self.recent_files.remove(name);
+
+        if let Some(profiles) = self.profiles.as_ref() {
+            if let Err(error) = profiles.delete(name) {
+                tracing::error!("failed to delete stored profile {}: {:#}", name, error);
+            }
+        }
+    }
+
+    fn save_recent_files(&mut self) {
+        if let Some(storage) = self.storage.as_mut() {
+            storage.store(RECENT_FILES_KEY, Json(&self.recent_files));
+        }
+    }
+
+    fn view_loads(&self) -> Html {
+        let loads = self.loads.iter().map(|load| {
+            let id = load.id;
+
+            html! {
+                <li>
+                    { format!("{}: {}", load.name, load.progress.stage) }
+                    <progress max="1" value=load.progress.fraction.to_string() />
+                    <button onclick=self.link.callback(move |_| Message::CancelLoad(id))>{ "Cancel" }</button>
+                </li>
+            }
+        });
+
+        html! {
+            <ul id="profile-loads">{ for loads }</ul>
+        }
+    }
+
+    fn view_tabs(&self) -> Html {
+        let tabs = self.tabs.iter().map(|tab| {
+            let id = tab.id;
+            let class = if self.active_tab == Some(id) {
+                "profile-tab active"
+            } else if self.stacked_tab == Some(id) {
+                "profile-tab stacked"
+            } else {
+                "profile-tab"
+            };
+
+            let stack = if self.active_tab == Some(id) {
+                html! {}
+            } else {
+                let (label, stacked) = if self.stacked_tab == Some(id) {
+                    ("Unstack", None)
+                } else {
+                    ("Stack", Some(id))
+                };
+
+                html! {
+                    <button
+                        title="Show below the current tab with a synchronized time axis"
+                        onclick=self.link.callback(move |event: MouseEvent| {
+                            // Don't also select the tab
+                            event.stop_propagation();
+                            Message::StackTab(stacked)
+                        })
+                    >
+                        { label }
+                    </button>
+                }
+            };
+
+            html! {
+                <li class=class onclick=self.link.callback(move |_| Message::SelectTab(id))>
+                    <span>{ &*tab.name }</span>
+                    { stack }
+                    <button
+                        title="Close"
+                        onclick=self.link.callback(move |event: MouseEvent| {
+                            event.stop_propagation();
+                            Message::CloseTab(id)
+                        })
+                    >
+                        { "×" }
+                    </button>
+                </li>
+            }
+        });
+
+        html! {
+            <ul id="profile-tabs">{ for tabs }</ul>
+        }
+    }
+
+    /// Every tab keeps its timeline around so that switching tabs preserves
+    /// their views, only the active and stacked tabs are shown
+    fn view_timelines(&self) -> Html {
+        let timelines = self.tabs.iter().map(|tab| {
+            let visible = self.active_tab == Some(tab.id) || self.stacked_tab == Some(tab.id);
+            let stacked = visible && self.stacked_tab.is_some();
+            let view = self.synced_view.clone().filter(|_| stacked);
+
+            html! {
+                <div key=tab.id.to_string() class=if visible { "profile-timeline" } else { "profile-timeline hidden" }>
+                    <p class=if stacked { "profile-name" } else { "profile-name hidden" }>{ &*tab.name }</p>
+                    <Timeline
+                        profile=tab.name.clone()
+                        events=tab.events.clone()
+                        lanes=tab.lanes.clone()
+                        trace=tab.trace.clone()
+                        parks=tab.parks.clone()
+                        duration=tab.duration
+                        scale=50.0
+                        filter=self.filter.clone()
+                        visible=visible
+                        view=view
+                        onviewchange=self.link.callback(Message::ViewChanged)
+                    />
+                </div>
+            }
+        });
+
+        html! {
+            <div id="timelines">{ for timelines }</div>
+        }
+    }
+
+    fn view_problems(&self) -> Html {
+        let profile = match self.active_profile() {
+            Some(profile) if !profile.problems.is_empty() => profile,
+            _ => return html! {},
+        };
+
+        let problems = profile
+            .problems
+            .iter()
+            .map(|problem| html! { <li>{ problem }</li> });
+
+        let repair = if profile.repaired.is_some() {
+            html! {
+                <button onclick=self.link.callback(|_| Message::DownloadRepaired)>
+                    { "Download repaired profile" }
+                </button>
+            }
+        } else {
+            html! {}
+        };
+
+        html! {
+            <details id="profile-problems">
+                <summary>{ format!("{} problems in {}", profile.problems.len(), profile.name) }</summary>
+                <ul>{ for problems }</ul>
+                { repair }
+            </details>
+        }
+    }
+
+    fn view_recent_files(&self) -> Html {
+        if self.recent_files.is_empty() {
+            return html! {};
+        }
+
+        let files = self.recent_files.iter().map(|file| {
+            let name = file.name.clone();
+
+            html! {
+                <li onclick=self.link.callback(move |_| Message::OpenRecent { name: name.clone() })>
+                    { format!("{} ({:.1} MB)", file.name, file.size as f64 / 1_000_000.0) }
+                </li>
+            }
+        });
+
+        html! {
+            <div id="recent-files">
+                <p>{ "Recent profiles" }</p>
+                <ul>{ for files }</ul>
+                <button onclick=self.link.callback(|_| Message::ClearRecent)>{ "Clear recent profiles" }</button>
+            </div>
+        }
+    }
 }
 
 impl Component for Dashboard {
@@ -139,22 +955,39 @@ impl Component for Dashboard {
     fn create(_properties: Self::Properties, link: ComponentLink<Self>) -> Self {
         let storage = StorageService::new(Area::Local).ok();
 
-        if let Some(_file) = storage
+        let recent_files = storage
             .as_ref()
-            .and_then(|storage| storage.restore::<Result<String>>(LAST_FILE_KEY).ok())
-        {
-            // TODO: Load the most recently loaded file
-            // FetchService::fetch(Request::get(file), |response| {})
+            .and_then(|storage| {
+                let Json(recent_files) = storage.restore(RECENT_FILES_KEY);
+                recent_files.ok()
+            })
+            .unwrap_or_default();
+
+        // Profiles linked to with `?profile=<url>` take priority over the
+        // last opened profile, which is reopened once the store is ready
+        if let Some(url) = profile_url_param() {
+            link.send_message(Message::FetchProfile { url });
         }
+        ProfileStore::open(link.callback(Message::ProfileStoreReady));
+
+        let loader = ProfileLoader::bridge(link.callback(Message::Loader));
 
         Self {
             link,
-            file_task: None,
-            alerts: Vec::new(),
-            events: Rc::from(Vec::new()),
+            loader,
+            loads: Vec::new(),
+            next_load_id: 0,
+            tabs: Vec::new(),
+            next_tab_id: 0,
+            active_tab: None,
+            stacked_tab: None,
+            synced_view: None,
+            notifications: Rc::new(Vec::new()),
+            next_notification_id: 0,
             storage,
-            cutoff_display: NodeRef::default(),
-            cutoff_value: 0,
+            filter: Rc::new(EventFilter::default()),
+            recent_files,
+            profiles: None,
         }
     }
 
@@ -167,89 +1000,137 @@ impl Component for Dashboard {
     }
 
     fn view(&self) -> Html {
-        let duration = self
-            .events
-            .iter()
-            .map(|event| event.end_time() as f64 / NS_TO_MS)
-            .max_by(|x, y| x.partial_cmp(y).unwrap_or(Ordering::Less))
-            .unwrap_or(0.0);
+        let (filter_panel, heatmap, traffic, spines, flame_graph, console) =
+            match self.active_profile() {
+                Some(profile) => (
+                    html! {
+                        <FilterPanel
+                            events=profile.events.clone()
+                            onchange=self.link.callback(Message::ChangeFilter)
+                        />
+                    },
+                    html! { <WorkerHeatmap heatmap=profile.heatmap.clone() /> },
+                    html! { <TrafficPanel traffic=profile.traffic.clone() /> },
+                    html! { <SpinePanel spines=profile.spines.clone() /> },
+                    html! {
+                        <FlameGraph
+                            nodes=profile.nodes.clone()
+                            subgraphs=profile.subgraphs.clone()
+                            onfocus=self.link.callback(Message::FocusScope)
+                        />
+                    },
+                    html! { <Console events=profile.events.clone() nodes=profile.nodes.clone() /> },
+                ),
+                None => (html! {}, html! {}, html! {}, html! {}, html! {}, html! {}),
+            };
 
         html! {
-            <>
+            <div
+                id="dashboard"
+                ondragover=self.link.batch_callback(|event: DragEvent| {
+                    // Allows dropping files onto the page
+                    event.prevent_default();
+                    Vec::new()
+                })
+                ondrop=self.link.batch_callback(|event: DragEvent| {
+                    event.prevent_default();
+
+                    event
+                        .data_transfer()
+                        .and_then(|data| data.files())
+                        .map(|files| vec![Message::LoadFiles { files }])
+                        .unwrap_or_default()
+                })
+            >
                 <div id="menu">
-                    <p>{ "Choose a file to visualize profile events for" }</p>
-                    <input type="file" multiple=false accept=".json,.json5" onchange=self.link.callback(move |change_data| {
+                    <p>{ "Choose or drop files to visualize profile events for" }</p>
+                    <input type="file" multiple=true accept=".json,.json5" onchange=self.link.batch_callback(move |change_data| {
                             if let ChangeData::Files(files) = change_data {
-                                // TODO: Can the user give no files?
-                                debug_assert_eq!(files.length(), 1);
-
-                                Message::LoadFile { file: files.get(0).unwrap() }
+                                vec![Message::LoadFiles { files }]
                             } else {
                                 unreachable!()
                             }
                         })
                     />
-
-                    <p ref=self.cutoff_display.clone()>{ format!("Event time cutoff: {}ms", self.cutoff_value) }</p>
-                    <input type="range" min="0" max="10000" step="1" value={ self.cutoff_value } oninput=self.link.callback(move |input_data: InputData| {
-                            Message::ChangeCutoff(input_data.value.parse().unwrap())
+                    <input
+                        type="url"
+                        placeholder="or load a profile from a url"
+                        onchange=self.link.batch_callback(|change_data| match change_data {
+                            ChangeData::Value(url) if !url.trim().is_empty() => {
+                                vec![Message::FetchProfile { url: url.trim().to_owned() }]
+                            }
+                            _ => Vec::new(),
                         })
                     />
+                    <button onclick=self.link.callback(|_| Message::LoadDemo)>{ "Load demo profile" }</button>
+                    { self.view_recent_files() }
+                    { filter_panel }
                 </div>
 
-                { self.alerts.iter().map(Alert::render).collect::<Html>() }
-
-                <Timeline
-                    events=self.events.clone()
-                    duration=duration
-                    scale=50.0
-                    event_cutoff=self.cutoff_value
+                <NotificationCenter
+                    notifications=self.notifications.clone()
+                    onclear=self.link.callback(|()| Message::ClearNotifications)
                 />
-            </>
-        }
-    }
-
-    fn rendered(&mut self, _is_first_render: bool) {
-        self.alerts.clear();
-    }
-}
-
-#[derive(Debug)]
-enum Alert {
-    LoadFileCanceled { file: File },
-    FailedFetch { file: File, error: Error },
-    Generic(String),
-}
 
-impl Alert {
-    fn generic<T>(error: T) -> Self
-    where
-        T: ToString,
-    {
-        Self::Generic(error.to_string())
-    }
-
-    // TODO: Make this a full-blown component with modal popups
-    fn render(&self) -> Html {
-        let content = match self {
-            Self::LoadFileCanceled { file } => format!("Canceled loading {}", file.name()),
-            Self::FailedFetch { file, error } => {
-                format!("Failed to load {}: {}", file.name(), error)
-            }
-            Self::Generic(message) => message.clone(),
-        };
-
-        html! {
-            <div class="alert-popup">
-                { content }
+                { self.view_loads() }
+                { self.view_tabs() }
+                { self.view_problems() }
+                { self.view_timelines() }
+                { heatmap }
+                { traffic }
+                { spines }
+                { flame_graph }
+                { console }
             </div>
         }
     }
 }
 
+/// Reads the `profile` query parameter, a url to load a profile from
+fn profile_url_param() -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+    UrlSearchParams::new_with_str(&search).ok()?.get("profile")
+}
+
 #[derive(Debug)]
 pub enum Message {
-    LoadFile { file: File },
-    FileReady { data: Result<ProfilingData> },
-    ChangeCutoff(u64),
+    LoadFiles {
+        files: FileList,
+    },
+    LoadFile {
+        file: File,
+    },
+    FileChunk {
+        id: LoadId,
+        chunk: Option<FileChunk>,
+    },
+    FetchProfile {
+        url: String,
+    },
+    FetchReady {
+        id: LoadId,
+        content: Result<Vec<u8>>,
+    },
+    CancelLoad(LoadId),
+    ProfileStoreReady(Result<ProfileStore>),
+    OpenRecent {
+        name: String,
+    },
+    RecentLoaded {
+        name: String,
+        file: Result<Option<File>>,
+    },
+    ClearRecent,
+    LoadDemo,
+    ClearNotifications,
+    DownloadRepaired,
+    Loader(LoaderResponse),
+    ChangeFilter(EventFilter),
+    /// Filters the timeline to the operators within a scope, or stops with `None`
+    FocusScope(Option<BTreeSet<usize>>),
+    SelectTab(usize),
+    CloseTab(usize),
+    /// Stacks a tab below the active one, or unstacks with `None`
+    StackTab(Option<usize>),
+    ViewChanged(Range<u64>),
 }
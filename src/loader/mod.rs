@@ -0,0 +1,695 @@
+mod stream;
+mod validate;
+
+pub use stream::ProfileParser;
+pub use validate::{validate, Problem};
+
+use crate::{
+    data::{Node, ProfilingData, Subgraph, TimelineEvent, WorkerTimelineEvent},
+    heatmap::{Heatmap, HEATMAP_BUCKETS},
+    spine::SpineSummary,
+    timeline::constants::NS_TO_MS,
+    traffic::{TrafficSummary, TRAFFIC_BUCKETS},
+};
+use anyhow::{Context, Error, Result};
+use gigatrace::{
+    compress::CompressedBlockPool,
+    payload::{Arg, ArgValue, PayloadId},
+    trace::{BlockPool, BlockStore, TraceClock, TraceEvent, Track},
+    Trace, TrackInfo,
+};
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, collections::HashMap, fmt};
+use yew::worker::{Agent, AgentLink, HandlerId, Public};
+
+/// Identifies a single load, so that responses can be matched up with their requests
+pub type LoadId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LoadStage {
+    Reading,
+    Parsing,
+    Validating,
+    Sorting,
+    Indexing,
+}
+
+impl fmt::Display for LoadStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Reading => "Reading",
+            Self::Parsing => "Parsing",
+            Self::Validating => "Validating",
+            Self::Sorting => "Sorting",
+            Self::Indexing => "Indexing",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LoadProgress {
+    pub stage: LoadStage,
+    /// How far along the current stage is, from 0 to 1
+    pub fraction: f64,
+}
+
+impl LoadProgress {
+    pub const fn new(stage: LoadStage, fraction: f64) -> Self {
+        Self { stage, fraction }
+    }
+}
+
+/// How the events of a profile's tracks are sorted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ingest {
+    /// Every event is at hand, so each track is sorted all at once
+    Batch,
+    /// Events are sorted as they arrive, they can be logged up to `max_delay`
+    /// nanoseconds out of order and later ones are left out of the timeline
+    Streamed { max_delay: u64 },
+}
+
+/// A profile's trace, its blocks are compressed once it's built
+pub type ProfileTrace = Trace<PayloadId, CompressedBlockPool<PayloadId>>;
+
+/// A parsed profile along with everything the timeline needs to display it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoadedProfile {
+    pub name: String,
+    /// Sorted by their lane
+    pub events: Vec<WorkerTimelineEvent>,
+    pub nodes: Vec<Node>,
+    pub subgraphs: Vec<Subgraph>,
+    /// Every distinct lane, ordered from the top of the timeline down
+    pub lanes: Vec<TimelineEvent>,
+    /// Has one track per lane
+    pub trace: ProfileTrace,
+    /// Every worker's parks, their blocks are stored in `trace`'s pool
+    pub parks: Vec<TrackInfo<PayloadId>>,
+    /// What each worker spent its time on over the course of the profile
+    pub heatmap: Heatmap,
+    /// What workers sent each other
+    pub traffic: TrafficSummary,
+    /// How each arrangement's batches were merged
+    pub spines: SpineSummary,
+    /// The end of the last event in milliseconds
+    pub duration: f64,
+    /// Everything wrong with the profile, it's displayed as well as it can be
+    pub problems: Vec<Problem>,
+    /// The profile's json with its problems repaired, if any of them needed it
+    pub repaired: Option<String>,
+}
+
+/// Parses a profile and builds its trace, `progress` is called as each stage starts
+/// and periodically while indexing
+pub fn load_profile<F>(name: String, content: &[u8], mut progress: F) -> Result<LoadedProfile>
+where
+    F: FnMut(LoadProgress),
+{
+    progress(LoadProgress::new(LoadStage::Parsing, 0.0));
+    let mut parser = ProfileParser::new();
+    let (data, problems) = parser
+        .push(content)
+        .and_then(|()| parser.finish())
+        .with_context(|| format!("failed to parse profile {}", name))?;
+
+    Ok(index_profile(name, data, problems, Ingest::Batch, progress))
+}
+
+/// Validates a parsed profile, sorts its events and builds its trace. `problems`
+/// are any found while parsing it
+pub fn index_profile<F>(
+    name: String,
+    mut data: ProfilingData,
+    mut problems: Vec<Problem>,
+    ingest: Ingest,
+    mut progress: F,
+) -> LoadedProfile
+where
+    F: FnMut(LoadProgress),
+{
+    progress(LoadProgress::new(LoadStage::Validating, 0.0));
+    problems.extend(validate(&mut data));
+    for problem in &problems {
+        tracing::warn!("{}: {}", name, problem);
+    }
+
+    // Serializing the profile can't fail since all of its keys are strings
+    let repaired = if problems.iter().any(Problem::is_repaired) {
+        serde_json::to_string(&data).ok()
+    } else {
+        None
+    };
+
+    progress(LoadProgress::new(LoadStage::Sorting, 0.0));
+    // A stable sort keeps each lane's events in the order they were logged in
+    data.timeline_events
+        .sort_by_key(|event| event.event.clone());
+    let lanes = sort_events(&data.timeline_events);
+
+    let duration = data
+        .timeline_events
+        .iter()
+        .map(|event| event.end_time() as f64 / NS_TO_MS)
+        .max_by(|x, y| x.partial_cmp(y).unwrap_or(Ordering::Less))
+        .unwrap_or(0.0);
+
+    progress(LoadProgress::new(LoadStage::Indexing, 0.0));
+    let (trace, parks, late) = build_trace(&data.timeline_events, &lanes, ingest, |fraction| {
+        progress(LoadProgress::new(LoadStage::Indexing, fraction))
+    });
+    for problem in &late {
+        tracing::warn!("{}: {}", name, problem);
+    }
+    problems.extend(late);
+    let heatmap = Heatmap::new(&trace, &lanes, HEATMAP_BUCKETS);
+    let traffic = TrafficSummary::new(
+        &data.timeline_events,
+        &data.nodes,
+        &data.subgraphs,
+        &data.edges,
+        TRAFFIC_BUCKETS,
+    );
+    let spines = SpineSummary::new(&data.timeline_events);
+
+    LoadedProfile {
+        name,
+        events: data.timeline_events,
+        nodes: data.nodes,
+        subgraphs: data.subgraphs,
+        lanes,
+        trace,
+        parks,
+        heatmap,
+        traffic,
+        spines,
+        duration,
+        problems,
+        repaired,
+    }
+}
+
+/// Collects every distinct lane, ordered from the top of the timeline down
+fn sort_events(events: &[WorkerTimelineEvent]) -> Vec<TimelineEvent> {
+    let mut sorted_events: Vec<_> = events.iter().map(|event| event.event.clone()).collect();
+    sorted_events.sort_unstable();
+    sorted_events.dedup();
+    sorted_events.reverse();
+
+    sorted_events
+}
+
+/// Builds a trace with one track per lane of `sorted_events`, each event's
+/// name, worker and operator are stored as its payload. Also returns a track
+/// of each worker's parks, its blocks are stored in the trace's pool, and the
+/// problems of any events that were streamed in too late
+fn build_trace<F>(
+    events: &[WorkerTimelineEvent],
+    sorted_events: &[TimelineEvent],
+    ingest: Ingest,
+    mut progress: F,
+) -> (ProfileTrace, Vec<TrackInfo<PayloadId>>, Vec<Problem>)
+where
+    F: FnMut(f64),
+{
+    let start = events.iter().map(|event| event.start_time).min();
+    let end = events.iter().map(WorkerTimelineEvent::end_time).max();
+    let clock = TraceClock::for_span(start.unwrap_or(0)..end.unwrap_or(0));
+
+    let mut trace = Trace::with_clock(clock);
+    let lanes: HashMap<_, _> = sorted_events
+        .iter()
+        .enumerate()
+        .map(|(idx, event)| (event, idx))
+        .collect();
+    let mut lane_events: Vec<_> = sorted_events.iter().map(|_| Vec::new()).collect();
+    let mut worker_parks: Vec<Vec<_>> = Vec::new();
+
+    let operator_id = trace.strings.intern("operator_id");
+    let mut payloads = HashMap::new();
+
+    for event in events {
+        let lane = match lanes.get(&event.event) {
+            Some(&lane) => lane,
+            None => {
+                tracing::warn!(event = ?event, "skipping event without a lane");
+                continue;
+            }
+        };
+
+        let (timestamp, duration) = match (
+            clock.pack(event.start_time),
+            clock.pack_duration(event.duration),
+        ) {
+            (Ok(timestamp), Ok(duration)) => (timestamp, duration),
+            (Err(error), _) | (_, Err(error)) => {
+                tracing::warn!(event = ?event, "skipping event: {}", error);
+                continue;
+            }
+        };
+
+        let name = trace.strings.intern(&event.event.to_string());
+        // Events of the same lane and worker share a payload, so the kinds of the
+        // compressed trace repeat. What differs between them (like their id) is
+        // only kept in the profile's events
+        let payload = *payloads.entry((name, event.worker)).or_insert_with(|| {
+            let args = event
+                .event
+                .operator_id()
+                .map(|id| Arg::new(operator_id, ArgValue::U64(id as u64)));
+
+            trace.payloads.push(name, event.worker as u32, args)
+        });
+
+        let trace_event = TraceEvent {
+            kind: payload,
+            timestamp,
+            duration,
+        };
+        // Workers that never park still get an empty track
+        if worker_parks.len() <= event.worker {
+            worker_parks.resize_with(event.worker + 1, Vec::new);
+        }
+        if event.event == TimelineEvent::Parked {
+            worker_parks[event.worker].push((trace_event, event));
+        }
+
+        lane_events[lane].push((trace_event, event));
+    }
+
+    let mut late = Vec::new();
+    let num_lanes = lane_events.len();
+    for (idx, events) in lane_events.iter().enumerate() {
+        let info = ingest_track(&mut trace.pool, clock, ingest, events, &mut late);
+        trace.tracks.push(info);
+
+        progress((idx + 1) as f64 / num_lanes as f64);
+    }
+
+    let parks = worker_parks
+        .iter()
+        .map(|events| ingest_track(&mut trace.pool, clock, ingest, events, &mut late))
+        .collect();
+
+    let usage = trace.pool.memory_usage();
+    let trace = trace.compress();
+    let compressed = trace.pool.memory_usage();
+    tracing::info!(
+        blocks = usage.blocks,
+        events = usage.events,
+        payloads = trace.payloads.len(),
+        bytes = usage.bytes,
+        compressed_bytes = compressed.bytes,
+        bytes_per_event = %compressed.bytes_per_event(),
+        "built trace",
+    );
+
+    tracing::debug!(trace = ?trace);
+    (trace, parks, late)
+}
+
+/// Sorts and indexes the events of a track, each along with the event it was
+/// built from. Streamed events are ingested in the order they were logged in
+/// and the ones logged too late are added to `late`
+fn ingest_track(
+    pool: &mut BlockPool<PayloadId>,
+    clock: TraceClock,
+    ingest: Ingest,
+    events: &[(TraceEvent<PayloadId>, &WorkerTimelineEvent)],
+    late: &mut Vec<Problem>,
+) -> TrackInfo<PayloadId> {
+    let trace_events = events.iter().map(|&(event, _)| event);
+
+    match ingest {
+        Ingest::Batch => {
+            let track = Track::from_unsorted(pool, trace_events);
+            TrackInfo::build(track, pool)
+        }
+
+        Ingest::Streamed { max_delay } => {
+            let (info, late_events) = TrackInfo::ingest(pool, clock.ticks(max_delay), trace_events);
+
+            for (idx, _) in late_events {
+                let event = events[idx].1;
+                let problem = Problem::LateEvent {
+                    event_id: event.event_id,
+                    worker: event.worker,
+                    max_delay,
+                };
+
+                // Parks are in both their lane's track and their worker's
+                if !late.contains(&problem) {
+                    late.push(problem);
+                }
+            }
+
+            info
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum LoaderRequest {
+    Load {
+        id: LoadId,
+        name: String,
+        content: Vec<u8>,
+    },
+    /// Starts a load whose contents arrive in `Chunk`s, followed by a `Finish`
+    Begin {
+        id: LoadId,
+        name: String,
+        /// How many nanoseconds out of order the profile's events can be logged in
+        max_delay: u64,
+    },
+    Chunk {
+        id: LoadId,
+        data: Vec<u8>,
+    },
+    Finish {
+        id: LoadId,
+    },
+    /// Discards a streamed load, loads can't be canceled once they've finished
+    /// streaming so their responses have to be ignored instead
+    Cancel {
+        id: LoadId,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum LoaderResponse {
+    Progress {
+        id: LoadId,
+        progress: LoadProgress,
+    },
+    Loaded {
+        id: LoadId,
+        profile: Box<LoadedProfile>,
+    },
+    /// Errors are sent as their formatted causes since `anyhow::Error` can't cross threads
+    Failed {
+        id: LoadId,
+        error: Vec<String>,
+    },
+}
+
+/// Loads profiles within a web worker so that large profiles don't freeze the page
+#[derive(Debug)]
+pub struct ProfileLoader {
+    link: AgentLink<Self>,
+    /// Loads that are still being streamed in, along with their names and how
+    /// out of order their events can be
+    streams: HashMap<LoadId, (String, u64, ProfileParser)>,
+}
+
+impl ProfileLoader {
+    /// Progress is only reported in steps of this size so the page isn't
+    /// flooded with messages for profiles with many lanes
+    const PROGRESS_STEP: f64 = 0.05;
+
+    /// Runs `load`, relaying its progress and result to `who`
+    fn respond<F>(&self, who: HandlerId, id: LoadId, load: F)
+    where
+        F: FnOnce(&mut dyn FnMut(LoadProgress)) -> Result<LoadedProfile>,
+    {
+        let mut last_reported: Option<LoadProgress> = None;
+        let loaded = load(&mut |progress| {
+            let report = last_reported.is_none_or(|last| {
+                last.stage != progress.stage
+                    || progress.fraction - last.fraction >= Self::PROGRESS_STEP
+            });
+
+            if report {
+                last_reported = Some(progress);
+                self.link
+                    .respond(who, LoaderResponse::Progress { id, progress });
+            }
+        });
+
+        let response = match loaded {
+            Ok(profile) => LoaderResponse::Loaded {
+                id,
+                profile: Box::new(profile),
+            },
+            Err(error) => LoaderResponse::Failed {
+                id,
+                error: error_chain(&error),
+            },
+        };
+        self.link.respond(who, response);
+    }
+}
+
+impl Agent for ProfileLoader {
+    type Reach = Public<Self>;
+    type Message = ();
+    type Input = LoaderRequest;
+    type Output = LoaderResponse;
+
+    fn create(link: AgentLink<Self>) -> Self {
+        Self {
+            link,
+            streams: HashMap::new(),
+        }
+    }
+
+    fn update(&mut self, _message: Self::Message) {}
+
+    fn handle_input(&mut self, request: Self::Input, who: HandlerId) {
+        match request {
+            LoaderRequest::Load { id, name, content } => {
+                self.respond(who, id, |progress| load_profile(name, &content, progress));
+            }
+
+            LoaderRequest::Begin {
+                id,
+                name,
+                max_delay,
+            } => {
+                self.streams
+                    .insert(id, (name, max_delay, ProfileParser::new()));
+            }
+
+            LoaderRequest::Chunk { id, data } => {
+                if let Some((name, _, parser)) = self.streams.get_mut(&id) {
+                    if let Err(error) = parser.push(&data) {
+                        let error = error_chain(
+                            &error.context(format!("failed to parse profile {}", name)),
+                        );
+
+                        self.streams.remove(&id);
+                        self.link.respond(who, LoaderResponse::Failed { id, error });
+                    }
+                }
+            }
+
+            LoaderRequest::Finish { id } => {
+                if let Some((name, max_delay, parser)) = self.streams.remove(&id) {
+                    self.respond(who, id, move |progress| {
+                        let (data, problems) = parser
+                            .finish()
+                            .with_context(|| format!("failed to parse profile {}", name))?;
+
+                        let ingest = Ingest::Streamed { max_delay };
+                        Ok(index_profile(name, data, problems, ingest, progress))
+                    });
+                }
+            }
+
+            LoaderRequest::Cancel { id } => {
+                self.streams.remove(&id);
+            }
+        }
+    }
+
+    fn name_of_resource() -> &'static str {
+        "worker.js"
+    }
+}
+
+/// Formats an error and each of its causes, outermost first
+fn error_chain(error: &Error) -> Vec<String> {
+    error.chain().map(ToString::to_string).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{index_profile, load_profile, Ingest, LoadStage, LoadedProfile, Problem};
+    use crate::{
+        data::ProfilingData,
+        demo::{demo_profile, DemoConfig},
+    };
+    use gigatrace::{
+        payload::PayloadId,
+        trace::{BlockStore, TraceEvent},
+        TrackInfo,
+    };
+    use std::mem;
+
+    const STREAM_DELAY: u64 = 1_000_000_000;
+
+    #[test]
+    fn loads_example_profile() {
+        let content = include_bytes!("../../test/data/example.json");
+
+        let mut stages = Vec::new();
+        let profile = load_profile("example.json".to_owned(), content, |progress| {
+            if stages.last() != Some(&progress.stage) {
+                stages.push(progress.stage);
+            }
+        })
+        .unwrap();
+
+        assert_eq!(
+            stages,
+            [
+                LoadStage::Parsing,
+                LoadStage::Validating,
+                LoadStage::Sorting,
+                LoadStage::Indexing,
+            ],
+        );
+        assert_eq!(profile.problems, []);
+        assert_eq!(profile.repaired, None);
+        assert_eq!(profile.trace.tracks.len(), profile.lanes.len());
+        assert!(profile.duration > 0.0);
+        assert!(profile
+            .events
+            .windows(2)
+            .all(|events| events[0].event <= events[1].event));
+
+        // Profiles are serialized to get them out of the worker
+        let json = serde_json::to_string(&profile).unwrap();
+        let sent: LoadedProfile = serde_json::from_str(&json).unwrap();
+        assert_eq!(sent.trace.time_bounds(), profile.trace.time_bounds());
+        assert_eq!(sent.trace.tracks.len(), profile.trace.tracks.len());
+    }
+
+    #[test]
+    fn compresses_the_trace() {
+        let content = include_bytes!("../../test/data/example.json");
+        let profile = load_profile("example.json".to_owned(), content, |_| {}).unwrap();
+
+        // Events of the same lane and worker share their payload
+        assert!(profile.trace.payloads.len() * 10 < profile.events.len());
+        assert!(profile.trace.pool.uses_dictionary());
+
+        let usage = profile.trace.pool.memory_usage();
+        let parks: usize = profile
+            .parks
+            .iter()
+            .map(|parks| parks.track.events_in(&profile.trace.pool).count())
+            .sum();
+        assert_eq!(usage.events, profile.events.len() + parks);
+        // Smaller than the events alone, let alone their blocks
+        assert!(usage.bytes_per_event() < mem::size_of::<TraceEvent<PayloadId>>() as f64);
+    }
+
+    #[test]
+    fn sorts_batches_logged_out_of_order() {
+        let mut data = demo_profile(&DemoConfig::default());
+        data.timeline_events.sort_by_key(|event| event.start_time);
+        let in_order = index_profile(
+            "demo.json".to_owned(),
+            data.clone(),
+            Vec::new(),
+            Ingest::Batch,
+            |_| {},
+        );
+
+        // Swap every pair of neighbouring batches, like workers flushing their logs
+        let mut batches: Vec<_> = data.timeline_events.chunks(50).map(<[_]>::to_vec).collect();
+        batches.chunks_mut(2).for_each(<[_]>::reverse);
+        data.timeline_events = batches.into_iter().flatten().collect();
+        let streamed = |max_delay| {
+            let ingest = Ingest::Streamed { max_delay };
+            index_profile(
+                "demo.json".to_owned(),
+                data.clone(),
+                Vec::new(),
+                ingest,
+                |_| {},
+            )
+        };
+        let reordered = streamed(STREAM_DELAY);
+
+        let events = |profile: &LoadedProfile, info: &TrackInfo<PayloadId>| {
+            let trace = &profile.trace;
+            let events: Vec<_> = info
+                .track
+                .events_in(&trace.pool)
+                .map(|event| {
+                    let payload = trace.payload(&event).unwrap();
+                    let name = trace.strings.get(payload.name).unwrap().to_owned();
+                    (event.timestamp, event.duration, name, payload.worker)
+                })
+                .collect();
+
+            let stats = info
+                .stats_index
+                .range_query(0..info.track.block_locations().len());
+            assert_eq!(stats.count, events.len() as u64);
+            assert!(events.windows(2).all(|events| events[0].0 <= events[1].0));
+            events
+        };
+
+        let tracks = |profile: &LoadedProfile| {
+            let (tracks, parks) = (&profile.trace.tracks, &profile.parks);
+            tracks
+                .iter()
+                .chain(parks)
+                .map(|info| {
+                    // Events logged at the same time can be in either order
+                    let mut events = events(profile, info);
+                    events.sort();
+                    events
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(reordered.lanes, in_order.lanes);
+        assert_eq!(reordered.problems, []);
+        assert_eq!(tracks(&reordered), tracks(&in_order));
+
+        // Events logged further out of order than that are left out of the timeline
+        let late = streamed(0);
+        assert!(!late.problems.is_empty());
+        assert!(late
+            .problems
+            .iter()
+            .all(|problem| matches!(problem, Problem::LateEvent { max_delay: 0, .. })));
+        let timeline_events: usize = late
+            .trace
+            .tracks
+            .iter()
+            .map(|info| info.track.events_in(&late.trace.pool).count())
+            .sum();
+        assert!(timeline_events < late.events.len());
+    }
+
+    #[test]
+    fn loads_what_it_can() {
+        let content = br#"{"nodes": [], "edges": [{"src": [0], "dest": [1], "channel_id": 0, "edge_kind": "Normal"}],
+            "timeline_events": [
+                {"event_id": 0, "worker": 0, "event": "Input", "start_time": 10, "duration": 5, "collapsed_events": 1},
+                {"event_id": 1, "worker": 0, "event": "Input", "start_time": -10, "duration": 5, "collapsed_events": 1}
+            ]}"#;
+        let profile = load_profile("broken.json".to_owned(), content, |_| {}).unwrap();
+
+        assert_eq!(profile.events.len(), 1);
+        assert_eq!(profile.trace.tracks.len(), 1);
+        assert_eq!(profile.problems.len(), 4);
+
+        // The repaired profile has every field and nothing that needed removing
+        let repaired: ProfilingData = serde_json::from_str(&profile.repaired.unwrap()).unwrap();
+        assert_eq!(repaired.timeline_events, profile.events);
+        assert_eq!(repaired.edges, []);
+        assert_eq!(repaired.subgraphs, []);
+    }
+
+    #[test]
+    fn rejects_invalid_profiles() {
+        let error = load_profile("broken.json".to_owned(), b"{\"nodes\": [", |_| {}).unwrap_err();
+        assert!(format!("{:#}", error).contains("broken.json"));
+    }
+}
@@ -0,0 +1,430 @@
+use crate::{data::ProfilingData, loader::Problem};
+use anyhow::{bail, Context, Result};
+use serde::de::DeserializeOwned;
+
+/// The fields of a `ProfilingData`, all of them are arrays
+const FIELDS: [&str; 5] = [
+    "nodes",
+    "subgraphs",
+    "edges",
+    "palette_colors",
+    "timeline_events",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Before the root object's `{`
+    Start,
+    /// Within the root object, expecting a key or its closing `}`
+    Key,
+    /// After a `,` in the root object, expecting another key
+    NextKey,
+    /// Reading a key's string
+    InKey,
+    /// Expecting the `:` after a key
+    Colon,
+    /// Expecting a field's value
+    Value,
+    /// Within one of the profile's arrays, expecting an element or `]`
+    Element,
+    /// After a `,` in one of the profile's arrays, expecting another element
+    NextElement,
+    /// Buffering an array element
+    InElement,
+    /// Skipping the value of a field that isn't part of a profile
+    InValue,
+    /// Expecting a `,` or `}` after a field
+    AfterValue,
+    /// After the root object's closing `}`
+    End,
+}
+
+/// Parses a `ProfilingData` as its json arrives in chunks. Elements of the
+/// profile's arrays are parsed as soon as they're complete, so only a
+/// single element is ever buffered. Elements that can't be parsed and
+/// missing fields are reported as problems rather than failing the whole profile
+#[derive(Debug)]
+pub struct ProfileParser {
+    state: State,
+    /// The element currently being read
+    buffer: Vec<u8>,
+    key: Vec<u8>,
+    /// Nesting depth within the current element or value
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    /// Bytes consumed so far, used to point errors at their location
+    offset: usize,
+    /// The line and column of the current byte, both starting at 1
+    position: (usize, usize),
+    /// Where the current element started, serde_json's errors are relative to it
+    element_start: (usize, usize),
+    /// The number of elements parsed from the current array
+    elements: usize,
+    seen: [bool; FIELDS.len()],
+    data: ProfilingData,
+    problems: Vec<Problem>,
+}
+
+impl ProfileParser {
+    pub fn new() -> Self {
+        Self {
+            state: State::Start,
+            buffer: Vec::new(),
+            key: Vec::new(),
+            depth: 0,
+            in_string: false,
+            escaped: false,
+            offset: 0,
+            position: (1, 1),
+            element_start: (1, 1),
+            elements: 0,
+            seen: [false; FIELDS.len()],
+            data: ProfilingData {
+                nodes: Vec::new(),
+                subgraphs: Vec::new(),
+                edges: Vec::new(),
+                palette_colors: Vec::new(),
+                timeline_events: Vec::new(),
+            },
+            problems: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, chunk: &[u8]) -> Result<()> {
+        for &byte in chunk {
+            self.push_byte(byte).with_context(|| {
+                let (line, column) = self.position;
+                format!(
+                    "invalid profile at line {}, column {} (byte {})",
+                    line, column, self.offset,
+                )
+            })?;
+            self.offset += 1;
+
+            if byte == b'\n' {
+                self.position = (self.position.0 + 1, 1);
+            } else {
+                self.position.1 += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the whole profile has been read and returns it along
+    /// with any problems found while parsing it
+    pub fn finish(mut self) -> Result<(ProfilingData, Vec<Problem>)> {
+        if self.state != State::End {
+            bail!("the profile ended unexpectedly after {} bytes", self.offset);
+        }
+
+        if self.seen.iter().all(|&seen| !seen) {
+            bail!("the file doesn't contain any profile fields");
+        }
+
+        let missing = FIELDS
+            .iter()
+            .zip(&self.seen)
+            .filter(|&(_, &seen)| !seen)
+            .map(|(field, _)| Problem::MissingField {
+                field: (*field).to_owned(),
+            });
+        self.problems.extend(missing);
+
+        Ok((self.data, self.problems))
+    }
+
+    fn push_byte(&mut self, byte: u8) -> Result<()> {
+        let is_space = byte.is_ascii_whitespace();
+
+        match self.state {
+            State::Start if is_space => {}
+            State::Start if byte == b'{' => self.state = State::Key,
+            State::Start => bail!("expected a json object"),
+
+            State::Key | State::NextKey if is_space => {}
+            State::Key | State::NextKey if byte == b'"' => {
+                self.key.clear();
+                self.state = State::InKey;
+            }
+            State::Key if byte == b'}' => self.state = State::End,
+            State::NextKey if byte == b'}' => bail!("trailing `,` before a `}}`"),
+            State::Key | State::NextKey => bail!("expected a field name"),
+
+            State::InKey if byte == b'"' && !self.escaped => self.state = State::Colon,
+            State::InKey => {
+                self.escaped = byte == b'\\' && !self.escaped;
+                self.key.push(byte);
+            }
+
+            State::Colon if is_space => {}
+            State::Colon if byte == b':' => self.state = State::Value,
+            State::Colon => bail!("expected a `:` after a field name"),
+
+            State::Value if is_space => {}
+            State::Value => match self.field() {
+                Some(field) if byte == b'[' => {
+                    if self.seen[field] {
+                        bail!("duplicate field `{}`", FIELDS[field]);
+                    }
+
+                    self.seen[field] = true;
+                    self.elements = 0;
+                    self.state = State::Element;
+                }
+                Some(field) => bail!("expected `{}` to be an array", FIELDS[field]),
+
+                // Unknown fields are skipped, the same as when deserializing
+                None => {
+                    self.state = State::InValue;
+                    self.push_byte(byte)?;
+                }
+            },
+
+            State::Element | State::NextElement if is_space => {}
+            State::Element if byte == b']' => self.state = State::AfterValue,
+            State::NextElement if byte == b']' => bail!("trailing `,` before a `]`"),
+            State::Element | State::NextElement => {
+                self.element_start = self.position;
+                self.state = State::InElement;
+                self.push_byte(byte)?;
+            }
+
+            State::InElement => {
+                if self.scan(byte) {
+                    self.parse_element();
+                    self.buffer.clear();
+
+                    self.state = if byte == b',' {
+                        State::NextElement
+                    } else {
+                        State::AfterValue
+                    };
+                } else {
+                    self.buffer.push(byte);
+                }
+            }
+
+            State::InValue => {
+                if self.scan(byte) {
+                    self.state = State::AfterValue;
+                    self.push_byte(byte)?;
+                }
+            }
+
+            State::AfterValue if is_space => {}
+            State::AfterValue if byte == b',' => self.state = State::NextKey,
+            State::AfterValue if byte == b'}' => self.state = State::End,
+            State::AfterValue => bail!("expected a `,` or `}}` after a field"),
+
+            State::End if is_space => {}
+            State::End => bail!("unexpected data after the profile"),
+        }
+
+        Ok(())
+    }
+
+    /// Tracks strings and nesting, returning true if `byte` ends the current
+    /// element or value (the `,` or closing bracket after it)
+    fn scan(&mut self, byte: u8) -> bool {
+        if self.in_string {
+            if self.escaped {
+                self.escaped = false;
+            } else if byte == b'\\' {
+                self.escaped = true;
+            } else if byte == b'"' {
+                self.in_string = false;
+            }
+
+            return false;
+        }
+
+        match byte {
+            b'"' => self.in_string = true,
+            b'{' | b'[' => self.depth += 1,
+            b'}' | b']' if self.depth == 0 => return true,
+            b'}' | b']' => self.depth -= 1,
+            b',' if self.depth == 0 => return true,
+            _ => {}
+        }
+
+        false
+    }
+
+    fn field(&self) -> Option<usize> {
+        FIELDS
+            .iter()
+            .position(|field| field.as_bytes() == &*self.key)
+    }
+
+    fn parse_element(&mut self) {
+        let field = match self.field() {
+            Some(field) => field,
+            None => return,
+        };
+
+        let buffer = &self.buffer;
+        let parsed = match FIELDS[field] {
+            "nodes" => parse_into(buffer, &mut self.data.nodes),
+            "subgraphs" => parse_into(buffer, &mut self.data.subgraphs),
+            "edges" => parse_into(buffer, &mut self.data.edges),
+            "palette_colors" => parse_into(buffer, &mut self.data.palette_colors),
+            _ => parse_into(buffer, &mut self.data.timeline_events),
+        };
+
+        let element = self.elements;
+        self.elements += 1;
+
+        if let Err(error) = parsed {
+            let (line, column) = self.error_position(&error);
+
+            // serde_json's position is relative to the element, so it's replaced
+            let message = error.to_string();
+            let message = message
+                .rsplit_once(" at line ")
+                .map_or(&*message, |(message, _)| message);
+
+            self.problems.push(Problem::InvalidElement {
+                field: FIELDS[field].to_owned(),
+                index: element,
+                line,
+                column,
+                error: message.to_owned(),
+            });
+        }
+    }
+
+    /// Translates the position of an error within the current element into
+    /// its position within the whole profile
+    fn error_position(&self, error: &serde_json::Error) -> (usize, usize) {
+        let (start_line, start_column) = self.element_start;
+
+        if error.line() <= 1 {
+            (start_line, start_column + error.column().saturating_sub(1))
+        } else {
+            (start_line + error.line() - 1, error.column())
+        }
+    }
+}
+
+impl Default for ProfileParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn parse_into<T: DeserializeOwned>(
+    element: &[u8],
+    elements: &mut Vec<T>,
+) -> serde_json::Result<()> {
+    elements.push(serde_json::from_slice(element)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProfileParser;
+    use crate::{data::ProfilingData, loader::Problem};
+
+    const EXAMPLE: &[u8] = include_bytes!("../../test/data/example.json");
+
+    #[test]
+    fn matches_serde_json() {
+        let expected: ProfilingData = serde_json::from_slice(EXAMPLE).unwrap();
+
+        // Chunk boundaries can land anywhere, including within strings and escapes
+        for &chunk_size in &[1, 7, 4096, EXAMPLE.len()] {
+            let mut parser = ProfileParser::new();
+            for chunk in EXAMPLE.chunks(chunk_size) {
+                parser.push(chunk).unwrap();
+            }
+
+            assert_eq!(parser.finish().unwrap(), (expected.clone(), Vec::new()));
+        }
+    }
+
+    #[test]
+    fn skips_unknown_fields() {
+        let mut parser = ProfileParser::new();
+        parser
+            .push(
+                br##"{"version": {"major": [1, "}"]}, "nodes": [], "subgraphs": [], "edges": [],
+                    "palette_colors": ["#fff", "\"]"], "timeline_events": [], "extra": 5}"##,
+            )
+            .unwrap();
+
+        let (data, problems) = parser.finish().unwrap();
+        assert_eq!(data.palette_colors, ["#fff", "\"]"]);
+        assert_eq!(problems, []);
+    }
+
+    #[test]
+    fn skips_invalid_elements() {
+        // Positions are within the whole profile rather than the element
+        let mut parser = ProfileParser::new();
+        parser
+            .push(b"{\"nodes\": [], \"subgraphs\": [], \"edges\": [],\n  \"palette_colors\": [\"#fff\",\n    5, \"#000\"]}")
+            .unwrap();
+
+        let (data, problems) = parser.finish().unwrap();
+        assert_eq!(data.palette_colors, ["#fff", "#000"]);
+        assert_eq!(
+            problems,
+            [
+                Problem::InvalidElement {
+                    field: "palette_colors".to_owned(),
+                    index: 1,
+                    line: 3,
+                    column: 5,
+                    error: "invalid type: integer `5`, expected a string".to_owned(),
+                },
+                Problem::MissingField {
+                    field: "timeline_events".to_owned(),
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn reports_errors() {
+        let mut parser = ProfileParser::new();
+        let error = parser.push(br#"{"nodes": [] "edges": []"#).unwrap_err();
+        assert!(format!("{:#}", error).contains("invalid profile at line 1, column 14 (byte 13)"));
+
+        let mut parser = ProfileParser::new();
+        let error = parser
+            .push(b"{\n  \"nodes\": [],\n  \"edges\" []")
+            .unwrap_err();
+        assert!(format!("{:#}", error).contains("invalid profile at line 3, column 11 (byte 27)"));
+
+        let mut parser = ProfileParser::new();
+        parser.push(br#"{"nodes": [], "edges": []"#).unwrap();
+        assert!(parser.finish().is_err());
+
+        let mut parser = ProfileParser::new();
+        parser.push(br#"{"version": 2}"#).unwrap();
+        assert!(parser.finish().is_err());
+    }
+
+    #[test]
+    fn rejects_trailing_commas() {
+        for &(profile, error) in &[
+            (
+                &br##"{"palette_colors": ["#fff",]}"##[..],
+                "trailing `,` before a `]`",
+            ),
+            (br#"{"nodes": [], }"#, "trailing `,` before a `}`"),
+            (br#"{"nodes": [],}"#, "trailing `,` before a `}`"),
+        ] {
+            let mut parser = ProfileParser::new();
+            let message = format!("{:#}", parser.push(profile).unwrap_err());
+            assert!(message.contains(error), "{}", message);
+        }
+
+        // Empty objects and arrays are still fine
+        let mut parser = ProfileParser::new();
+        parser.push(br#"{"nodes": [], "edges": [ ]}"#).unwrap();
+        assert!(parser.finish().is_ok());
+    }
+}
@@ -0,0 +1,441 @@
+use crate::data::{EventId, EventKind, ProfilingData};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+/// Something wrong with a profile, along with how it was repaired
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Problem {
+    /// A field of the profile was missing, it's treated as empty
+    MissingField { field: String },
+    /// An element of one of the profile's arrays couldn't be parsed and was skipped
+    InvalidElement {
+        field: String,
+        index: usize,
+        line: usize,
+        column: usize,
+        error: String,
+    },
+    /// An event's end time doesn't fit in a `u64`, the event was removed
+    TimeOverflow {
+        event_id: EventId,
+        start_time: u64,
+        duration: u64,
+    },
+    /// An event refers to an operator that isn't in the profile's nodes or
+    /// subgraphs, the event is kept
+    UnknownOperator {
+        event_id: EventId,
+        operator_id: usize,
+    },
+    /// An edge connects an address that isn't in the profile, the edge was removed
+    MissingAddr { channel_id: usize, addr: Vec<usize> },
+    /// The same event was recorded more than once, only the first was kept
+    DuplicateEvent {
+        event_id: EventId,
+        worker: usize,
+        count: usize,
+    },
+    /// An event other than a message or progress update recorded traffic, it was removed
+    UnexpectedTraffic { event_id: EventId, worker: usize },
+    /// A message was sent over a channel that isn't one of the profile's edges,
+    /// the message is kept
+    UnknownChannel {
+        event_id: EventId,
+        channel_id: usize,
+    },
+    /// An event other than a merge recorded merged batches, they were removed
+    UnexpectedMerge { event_id: EventId, worker: usize },
+    /// A streamed event was logged more than `max_delay` nanoseconds out of
+    /// order, it's left out of the timeline but the profile keeps it
+    LateEvent {
+        event_id: EventId,
+        worker: usize,
+        max_delay: u64,
+    },
+}
+
+impl Problem {
+    /// Whether fixing the problem changed the profile's contents
+    pub const fn is_repaired(&self) -> bool {
+        !matches!(
+            self,
+            Self::UnknownOperator { .. } | Self::UnknownChannel { .. } | Self::LateEvent { .. }
+        )
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingField { field } => {
+                write!(f, "the `{}` field is missing, treating it as empty", field)
+            }
+            Self::InvalidElement {
+                field,
+                index,
+                line,
+                column,
+                error,
+            } => write!(
+                f,
+                "skipped element {} of `{}` at line {}, column {}: {}",
+                index, field, line, column, error,
+            ),
+            Self::TimeOverflow {
+                event_id,
+                start_time,
+                duration,
+            } => write!(
+                f,
+                "removed event {} since it ends after the largest time (starts at {}, lasts {})",
+                event_id, start_time, duration,
+            ),
+            Self::UnknownOperator {
+                event_id,
+                operator_id,
+            } => write!(
+                f,
+                "event {} refers to operator {} which doesn't exist",
+                event_id, operator_id,
+            ),
+            Self::MissingAddr { channel_id, addr } => write!(
+                f,
+                "removed channel {} since it connects {:?} which doesn't exist",
+                channel_id, addr,
+            ),
+            Self::DuplicateEvent {
+                event_id,
+                worker,
+                count,
+            } => write!(
+                f,
+                "event {} on worker {} was recorded {} times, removed the duplicates",
+                event_id, worker, count,
+            ),
+            Self::UnexpectedTraffic { event_id, worker } => write!(
+                f,
+                "removed the traffic of event {} on worker {} since it isn't a message or progress update",
+                event_id, worker,
+            ),
+            Self::UnknownChannel {
+                event_id,
+                channel_id,
+            } => write!(
+                f,
+                "event {} was sent over channel {} which doesn't exist",
+                event_id, channel_id,
+            ),
+            Self::UnexpectedMerge { event_id, worker } => write!(
+                f,
+                "removed the merged batches of event {} on worker {} since it isn't a merge",
+                event_id, worker,
+            ),
+            Self::LateEvent {
+                event_id,
+                worker,
+                max_delay,
+            } => write!(
+                f,
+                "left event {} on worker {} out of the timeline since it was logged more than {}ns out of order",
+                event_id, worker, max_delay,
+            ),
+        }
+    }
+}
+
+/// Checks a parsed profile for inconsistencies, removing anything that
+/// can't be displayed and returning every problem found
+pub fn validate(data: &mut ProfilingData) -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    data.timeline_events.retain(|event| {
+        let overflows = event.start_time.checked_add(event.duration).is_none();
+        if overflows {
+            problems.push(Problem::TimeOverflow {
+                event_id: event.event_id,
+                start_time: event.start_time,
+                duration: event.duration,
+            });
+        }
+
+        !overflows
+    });
+
+    // Events are identified by their id, worker and start since ids are
+    // shared by events of the same kind
+    let mut recorded: HashMap<_, usize> = HashMap::new();
+    data.timeline_events.retain(|event| {
+        let count = recorded
+            .entry((event.event_id, event.worker, event.start_time))
+            .or_default();
+        *count += 1;
+
+        *count == 1
+    });
+
+    let mut duplicates: Vec<_> = recorded
+        .into_iter()
+        .filter(|&(_, count)| count > 1)
+        .collect();
+    duplicates.sort_unstable();
+    problems.extend(
+        duplicates
+            .into_iter()
+            .map(|((event_id, worker, _), count)| Problem::DuplicateEvent {
+                event_id,
+                worker,
+                count,
+            }),
+    );
+
+    let operators: HashSet<_> = data
+        .nodes
+        .iter()
+        .map(|node| node.id)
+        .chain(data.subgraphs.iter().map(|subgraph| subgraph.id))
+        .collect();
+    let mut unknown = HashSet::new();
+    for event in &data.timeline_events {
+        if let Some(operator_id) = event.event.operator_id() {
+            // Only report each missing operator once
+            if !operators.contains(&operator_id) && unknown.insert(operator_id) {
+                problems.push(Problem::UnknownOperator {
+                    event_id: event.event_id,
+                    operator_id,
+                });
+            }
+        }
+    }
+
+    let addrs: HashSet<&[usize]> = data
+        .nodes
+        .iter()
+        .map(|node| &*node.addr)
+        .chain(data.subgraphs.iter().map(|subgraph| &*subgraph.addr))
+        .collect();
+    data.edges.retain(|edge| {
+        let missing = [&edge.src, &edge.dest]
+            .iter()
+            .copied()
+            .find(|addr| !addrs.contains(addr.as_slice()));
+
+        if let Some(addr) = missing {
+            problems.push(Problem::MissingAddr {
+                channel_id: edge.channel_id,
+                addr: addr.clone(),
+            });
+        }
+
+        missing.is_none()
+    });
+
+    let channels: HashSet<_> = data.edges.iter().map(|edge| edge.channel_id).collect();
+    let mut unknown = HashSet::new();
+    for event in &mut data.timeline_events {
+        let traffic = match event.traffic {
+            Some(traffic) => traffic,
+            None => continue,
+        };
+
+        match event.event.kind() {
+            EventKind::Message => {
+                // Only report each missing channel once
+                if !channels.contains(&traffic.channel_id) && unknown.insert(traffic.channel_id) {
+                    problems.push(Problem::UnknownChannel {
+                        event_id: event.event_id,
+                        channel_id: traffic.channel_id,
+                    });
+                }
+            }
+
+            // Progress is exchanged over channels that aren't edges of the dataflow
+            EventKind::Progress => {}
+
+            _ => {
+                event.traffic = None;
+                problems.push(Problem::UnexpectedTraffic {
+                    event_id: event.event_id,
+                    worker: event.worker,
+                });
+            }
+        }
+    }
+
+    for event in &mut data.timeline_events {
+        if event.merge.is_some() && event.event.kind() != EventKind::Merge {
+            event.merge = None;
+            problems.push(Problem::UnexpectedMerge {
+                event_id: event.event_id,
+                worker: event.worker,
+            });
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate, Problem};
+    use crate::data::{MergeInfo, ProfilingData, TimelineEvent, Traffic};
+
+    const EXAMPLE: &[u8] = include_bytes!("../../test/data/example.json");
+
+    #[test]
+    fn example_is_valid() {
+        let mut data: ProfilingData = serde_json::from_slice(EXAMPLE).unwrap();
+        let expected = data.clone();
+
+        assert_eq!(validate(&mut data), []);
+        assert_eq!(data, expected);
+    }
+
+    #[test]
+    fn repairs_problems() {
+        let mut data: ProfilingData = serde_json::from_slice(EXAMPLE).unwrap();
+        let (events, edges) = (data.timeline_events.len(), data.edges.len());
+
+        let mut overflowing = data.timeline_events[0].clone();
+        overflowing.start_time = u64::MAX - 10;
+        let duplicate = data.timeline_events[1].clone();
+        let mut unknown = data
+            .timeline_events
+            .iter()
+            .find(|event| matches!(event.event, TimelineEvent::OperatorActivation { .. }))
+            .unwrap()
+            .clone();
+        if let TimelineEvent::OperatorActivation { operator_id, .. } = &mut unknown.event {
+            *operator_id = 10_000;
+        }
+        unknown.start_time += 1;
+        data.timeline_events
+            .extend(vec![overflowing, duplicate.clone(), unknown.clone()]);
+
+        let mut dangling = data.edges[0].clone();
+        dangling.dest = vec![0, 99];
+        data.edges.push(dangling.clone());
+
+        let problems = validate(&mut data);
+        assert_eq!(
+            problems,
+            [
+                Problem::TimeOverflow {
+                    event_id: data.timeline_events[0].event_id,
+                    start_time: u64::MAX - 10,
+                    duration: data.timeline_events[0].duration,
+                },
+                Problem::DuplicateEvent {
+                    event_id: duplicate.event_id,
+                    worker: duplicate.worker,
+                    count: 2,
+                },
+                Problem::UnknownOperator {
+                    event_id: unknown.event_id,
+                    operator_id: 10_000,
+                },
+                Problem::MissingAddr {
+                    channel_id: dangling.channel_id,
+                    addr: vec![0, 99],
+                },
+            ],
+        );
+
+        // The unknown operator's event is the only addition that's kept
+        assert_eq!(data.timeline_events.len(), events + 1);
+        assert_eq!(data.edges.len(), edges);
+    }
+
+    #[test]
+    fn checks_traffic() {
+        let mut data: ProfilingData = serde_json::from_slice(EXAMPLE).unwrap();
+        let traffic = Traffic {
+            channel_id: data.edges[0].channel_id,
+            source_worker: 0,
+            target_worker: 1,
+            records: 10,
+            bytes: None,
+        };
+
+        let mut message = data.timeline_events[0].clone();
+        message.event = TimelineEvent::Message;
+        message.start_time += 10;
+        message.traffic = Some(traffic);
+        let mut lost = message.clone();
+        lost.start_time += 1;
+        lost.traffic = Some(Traffic {
+            channel_id: 10_000,
+            ..traffic
+        });
+        let mut progress = message.clone();
+        progress.event = TimelineEvent::Progress;
+        progress.start_time += 2;
+        progress.traffic = Some(Traffic {
+            channel_id: 20_000,
+            ..traffic
+        });
+        let mut parked = message.clone();
+        parked.event = TimelineEvent::Parked;
+        parked.start_time += 3;
+
+        data.timeline_events
+            .extend(vec![message, lost.clone(), progress, parked.clone()]);
+        let events = data.timeline_events.len();
+
+        assert_eq!(
+            validate(&mut data),
+            [
+                Problem::UnknownChannel {
+                    event_id: lost.event_id,
+                    channel_id: 10_000,
+                },
+                Problem::UnexpectedTraffic {
+                    event_id: parked.event_id,
+                    worker: parked.worker,
+                },
+            ],
+        );
+
+        assert_eq!(data.timeline_events.len(), events);
+        assert_eq!(data.timeline_events.last().unwrap().traffic, None);
+    }
+
+    #[test]
+    fn checks_merges() {
+        let mut data: ProfilingData = serde_json::from_slice(EXAMPLE).unwrap();
+        let info = MergeInfo {
+            scale: 4,
+            length1: 16,
+            length2: 12,
+            complete: Some(20),
+            effort: 28,
+        };
+
+        let mut merge = data.timeline_events[0].clone();
+        merge.event = TimelineEvent::Merge {
+            operator_id: data.nodes[0].id,
+            operator_name: data.nodes[0].name.clone(),
+        };
+        merge.start_time += 10;
+        merge.merge = Some(info);
+        let mut message = merge.clone();
+        message.event = TimelineEvent::Message;
+        message.start_time += 1;
+
+        data.timeline_events.extend(vec![merge, message.clone()]);
+
+        assert_eq!(
+            validate(&mut data),
+            [Problem::UnexpectedMerge {
+                event_id: message.event_id,
+                worker: message.worker,
+            }],
+        );
+
+        let events = &data.timeline_events;
+        assert_eq!(events[events.len() - 2].merge, Some(info));
+        assert_eq!(events[events.len() - 1].merge, None);
+    }
+}
@@ -0,0 +1,322 @@
+use anyhow::Error;
+use std::{
+    fmt,
+    rc::Rc,
+    time::{Duration, UNIX_EPOCH},
+};
+use web_sys::MouseEvent;
+use yew::{
+    html,
+    services::{timeout::TimeoutTask, TimeoutService},
+    Callback, Component, ComponentLink, Html, Properties, ShouldRender,
+};
+
+/// How long info and warning toasts stay up, errors stay until they're dismissed
+const TOAST_TIMEOUT: Duration = Duration::from_secs(6);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    fn class(self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Info => "Info",
+            Self::Warning => "Warning",
+            Self::Error => "Error",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    /// Assigned when the notification is posted
+    pub id: usize,
+    /// When the notification was posted, in milliseconds since the unix epoch
+    pub time: f64,
+    pub severity: Severity,
+    pub message: String,
+    /// The causes of an error, outermost first
+    pub details: Vec<String>,
+}
+
+impl Notification {
+    pub fn new<T: ToString>(severity: Severity, message: T) -> Self {
+        Self {
+            id: 0,
+            time: 0.0,
+            severity,
+            message: message.to_string(),
+            details: Vec::new(),
+        }
+    }
+
+    pub fn info<T: ToString>(message: T) -> Self {
+        Self::new(Severity::Info, message)
+    }
+
+    pub fn warning<T: ToString>(message: T) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    /// An error along with each of its causes
+    pub fn error<T: ToString>(message: T, error: &Error) -> Self {
+        Self::error_chain(message, error.chain().map(ToString::to_string))
+    }
+
+    /// An error whose causes have already been formatted, such as ones sent from a worker
+    pub fn error_chain<T, I>(message: T, chain: I) -> Self
+    where
+        T: ToString,
+        I: IntoIterator<Item = String>,
+    {
+        Self {
+            details: chain.into_iter().collect(),
+            ..Self::new(Severity::Error, message)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Properties)]
+pub struct NotificationCenterProps {
+    /// Every notification that's been posted, oldest first
+    pub notifications: Rc<Vec<Notification>>,
+    pub onclear: Callback<()>,
+}
+
+#[derive(Debug)]
+pub enum Message {
+    Dismiss(usize),
+    ShowDetails(usize),
+    CloseDetails,
+    ClearLog,
+}
+
+/// Shows new notifications as toasts and keeps a log of all of them,
+/// an error's causes can be viewed in a popup
+#[derive(Debug)]
+pub struct NotificationCenter {
+    link: ComponentLink<Self>,
+    properties: NotificationCenterProps,
+    /// The ids of notifications shown as toasts
+    toasts: Vec<usize>,
+    timeouts: Vec<(usize, TimeoutTask)>,
+    /// The newest notification that's been shown
+    last_seen: Option<usize>,
+    details: Option<usize>,
+}
+
+impl NotificationCenter {
+    fn show_new(&mut self) {
+        let last_seen = self.last_seen;
+        let new = self
+            .properties
+            .notifications
+            .iter()
+            .filter(|notification| last_seen.is_none_or(|seen| notification.id > seen));
+
+        for notification in new {
+            let id = notification.id;
+            self.toasts.push(id);
+            self.last_seen = Some(id);
+
+            if notification.severity != Severity::Error {
+                let timeout = TimeoutService::spawn(
+                    TOAST_TIMEOUT,
+                    self.link.callback(move |_| Message::Dismiss(id)),
+                );
+                self.timeouts.push((id, timeout));
+            }
+        }
+    }
+
+    fn notification(&self, id: usize) -> Option<&Notification> {
+        self.properties
+            .notifications
+            .iter()
+            .find(|notification| notification.id == id)
+    }
+
+    fn view_toast(&self, notification: &Notification) -> Html {
+        let id = notification.id;
+
+        let details = if notification.details.is_empty() {
+            html! {}
+        } else {
+            html! {
+                <button onclick=self.link.callback(move |_| Message::ShowDetails(id))>{ "Details" }</button>
+            }
+        };
+
+        html! {
+            <div class=format!("toast {}", notification.severity.class())>
+                <span>{ &notification.message }</span>
+                { details }
+                <button title="Dismiss" onclick=self.link.callback(move |_| Message::Dismiss(id))>{ "×" }</button>
+            </div>
+        }
+    }
+
+    fn view_log(&self) -> Html {
+        let notifications = &self.properties.notifications;
+        if notifications.is_empty() {
+            return html! {};
+        }
+
+        let entries = notifications.iter().rev().map(|notification| {
+            let time = UNIX_EPOCH + Duration::from_millis(notification.time as u64);
+            let details = notification.details.iter().map(|cause| html! { <li>{ cause }</li> });
+
+            html! {
+                <li class=notification.severity.class()>
+                    { format!("[{}] {}: {}", humantime::format_rfc3339_seconds(time), notification.severity, notification.message) }
+                    <ol class="error-chain">{ for details }</ol>
+                </li>
+            }
+        });
+
+        let errors = notifications
+            .iter()
+            .filter(|notification| notification.severity == Severity::Error)
+            .count();
+
+        html! {
+            <details class="notification-log">
+                <summary>{ format!("Log ({} messages, {} errors)", notifications.len(), errors) }</summary>
+                <button onclick=self.link.callback(|_| Message::ClearLog)>{ "Clear log" }</button>
+                <ul>{ for entries }</ul>
+            </details>
+        }
+    }
+
+    fn view_details(&self) -> Html {
+        let notification = match self.details.and_then(|id| self.notification(id)) {
+            Some(notification) => notification,
+            None => return html! {},
+        };
+
+        let causes = notification
+            .details
+            .iter()
+            .map(|cause| html! { <li>{ cause }</li> });
+
+        html! {
+            <div class="modal-backdrop" onclick=self.link.callback(|_| Message::CloseDetails)>
+                <div
+                    class=format!("modal {}", notification.severity.class())
+                    onclick=self.link.batch_callback(|event: MouseEvent| {
+                        // Clicks within the popup shouldn't close it
+                        event.stop_propagation();
+                        Vec::new()
+                    })
+                >
+                    <h3>{ &notification.message }</h3>
+                    <ol class="error-chain">{ for causes }</ol>
+                    <button onclick=self.link.callback(|_| Message::CloseDetails)>{ "Close" }</button>
+                </div>
+            </div>
+        }
+    }
+}
+
+impl Component for NotificationCenter {
+    type Message = Message;
+    type Properties = NotificationCenterProps;
+
+    fn create(properties: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let mut center = Self {
+            link,
+            properties,
+            toasts: Vec::new(),
+            timeouts: Vec::new(),
+            last_seen: None,
+            details: None,
+        };
+        center.show_new();
+
+        center
+    }
+
+    fn update(&mut self, message: Self::Message) -> ShouldRender {
+        match message {
+            Message::Dismiss(id) => {
+                self.toasts.retain(|&toast| toast != id);
+                self.timeouts.retain(|(toast, _)| *toast != id);
+            }
+
+            Message::ShowDetails(id) => self.details = Some(id),
+            Message::CloseDetails => self.details = None,
+
+            Message::ClearLog => {
+                self.toasts.clear();
+                self.timeouts.clear();
+                self.details = None;
+                self.properties.onclear.emit(());
+            }
+        }
+
+        true
+    }
+
+    fn change(&mut self, properties: Self::Properties) -> ShouldRender {
+        let changed = !Rc::ptr_eq(&self.properties.notifications, &properties.notifications);
+        self.properties = properties;
+
+        if changed {
+            self.show_new();
+        }
+        changed
+    }
+
+    fn view(&self) -> Html {
+        let toasts = self
+            .toasts
+            .iter()
+            .filter_map(|&id| self.notification(id))
+            .map(|notification| self.view_toast(notification));
+
+        html! {
+            <div id="notifications">
+                <div class="toasts">{ for toasts }</div>
+                { self.view_log() }
+                { self.view_details() }
+            </div>
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Notification, Severity};
+    use anyhow::Context;
+
+    #[test]
+    fn error_details_include_every_cause() {
+        let error = serde_json::from_str::<Vec<u64>>("[1, 2,")
+            .context("failed to parse profile example.json")
+            .unwrap_err();
+        let notification = Notification::error("Failed to load example.json", &error);
+
+        assert_eq!(notification.severity, Severity::Error);
+        assert_eq!(
+            notification.details,
+            [
+                "failed to parse profile example.json",
+                "EOF while parsing a value at line 1 column 6",
+            ],
+        );
+    }
+}
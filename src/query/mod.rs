@@ -0,0 +1,721 @@
+//! A small pipeline query language over profiling data, for example
+//!
+//! ```text
+//! events | where kind = operator and duration > 5ms | group by operator_name
+//!        | aggregate count(), sum(duration) | sort by sum_duration desc | limit 10
+//! ```
+//!
+//! Queries start with a source (`events` or `nodes`) and are followed by any
+//! number of `where`, `group by`, `aggregate`, `sort by`, `limit` and `select` stages
+
+mod parse;
+
+pub use parse::{parse, AggregateFn, Aggregation, CompareOp, Expr, Query, Source, Stage};
+
+use crate::data::{Node, WorkerTimelineEvent};
+use anyhow::{bail, Result};
+use humantime::Duration as HumanDuration;
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fmt::{self, Display, Write},
+    rc::Rc,
+    time::Duration,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Int(u64),
+    Float(f64),
+    /// A duration in nanoseconds
+    Duration(u64),
+    Str(Rc<str>),
+}
+
+impl Value {
+    fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Self::Int(int) | Self::Duration(int) => Some(int as f64),
+            Self::Float(float) => Some(float),
+            Self::Null | Self::Str(_) => None,
+        }
+    }
+
+    /// Compares two values, numbers are compared with numbers and strings
+    /// with strings (ignoring case), anything else is incomparable
+    pub fn compare(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Self::Int(left), Self::Int(right)) | (Self::Duration(left), Self::Duration(right)) => {
+                Some(left.cmp(right))
+            }
+            (Self::Str(left), Self::Str(right)) => {
+                Some(left.to_lowercase().cmp(&right.to_lowercase()))
+            }
+            (left, right) => left.as_f64()?.partial_cmp(&right.as_f64()?),
+        }
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        match (self, other) {
+            (Self::Int(left), Self::Int(right)) => Self::Int(left + right),
+            (Self::Duration(left), Self::Duration(right)) => Self::Duration(left + right),
+            (Self::Null, value) | (value, Self::Null) => value.clone(),
+            (left, right) => match (left.as_f64(), right.as_f64()) {
+                (Some(left), Some(right)) => Self::Float(left + right),
+                _ => Self::Null,
+            },
+        }
+    }
+
+    fn divide(&self, count: usize) -> Self {
+        match *self {
+            Self::Duration(duration) => Self::Duration(duration / count as u64),
+            ref value => value
+                .as_f64()
+                .map_or(Self::Null, |value| Self::Float(value / count as f64)),
+        }
+    }
+
+    /// Durations are written as nanoseconds so that they can be processed further
+    fn to_csv(&self) -> String {
+        match self {
+            Self::Duration(duration) => duration.to_string(),
+            value => value.to_string(),
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => Ok(()),
+            Self::Int(int) => Display::fmt(int, f),
+            Self::Float(float) => write!(f, "{:.3}", float),
+            Self::Duration(duration) => {
+                Display::fmt(&HumanDuration::from(Duration::from_nanos(*duration)), f)
+            }
+            Self::Str(string) => f.write_str(string),
+        }
+    }
+}
+
+impl From<usize> for Value {
+    fn from(int: usize) -> Self {
+        Self::Int(int as u64)
+    }
+}
+
+impl From<Option<usize>> for Value {
+    fn from(int: Option<usize>) -> Self {
+        int.map_or(Self::Null, Self::from)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(string: &str) -> Self {
+        Self::Str(Rc::from(string))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Table {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl Table {
+    pub fn column(&self, name: &str) -> Result<usize> {
+        match self.columns.iter().position(|column| column == name) {
+            Some(column) => Ok(column),
+            None => bail!(
+                "unknown column '{}', expected one of {}",
+                name,
+                self.columns.join(", "),
+            ),
+        }
+    }
+
+    /// Sorts rows by the given column, nulls are always sorted last
+    pub fn sort_by(&mut self, column: usize, descending: bool) {
+        self.rows.sort_by(|left, right| {
+            let (left, right) = (&left[column], &right[column]);
+
+            match (left, right) {
+                (Value::Null, Value::Null) => Ordering::Equal,
+                (Value::Null, _) => Ordering::Greater,
+                (_, Value::Null) => Ordering::Less,
+                (left, right) => {
+                    let ordering = left
+                        .compare(right)
+                        .unwrap_or_else(|| left.to_string().cmp(&right.to_string()));
+
+                    if descending {
+                        ordering.reverse()
+                    } else {
+                        ordering
+                    }
+                }
+            }
+        });
+    }
+
+    /// Writes the table as csv, unlike its `Display` durations are in nanoseconds
+    pub fn to_csv(&self) -> String {
+        fn write_row(csv: &mut String, row: impl Iterator<Item = String>) {
+            for (idx, value) in row.enumerate() {
+                if idx != 0 {
+                    csv.push(',');
+                }
+
+                if value.contains(&[',', '"', '\n'][..]) {
+                    write!(csv, "\"{}\"", value.replace('"', "\"\"")).unwrap();
+                } else {
+                    csv.push_str(&value);
+                }
+            }
+            csv.push('\n');
+        }
+
+        let mut csv = String::new();
+        write_row(&mut csv, self.columns.iter().cloned());
+        for row in &self.rows {
+            write_row(&mut csv, row.iter().map(Value::to_csv));
+        }
+
+        csv
+    }
+}
+
+impl Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| row.iter().map(ToString::to_string).collect())
+            .collect();
+
+        let mut widths: Vec<usize> = self.columns.iter().map(|column| column.len()).collect();
+        for row in &rows {
+            for (width, value) in widths.iter_mut().zip(row) {
+                *width = (*width).max(value.chars().count());
+            }
+        }
+
+        for (column, width) in self.columns.iter().zip(&widths) {
+            write!(f, "{:<width$}  ", column, width = width)?;
+        }
+        writeln!(f)?;
+
+        for width in &widths {
+            write!(f, "{}  ", "-".repeat(*width))?;
+        }
+        writeln!(f)?;
+
+        for row in rows {
+            for (value, width) in row.iter().zip(&widths) {
+                write!(f, "{:<width$}  ", value, width = width)?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses and runs a query against the given profile data
+pub fn run(query: &str, events: &[WorkerTimelineEvent], nodes: &[Node]) -> Result<Table> {
+    evaluate(&parse(query)?, events, nodes)
+}
+
+pub fn evaluate(query: &Query, events: &[WorkerTimelineEvent], nodes: &[Node]) -> Result<Table> {
+    let mut table = match query.source {
+        Source::Events => events_table(events),
+        Source::Nodes => nodes_table(nodes),
+    };
+
+    // `group by` is applied by the following `aggregate` stage, or
+    // as a count if the next stage isn't an aggregation
+    let mut groups: Option<&[String]> = None;
+    let count = [Aggregation {
+        function: AggregateFn::Count,
+        column: None,
+    }];
+
+    for stage in &query.stages {
+        if let Some(keys) = groups {
+            if !matches!(stage, Stage::Aggregate(_)) {
+                table = aggregate(&table, keys, &count)?;
+                groups = None;
+            }
+        }
+
+        match stage {
+            Stage::Where(expr) => {
+                check_columns(&table, expr)?;
+                let columns = &table.columns;
+                table.rows.retain(|row| matches(columns, row, expr));
+            }
+
+            Stage::GroupBy(keys) => groups = Some(keys),
+
+            Stage::Aggregate(aggregations) => {
+                table = aggregate(&table, groups.take().unwrap_or(&[]), aggregations)?;
+            }
+
+            Stage::Sort { column, descending } => {
+                let column = table.column(column)?;
+                table.sort_by(column, *descending);
+            }
+
+            Stage::Limit(limit) => table.rows.truncate(*limit),
+
+            Stage::Select(columns) => {
+                let indices = columns
+                    .iter()
+                    .map(|column| table.column(column))
+                    .collect::<Result<Vec<_>>>()?;
+
+                table = Table {
+                    columns: columns.clone(),
+                    rows: table
+                        .rows
+                        .iter()
+                        .map(|row| indices.iter().map(|&idx| row[idx].clone()).collect())
+                        .collect(),
+                };
+            }
+        }
+    }
+
+    if let Some(keys) = groups {
+        table = aggregate(&table, keys, &count)?;
+    }
+
+    Ok(table)
+}
+
+fn check_columns(table: &Table, expr: &Expr) -> Result<()> {
+    match expr {
+        Expr::Compare { column, .. } => table.column(column).map(|_| ()),
+        Expr::And(left, right) | Expr::Or(left, right) => {
+            check_columns(table, left)?;
+            check_columns(table, right)
+        }
+        Expr::Not(expr) => check_columns(table, expr),
+    }
+}
+
+fn matches(columns: &[String], row: &[Value], expr: &Expr) -> bool {
+    match expr {
+        Expr::Compare { column, op, value } => {
+            let column = columns.iter().position(|name| name == column).unwrap();
+            let row_value = &row[column];
+
+            match op {
+                CompareOp::Contains => match (row_value, value) {
+                    (Value::Str(haystack), Value::Str(needle)) => {
+                        haystack.to_lowercase().contains(&needle.to_lowercase())
+                    }
+                    _ => false,
+                },
+                CompareOp::NotEqual => row_value.compare(value) != Some(Ordering::Equal),
+                op => row_value.compare(value).is_some_and(|ordering| match op {
+                    CompareOp::Equal => ordering == Ordering::Equal,
+                    CompareOp::Less => ordering == Ordering::Less,
+                    CompareOp::LessEq => ordering != Ordering::Greater,
+                    CompareOp::Greater => ordering == Ordering::Greater,
+                    CompareOp::GreaterEq => ordering != Ordering::Less,
+                    CompareOp::NotEqual | CompareOp::Contains => unreachable!(),
+                }),
+            }
+        }
+        Expr::And(left, right) => matches(columns, row, left) && matches(columns, row, right),
+        Expr::Or(left, right) => matches(columns, row, left) || matches(columns, row, right),
+        Expr::Not(expr) => !matches(columns, row, expr),
+    }
+}
+
+fn aggregate(table: &Table, keys: &[String], aggregations: &[Aggregation]) -> Result<Table> {
+    let key_columns = keys
+        .iter()
+        .map(|key| table.column(key))
+        .collect::<Result<Vec<_>>>()?;
+    let aggregate_columns = aggregations
+        .iter()
+        .map(|aggregation| {
+            aggregation
+                .column
+                .as_deref()
+                .map(|column| table.column(column))
+        })
+        .map(Option::transpose)
+        .collect::<Result<Vec<_>>>()?;
+
+    // Groups are kept in the order they're first seen
+    let mut group_lookup: HashMap<Vec<String>, usize> = HashMap::new();
+    let mut groups: Vec<Vec<&[Value]>> = Vec::new();
+    for row in &table.rows {
+        let key = key_columns
+            .iter()
+            .map(|&column| row[column].to_string())
+            .collect();
+
+        let group = *group_lookup.entry(key).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[group].push(row);
+    }
+
+    // Aggregating everything without any rows still produces a row of results
+    if keys.is_empty() && groups.is_empty() {
+        groups.push(Vec::new());
+    }
+
+    let mut columns = keys.to_vec();
+    columns.extend(aggregations.iter().map(Aggregation::output_name));
+
+    let rows = groups
+        .into_iter()
+        .map(|rows| {
+            let mut output: Vec<Value> = key_columns
+                .iter()
+                .map(|&column| rows.first().map_or(Value::Null, |row| row[column].clone()))
+                .collect();
+
+            for (aggregation, &column) in aggregations.iter().zip(&aggregate_columns) {
+                let values = rows
+                    .iter()
+                    .filter_map(|row| column.map(|column| &row[column]))
+                    .filter(|value| **value != Value::Null);
+
+                let value = match aggregation.function {
+                    AggregateFn::Count => Value::from(rows.len()),
+                    AggregateFn::Sum => values.fold(Value::Null, |sum, value| sum.add(value)),
+                    AggregateFn::Min => values
+                        .min_by(|left, right| left.compare(right).unwrap_or(Ordering::Equal))
+                        .cloned()
+                        .unwrap_or(Value::Null),
+                    AggregateFn::Max => values
+                        .max_by(|left, right| left.compare(right).unwrap_or(Ordering::Equal))
+                        .cloned()
+                        .unwrap_or(Value::Null),
+                    AggregateFn::Avg => {
+                        let (sum, count) = values.fold((Value::Null, 0), |(sum, count), value| {
+                            (sum.add(value), count + 1)
+                        });
+
+                        if count == 0 {
+                            Value::Null
+                        } else {
+                            sum.divide(count)
+                        }
+                    }
+                };
+
+                output.push(value);
+            }
+
+            output
+        })
+        .collect();
+
+    Ok(Table { columns, rows })
+}
+
+fn events_table(events: &[WorkerTimelineEvent]) -> Table {
+    let columns = [
+        "event_id",
+        "worker",
+        "kind",
+        "name",
+        "operator_id",
+        "operator_name",
+        "start_time",
+        "duration",
+        "end_time",
+        "collapsed_events",
+        "channel_id",
+        "source_worker",
+        "target_worker",
+        "records",
+        "bytes",
+        "merge_scale",
+        "merge_inputs",
+        "merge_output",
+        "merge_effort",
+    ];
+
+    let rows = events
+        .iter()
+        .map(|event| {
+            vec![
+                Value::Int(event.event_id),
+                Value::from(event.worker),
+                Value::from(event.event.kind().name()),
+                Value::from(&*event.event.to_string()),
+                Value::from(event.event.operator_id()),
+                event.event.operator_name().map_or(Value::Null, Value::from),
+                Value::Int(event.start_time),
+                Value::Duration(event.duration),
+                Value::Int(event.end_time()),
+                Value::from(event.collapsed_events),
+                Value::from(event.traffic.map(|traffic| traffic.channel_id)),
+                Value::from(event.traffic.map(|traffic| traffic.source_worker)),
+                Value::from(event.traffic.map(|traffic| traffic.target_worker)),
+                Value::from(event.traffic.map(|traffic| traffic.records)),
+                Value::from(event.traffic.and_then(|traffic| traffic.bytes)),
+                Value::from(event.merge.map(|merge| merge.scale)),
+                Value::from(event.merge.map(|merge| merge.inputs())),
+                Value::from(event.merge.and_then(|merge| merge.complete)),
+                Value::from(event.merge.map(|merge| merge.effort)),
+            ]
+        })
+        .collect();
+
+    Table {
+        columns: columns.iter().map(|&column| column.to_owned()).collect(),
+        rows,
+    }
+}
+
+fn nodes_table(nodes: &[Node]) -> Table {
+    let columns = [
+        "id",
+        "addr",
+        "name",
+        "invocations",
+        "total_activation_time",
+        "max_activation_time",
+        "min_activation_time",
+        "average_activation_time",
+        "max_arrangement_size",
+        "min_arrangement_size",
+    ];
+
+    let rows = nodes
+        .iter()
+        .map(|node| {
+            let addr = node
+                .addr
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(".");
+
+            let durations = node
+                .activation_durations
+                .iter()
+                .map(|duration| duration.activation_time);
+            let (total, max, min) = if node.activation_durations.is_empty() {
+                (
+                    parse_duration(&node.total_activation_time),
+                    parse_duration(&node.max_activation_time),
+                    parse_duration(&node.min_activation_time),
+                )
+            } else {
+                (
+                    Value::Duration(durations.clone().sum()),
+                    durations.clone().max().map_or(Value::Null, Value::Duration),
+                    durations.min().map_or(Value::Null, Value::Duration),
+                )
+            };
+
+            vec![
+                Value::from(node.id),
+                Value::from(&*addr),
+                Value::from(&*node.name),
+                Value::from(node.invocations),
+                total,
+                max,
+                min,
+                parse_duration(&node.average_activation_time),
+                Value::from(node.max_arrangement_size),
+                Value::from(node.min_arrangement_size),
+            ]
+        })
+        .collect();
+
+    Table {
+        columns: columns.iter().map(|&column| column.to_owned()).collect(),
+        rows,
+    }
+}
+
+fn parse_duration(duration: &str) -> Value {
+    humantime::parse_duration(duration).map_or(Value::Null, |duration| {
+        Value::Duration(duration.as_nanos() as u64)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run, Table, Value};
+    use crate::data::{ActivationDuration, Node, TimelineEvent, WorkerTimelineEvent};
+
+    fn events() -> Vec<WorkerTimelineEvent> {
+        let operator = |operator_id, name: &str| TimelineEvent::OperatorActivation {
+            operator_id,
+            operator_name: name.to_owned(),
+        };
+
+        [
+            (0, operator(1, "Join"), 10_000_000),
+            (1, operator(1, "Join"), 2_000_000),
+            (1, operator(4, "Map"), 7_000_000),
+            (0, TimelineEvent::Parked, 9_000_000),
+            (1, operator(1, "Join"), 6_000_000),
+        ]
+        .iter()
+        .cloned()
+        .enumerate()
+        .map(|(idx, (worker, event, duration))| WorkerTimelineEvent {
+            event_id: idx as u64,
+            worker,
+            event,
+            start_time: idx as u64 * 1_000,
+            duration,
+            collapsed_events: 1,
+            traffic: None,
+            merge: None,
+        })
+        .collect()
+    }
+
+    fn node(id: usize, name: &str, durations: &[u64]) -> Node {
+        Node {
+            id,
+            addr: vec![0, id],
+            name: name.to_owned(),
+            max_activation_time: String::new(),
+            min_activation_time: String::new(),
+            average_activation_time: "1ms".to_owned(),
+            total_activation_time: String::new(),
+            invocations: durations.len(),
+            fill_color: String::new(),
+            text_color: String::new(),
+            activation_durations: durations
+                .iter()
+                .map(|&activation_time| ActivationDuration {
+                    activation_time,
+                    activated_at: 0,
+                })
+                .collect(),
+            max_arrangement_size: None,
+            min_arrangement_size: None,
+        }
+    }
+
+    fn column(table: &Table, column: &str) -> Vec<Value> {
+        let column = table.column(column).unwrap();
+        table.rows.iter().map(|row| row[column].clone()).collect()
+    }
+
+    #[test]
+    fn filter_and_group_events() {
+        let table = run(
+            "events | where kind = operator and duration > 5ms \
+             | group by operator_name | aggregate count(), sum(duration) \
+             | sort by sum_duration desc",
+            &events(),
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(table.columns, ["operator_name", "count", "sum_duration"]);
+        assert_eq!(
+            column(&table, "operator_name"),
+            [Value::from("Join"), Value::from("Map")]
+        );
+        assert_eq!(column(&table, "count"), [Value::Int(2), Value::Int(1)]);
+        assert_eq!(
+            column(&table, "sum_duration"),
+            [Value::Duration(16_000_000), Value::Duration(7_000_000)],
+        );
+    }
+
+    #[test]
+    fn boolean_expressions() {
+        let events = events();
+        let ids = |query| column(&run(query, &events, &[]).unwrap(), "event_id");
+
+        assert_eq!(
+            ids("events | where worker = 0 or (name ~ 'map' and not duration < 5ms)"),
+            [Value::Int(0), Value::Int(2), Value::Int(3)],
+        );
+        assert_eq!(
+            ids("events | where operator_id != 1 | select event_id"),
+            [Value::Int(2), Value::Int(3)],
+        );
+        assert_eq!(
+            ids("events | sort by duration | limit 2"),
+            [Value::Int(1), Value::Int(4)],
+        );
+    }
+
+    #[test]
+    fn group_without_aggregate_counts() {
+        let table = run("events | group by worker | sort by worker", &events(), &[]).unwrap();
+
+        assert_eq!(table.columns, ["worker", "count"]);
+        assert_eq!(
+            table.rows,
+            [
+                vec![Value::Int(0), Value::Int(2)],
+                vec![Value::Int(1), Value::Int(3)],
+            ],
+        );
+    }
+
+    #[test]
+    fn node_metrics() {
+        let nodes = [node(1, "Input", &[100, 300]), node(2, "Join", &[5_000])];
+        let table = run(
+            "nodes | where total_activation_time >= 1us | aggregate avg(total_activation_time), max(invocations)",
+            &[],
+            &nodes,
+        )
+        .unwrap();
+
+        assert_eq!(table.rows, [vec![Value::Duration(5_000), Value::Int(1)]],);
+    }
+
+    #[test]
+    fn invalid_queries() {
+        let events = events();
+        for query in &[
+            "spans",
+            "events | where",
+            "events | where missing = 1",
+            "events | sort worker",
+            "events | aggregate sum()",
+            "events | limit ten",
+            "events | where name = 'unterminated",
+            "events worker",
+        ] {
+            assert!(run(query, &events, &[]).is_err(), "{} should fail", query);
+        }
+    }
+
+    #[test]
+    fn csv_escaping() {
+        let table = Table {
+            columns: vec!["name".to_owned(), "count".to_owned()],
+            rows: vec![vec![Value::from("a, \"b\""), Value::Int(2)]],
+        };
+
+        assert_eq!(table.to_csv(), "name,count\n\"a, \"\"b\"\"\",2\n");
+    }
+
+    #[test]
+    fn csv_durations_are_nanoseconds() {
+        let table = Table {
+            columns: vec!["total".to_owned(), "mean".to_owned()],
+            rows: vec![vec![Value::Duration(1_500_000), Value::Float(2.5)]],
+        };
+
+        assert_eq!(table.to_csv(), "total,mean\n1500000,2.500\n");
+        assert!(table.to_string().contains("1ms 500us"));
+    }
+}
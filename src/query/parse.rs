@@ -0,0 +1,420 @@
+use crate::query::Value;
+use anyhow::{bail, Context, Result};
+use std::{iter::Peekable, rc::Rc, str::CharIndices};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Events,
+    Nodes,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    pub source: Source,
+    pub stages: Vec<Stage>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stage {
+    Where(Expr),
+    GroupBy(Vec<String>),
+    Aggregate(Vec<Aggregation>),
+    Sort { column: String, descending: bool },
+    Limit(usize),
+    Select(Vec<String>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateFn {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Avg,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Aggregation {
+    pub function: AggregateFn,
+    pub column: Option<String>,
+}
+
+impl Aggregation {
+    /// The name of the column the aggregation produces, like `sum_duration`
+    pub fn output_name(&self) -> String {
+        let function = match self.function {
+            AggregateFn::Count => "count",
+            AggregateFn::Sum => "sum",
+            AggregateFn::Min => "min",
+            AggregateFn::Max => "max",
+            AggregateFn::Avg => "avg",
+        };
+
+        match self.column.as_ref() {
+            Some(column) => format!("{}_{}", function, column),
+            None => function.to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Equal,
+    NotEqual,
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
+    Contains,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Compare {
+        column: String,
+        op: CompareOp,
+        value: Value,
+    },
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(f64),
+    Duration(u64),
+    Str(String),
+    Pipe,
+    Comma,
+    OpenParen,
+    CloseParen,
+    Compare(CompareOp),
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = query.char_indices().peekable();
+
+    while let Some(&(start, char)) = chars.peek() {
+        let token = match char {
+            char if char.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+
+            '|' => single(&mut chars, Token::Pipe),
+            ',' => single(&mut chars, Token::Comma),
+            '(' => single(&mut chars, Token::OpenParen),
+            ')' => single(&mut chars, Token::CloseParen),
+            '~' => single(&mut chars, Token::Compare(CompareOp::Contains)),
+            '=' => {
+                chars.next();
+                // Allow both `=` and `==`
+                chars.next_if(|&(_, char)| char == '=');
+                Token::Compare(CompareOp::Equal)
+            }
+            '!' => {
+                chars.next();
+                if chars.next_if(|&(_, char)| char == '=').is_none() {
+                    bail!("expected '=' after '!' at {}", start);
+                }
+                Token::Compare(CompareOp::NotEqual)
+            }
+            '<' | '>' => {
+                chars.next();
+                let or_equal = chars.next_if(|&(_, char)| char == '=').is_some();
+
+                Token::Compare(match (char, or_equal) {
+                    ('<', false) => CompareOp::Less,
+                    ('<', true) => CompareOp::LessEq,
+                    ('>', false) => CompareOp::Greater,
+                    _ => CompareOp::GreaterEq,
+                })
+            }
+
+            '"' | '\'' => {
+                chars.next();
+
+                let mut string = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, end)) if end == char => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, escaped)) => string.push(escaped),
+                            None => bail!("unterminated string starting at {}", start),
+                        },
+                        Some((_, char)) => string.push(char),
+                        None => bail!("unterminated string starting at {}", start),
+                    }
+                }
+
+                Token::Str(string)
+            }
+
+            char if char.is_ascii_digit() => {
+                let end = take_while(&mut chars, |char| char.is_ascii_digit() || char == '.');
+                let number = &query[start..end];
+
+                // A number directly followed by a unit is a duration, like `5ms`
+                let unit_end = take_while(&mut chars, |char| char.is_alphabetic());
+                if unit_end > end {
+                    let duration = &query[start..unit_end];
+                    let duration = humantime::parse_duration(duration)
+                        .with_context(|| format!("invalid duration '{}'", duration))?;
+
+                    Token::Duration(duration.as_nanos() as u64)
+                } else {
+                    Token::Number(
+                        number
+                            .parse()
+                            .with_context(|| format!("invalid number '{}'", number))?,
+                    )
+                }
+            }
+
+            char if char.is_alphabetic() || char == '_' => {
+                let end = take_while(&mut chars, |char| char.is_alphanumeric() || char == '_');
+                Token::Ident(query[start..end].to_lowercase())
+            }
+
+            char => bail!("unexpected character '{}' at {}", char, start),
+        };
+
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+fn single(chars: &mut Peekable<CharIndices<'_>>, token: Token) -> Token {
+    chars.next();
+    token
+}
+
+/// Consumes characters matching `predicate`, returning the byte offset after the last one
+fn take_while<F>(chars: &mut Peekable<CharIndices<'_>>, predicate: F) -> usize
+where
+    F: Fn(char) -> bool,
+{
+    let mut end = chars.peek().map_or(0, |&(idx, _)| idx);
+    while let Some((idx, char)) = chars.next_if(|&(_, char)| predicate(char)) {
+        end = idx + char.len_utf8();
+    }
+
+    end
+}
+
+pub fn parse(query: &str) -> Result<Query> {
+    let tokens = tokenize(query)?;
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+    };
+
+    let source = match parser.ident()?.as_str() {
+        "events" => Source::Events,
+        "nodes" => Source::Nodes,
+        source => bail!("unknown source '{}', expected 'events' or 'nodes'", source),
+    };
+
+    let mut stages = Vec::new();
+    while parser.eat(&Token::Pipe) {
+        stages.push(parser.stage()?);
+    }
+
+    if let Some(token) = parser.peek() {
+        bail!("unexpected {:?}, stages must be separated by '|'", token);
+    }
+
+    Ok(Query { source, stages })
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(ident)) if ident == keyword) && {
+            self.position += 1;
+            true
+        }
+    }
+
+    fn expect(&mut self, token: &Token) -> Result<()> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            bail!("expected {:?}, found {:?}", token, self.peek())
+        }
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        match self.next() {
+            Some(Token::Ident(ident)) => Ok(ident),
+            token => bail!("expected a name, found {:?}", token),
+        }
+    }
+
+    fn idents(&mut self) -> Result<Vec<String>> {
+        let mut idents = vec![self.ident()?];
+        while self.eat(&Token::Comma) {
+            idents.push(self.ident()?);
+        }
+
+        Ok(idents)
+    }
+
+    fn stage(&mut self) -> Result<Stage> {
+        let stage = match self.ident()?.as_str() {
+            "where" | "filter" => Stage::Where(self.expr()?),
+
+            "group" => {
+                if !self.eat_keyword("by") {
+                    bail!("expected 'by' after 'group'");
+                }
+                Stage::GroupBy(self.idents()?)
+            }
+
+            "aggregate" | "agg" => {
+                let mut aggregations = vec![self.aggregation()?];
+                while self.eat(&Token::Comma) {
+                    aggregations.push(self.aggregation()?);
+                }
+                Stage::Aggregate(aggregations)
+            }
+
+            "sort" => {
+                if !self.eat_keyword("by") {
+                    bail!("expected 'by' after 'sort'");
+                }
+
+                let column = self.ident()?;
+                let descending = if self.eat_keyword("desc") {
+                    true
+                } else {
+                    self.eat_keyword("asc");
+                    false
+                };
+
+                Stage::Sort { column, descending }
+            }
+
+            "limit" => match self.next() {
+                Some(Token::Number(limit)) if limit >= 0.0 => Stage::Limit(limit as usize),
+                token => bail!("expected a number after 'limit', found {:?}", token),
+            },
+
+            "select" => Stage::Select(self.idents()?),
+
+            stage => bail!(
+                "unknown stage '{}', expected one of where, group, aggregate, sort, limit or select",
+                stage,
+            ),
+        };
+
+        Ok(stage)
+    }
+
+    fn aggregation(&mut self) -> Result<Aggregation> {
+        let function = match self.ident()?.as_str() {
+            "count" => AggregateFn::Count,
+            "sum" => AggregateFn::Sum,
+            "min" => AggregateFn::Min,
+            "max" => AggregateFn::Max,
+            "avg" | "mean" => AggregateFn::Avg,
+            function => bail!("unknown aggregate '{}'", function),
+        };
+
+        self.expect(&Token::OpenParen)?;
+        let column = if self.peek() == Some(&Token::CloseParen) {
+            None
+        } else {
+            Some(self.ident()?)
+        };
+        self.expect(&Token::CloseParen)?;
+
+        if column.is_none() && function != AggregateFn::Count {
+            bail!("{:?} needs a column to aggregate", function);
+        }
+
+        Ok(Aggregation { function, column })
+    }
+
+    fn expr(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+        while self.eat_keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.unary()?;
+        while self.eat_keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.eat_keyword("not") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+
+        if self.eat(&Token::OpenParen) {
+            let expr = self.expr()?;
+            self.expect(&Token::CloseParen)?;
+            return Ok(expr);
+        }
+
+        let column = self.ident()?;
+        let op = match self.next() {
+            Some(Token::Compare(op)) => op,
+            token => bail!(
+                "expected a comparison after '{}', found {:?}",
+                column,
+                token
+            ),
+        };
+        let value = match self.next() {
+            Some(Token::Number(number)) if number.fract() == 0.0 && number >= 0.0 => {
+                Value::Int(number as u64)
+            }
+            Some(Token::Number(number)) => Value::Float(number),
+            Some(Token::Duration(duration)) => Value::Duration(duration),
+            Some(Token::Str(string)) | Some(Token::Ident(string)) => Value::Str(Rc::from(string)),
+            token => bail!(
+                "expected a value to compare '{}' with, found {:?}",
+                column,
+                token
+            ),
+        };
+
+        Ok(Expr::Compare { column, op, value })
+    }
+}
@@ -0,0 +1,249 @@
+use crate::download::js_error;
+use anyhow::{anyhow, Context, Result};
+use js_sys::{Array, Uint8Array};
+use serde::{Deserialize, Serialize};
+use std::{cell::Cell, rc::Rc};
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use web_sys::{File, IdbDatabase, IdbObjectStore, IdbRequest, IdbTransactionMode};
+use yew::Callback;
+
+/// The most profiles that are remembered, older ones are evicted
+pub const MAX_RECENT_FILES: usize = 8;
+
+const DATABASE_NAME: &str = "differential-dashboard";
+const DATABASE_VERSION: u32 = 1;
+const PROFILES_STORE: &str = "profiles";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecentFile {
+    pub name: String,
+    /// The size of the profile in bytes
+    pub size: usize,
+}
+
+/// Recently opened profiles, most recent first. The list itself lives in
+/// local storage while the profiles' contents are kept in a `ProfileStore`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RecentFiles {
+    files: Vec<RecentFile>,
+}
+
+impl RecentFiles {
+    pub fn iter(&self) -> impl Iterator<Item = &RecentFile> + '_ {
+        self.files.iter()
+    }
+
+    pub fn last_opened(&self) -> Option<&RecentFile> {
+        self.files.first()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Moves the file to the front of the list, returning any files
+    /// that no longer fit
+    pub fn opened(&mut self, file: RecentFile) -> Vec<RecentFile> {
+        self.remove(&file.name);
+        self.files.insert(0, file);
+
+        if self.files.len() > MAX_RECENT_FILES {
+            self.files.split_off(MAX_RECENT_FILES)
+        } else {
+            Vec::new()
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<RecentFile> {
+        let idx = self.files.iter().position(|file| file.name == name)?;
+        Some(self.files.remove(idx))
+    }
+
+    pub fn clear(&mut self) -> Vec<RecentFile> {
+        self.files.drain(..).collect()
+    }
+}
+
+/// Profile files stored in IndexedDB, since profiles are often too large for
+/// local storage
+#[derive(Debug, Clone)]
+pub struct ProfileStore {
+    database: IdbDatabase,
+}
+
+impl ProfileStore {
+    pub fn open(callback: Callback<Result<Self>>) {
+        let open = || -> Result<()> {
+            let request = web_sys::window()
+                .context("failed to get the window")?
+                .indexed_db()
+                .map_err(js_error)?
+                .context("IndexedDB isn't available")?
+                .open_with_u32(DATABASE_NAME, DATABASE_VERSION)
+                .map_err(js_error)?;
+
+            // Only called when the database is first created or its version changes
+            let on_upgrade = {
+                let request = request.clone();
+
+                Closure::once_into_js(move || {
+                    let created = request
+                        .result()
+                        .map(|database| database.unchecked_into::<IdbDatabase>())
+                        .and_then(|database| database.create_object_store(PROFILES_STORE));
+
+                    if let Err(error) = created {
+                        tracing::error!("failed to create the profile store: {:?}", error);
+                    }
+                })
+            };
+            request.set_onupgradeneeded(Some(on_upgrade.unchecked_ref()));
+
+            let callback = callback.clone();
+            on_complete(&request, move |database| {
+                callback.emit(database.map(|database| Self {
+                    database: database.unchecked_into(),
+                }))
+            });
+
+            Ok(())
+        };
+
+        if let Err(error) = open() {
+            callback.emit(Err(error.context("failed to open the profile store")));
+        }
+    }
+
+    /// Stores a profile's file, IndexedDB keeps its own copy so the file's
+    /// contents never have to be read into memory
+    pub fn put(&self, name: &str, file: &File) -> Result<()> {
+        let request = self
+            .profiles(IdbTransactionMode::Readwrite)?
+            .put_with_key(file, &JsValue::from_str(name))
+            .map_err(js_error)?;
+
+        let name = name.to_owned();
+        on_complete(&request, move |result| {
+            if let Err(error) = result {
+                tracing::error!("failed to store profile {}: {:#}", name, error);
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Fetches a stored profile's file, if it's been stored
+    pub fn get(&self, name: &str, callback: Callback<Result<Option<File>>>) -> Result<()> {
+        let request = self
+            .profiles(IdbTransactionMode::Readonly)?
+            .get(&JsValue::from_str(name))
+            .map_err(js_error)?;
+
+        on_complete(&request, move |contents| {
+            let file = contents.map(|contents| contents.dyn_into::<File>().ok());
+
+            callback.emit(file);
+        });
+
+        Ok(())
+    }
+
+    pub fn delete(&self, name: &str) -> Result<()> {
+        self.profiles(IdbTransactionMode::Readwrite)?
+            .delete(&JsValue::from_str(name))
+            .map_err(js_error)?;
+
+        Ok(())
+    }
+
+    fn profiles(&self, mode: IdbTransactionMode) -> Result<IdbObjectStore> {
+        self.database
+            .transaction_with_str_and_mode(PROFILES_STORE, mode)
+            .and_then(|transaction| transaction.object_store(PROFILES_STORE))
+            .map_err(js_error)
+    }
+}
+
+/// Creates a file holding a copy of `contents`, it's kept by the browser
+/// rather than in the dashboard's memory
+pub fn file_from_bytes(name: &str, contents: &[u8]) -> Result<File> {
+    File::new_with_u8_array_sequence(&Array::of1(&Uint8Array::from(contents)), name)
+        .map_err(js_error)
+        .with_context(|| format!("failed to create a file for {}", name))
+}
+
+/// Calls `complete` with the request's result once it succeeds or fails
+fn on_complete<F>(request: &IdbRequest, complete: F)
+where
+    F: FnOnce(Result<JsValue>) + 'static,
+{
+    // Only one of the two handlers will ever be called
+    let complete = Rc::new(Cell::new(Some(complete)));
+
+    let on_success = {
+        let (request, complete) = (request.clone(), complete.clone());
+
+        Closure::once_into_js(move || {
+            if let Some(complete) = complete.take() {
+                complete(request.result().map_err(js_error));
+            }
+        })
+    };
+
+    let on_error = {
+        let request = request.clone();
+
+        Closure::once_into_js(move || {
+            if let Some(complete) = complete.take() {
+                let error = request
+                    .error()
+                    .ok()
+                    .flatten()
+                    .map_or_else(|| "unknown error".to_owned(), |error| error.message());
+
+                complete(Err(anyhow!("IndexedDB request failed: {}", error)));
+            }
+        })
+    };
+
+    request.set_onsuccess(Some(on_success.unchecked_ref()));
+    request.set_onerror(Some(on_error.unchecked_ref()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RecentFile, RecentFiles, MAX_RECENT_FILES};
+
+    fn file(name: &str) -> RecentFile {
+        RecentFile {
+            name: name.to_owned(),
+            size: 10,
+        }
+    }
+
+    #[test]
+    fn most_recent_first() {
+        let mut recent = RecentFiles::default();
+        assert!(recent.opened(file("a.json")).is_empty());
+        assert!(recent.opened(file("b.json")).is_empty());
+        assert!(recent.opened(file("a.json")).is_empty());
+
+        let names: Vec<_> = recent.iter().map(|file| file.name.as_str()).collect();
+        assert_eq!(names, ["a.json", "b.json"]);
+        assert_eq!(recent.last_opened(), Some(&file("a.json")));
+    }
+
+    #[test]
+    fn evicts_old_files() {
+        let mut recent = RecentFiles::default();
+        for idx in 0..MAX_RECENT_FILES {
+            recent.opened(file(&idx.to_string()));
+        }
+
+        assert_eq!(recent.opened(file("new")), vec![file("0")]);
+        assert_eq!(recent.remove("new"), Some(file("new")));
+        assert_eq!(recent.remove("new"), None);
+        assert_eq!(recent.clear().len(), MAX_RECENT_FILES - 1);
+        assert!(recent.is_empty());
+    }
+}
@@ -0,0 +1,505 @@
+use crate::{
+    data::{MergeInfo, TimelineEvent, WorkerTimelineEvent},
+    download::svg_url,
+    view::{format_duration, svg_document},
+};
+use gigatrace::render::{Color, DrawCommand, Point, Rect, TextAlign};
+use serde::{Deserialize, Serialize};
+use std::{cmp::Reverse, collections::BTreeMap, ops::Range, rc::Rc};
+use yew::{html, Component, ComponentLink, Html, Properties, ShouldRender};
+
+/// The most merges drawn per arrangement, the ones that merged the most updates are kept
+pub const MAX_MERGES: usize = 2_000;
+
+/// The width spines are drawn at, images are scaled to fit the page
+const SPINE_WIDTH: f64 = 1200.0;
+
+const LABEL_WIDTH: f64 = 80.0;
+const ROW_HEIGHT: f64 = 24.0;
+
+const INPUT_COLOR: Color = Color::rgba8(46, 125, 50, 0x55);
+const OUTPUT_COLOR: Color = Color::rgba8(46, 125, 50, 0xCC);
+const IN_PROGRESS_COLOR: Color = Color::rgba8(239, 108, 0, 0x55);
+const EFFORT_COLOR: Color = Color::rgba8(239, 108, 0, 0xCC);
+const ROW_COLOR: Color = Color::rgb8(0xBD, 0xBD, 0xBD);
+
+/// A merge of two batches of an arrangement's spine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpineMerge {
+    pub worker: usize,
+    /// When the merge's event started and ended, in nanoseconds
+    pub start: u64,
+    pub end: u64,
+    pub info: MergeInfo,
+}
+
+/// Every merge of a single arrangement's batches
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArrangementSpine {
+    pub operator_id: usize,
+    pub name: String,
+    /// Ordered by when they started
+    pub merges: Vec<SpineMerge>,
+    /// The number of merges that recorded their batches, including those not in `merges`
+    pub recorded: usize,
+    /// The number of merges that didn't record their batches
+    pub unrecorded: usize,
+    /// Time spent merging, in nanoseconds
+    pub merge_time: u64,
+    /// Updates going into and coming out of the merges that completed
+    pub records_in: u64,
+    pub records_out: u64,
+    /// The number of merges still running when their event ended
+    pub in_progress: usize,
+    /// The largest scale any batches were merged at
+    pub max_scale: usize,
+}
+
+impl ArrangementSpine {
+    /// The share of updates completed merges consolidated away
+    pub fn compaction(&self) -> f64 {
+        if self.records_in == 0 {
+            0.0
+        } else {
+            1.0 - self.records_out as f64 / self.records_in as f64
+        }
+    }
+}
+
+/// The merges of every arrangement's spine
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpineSummary {
+    /// The span of the profile's events, in nanoseconds
+    pub span: Range<u64>,
+    /// Most time spent merging first
+    pub arrangements: Vec<ArrangementSpine>,
+}
+
+impl SpineSummary {
+    pub fn new(events: &[WorkerTimelineEvent]) -> Self {
+        let start = events.iter().map(|event| event.start_time).min();
+        let end = events.iter().map(WorkerTimelineEvent::end_time).max();
+        let span = start.unwrap_or(0)..end.unwrap_or(0);
+
+        let mut arrangements = BTreeMap::new();
+        for event in events {
+            let (operator_id, operator_name) = match &event.event {
+                TimelineEvent::Merge {
+                    operator_id,
+                    operator_name,
+                } => (*operator_id, operator_name),
+                _ => continue,
+            };
+
+            let spine = arrangements
+                .entry(operator_id)
+                .or_insert_with(|| ArrangementSpine {
+                    operator_id,
+                    name: operator_name.clone(),
+                    merges: Vec::new(),
+                    recorded: 0,
+                    unrecorded: 0,
+                    merge_time: 0,
+                    records_in: 0,
+                    records_out: 0,
+                    in_progress: 0,
+                    max_scale: 0,
+                });
+            spine.merge_time += event.duration;
+
+            let info = match event.merge {
+                Some(info) => info,
+                None => {
+                    spine.unrecorded += 1;
+                    continue;
+                }
+            };
+
+            spine.recorded += 1;
+            spine.max_scale = spine.max_scale.max(info.scale);
+            match info.complete {
+                Some(output) => {
+                    spine.records_in += info.inputs() as u64;
+                    spine.records_out += output as u64;
+                }
+                None => spine.in_progress += 1,
+            }
+
+            spine.merges.push(SpineMerge {
+                worker: event.worker,
+                start: event.start_time,
+                end: event.end_time(),
+                info,
+            });
+        }
+
+        let mut arrangements: Vec<_> = arrangements.into_values().collect();
+        for spine in &mut arrangements {
+            let merges = &mut spine.merges;
+            if merges.len() > MAX_MERGES {
+                merges.select_nth_unstable_by_key(MAX_MERGES, |merge| Reverse(merge.info.inputs()));
+                merges.truncate(MAX_MERGES);
+            }
+            merges.sort_by_key(|merge| (merge.start, merge.worker));
+        }
+        arrangements.sort_by_key(|spine| Reverse(spine.merge_time));
+
+        Self { span, arrangements }
+    }
+
+    /// Whether any of the profile's merges recorded the batches they merged
+    pub fn is_empty(&self) -> bool {
+        self.arrangements.iter().all(|spine| spine.recorded == 0)
+    }
+
+    /// Lays out a row per scale of the spine with a bar for every merge at that
+    /// scale, returns the height of the layout along with its commands
+    ///
+    /// Each bar's faint part shows the updates that went into the merge and its
+    /// solid part the updates that came out, or the work done on merges that
+    /// were still in progress
+    pub fn spine_layout(&self, spine: &ArrangementSpine, width: f64) -> (f64, Vec<DrawCommand>) {
+        let row_y = |scale: usize| 10.0 + scale as f64 * ROW_HEIGHT;
+        let graph_width = (width - LABEL_WIDTH - 10.0).max(1.0);
+        let span_width = (self.span.end - self.span.start).max(1) as f64;
+        let time_x =
+            |time: u64| LABEL_WIDTH + (time - self.span.start) as f64 / span_width * graph_width;
+
+        let mut commands = Vec::new();
+        for scale in 0..=spine.max_scale {
+            let y = row_y(scale);
+
+            commands.push(DrawCommand::Text {
+                text: format!("Scale {}", scale),
+                at: Point::new(5.0, y + ROW_HEIGHT / 2.0 - 7.0),
+                size: 12.0,
+                color: Color::BLACK,
+                align: TextAlign::Start,
+                angle: 0.0,
+            });
+            commands.push(DrawCommand::Line {
+                from: Point::new(LABEL_WIDTH, y + ROW_HEIGHT),
+                to: Point::new(LABEL_WIDTH + graph_width, y + ROW_HEIGHT),
+                color: ROW_COLOR,
+                width: 1.0,
+                dash: None,
+            });
+        }
+
+        for merge in &spine.merges {
+            let x = time_x(merge.start);
+            let bar_width = (time_x(merge.end) - x).max(1.0);
+            let bottom = row_y(merge.info.scale) + ROW_HEIGHT - 1.0;
+            let height = ROW_HEIGHT - 4.0;
+
+            let inputs = merge.info.inputs().max(1) as f64;
+            let (done, background, foreground) = match merge.info.complete {
+                Some(output) => (output as f64, INPUT_COLOR, OUTPUT_COLOR),
+                None => (merge.info.effort as f64, IN_PROGRESS_COLOR, EFFORT_COLOR),
+            };
+            let done = (done / inputs).min(1.0) * height;
+
+            commands.push(DrawCommand::FillRect {
+                rect: Rect::new(x, bottom - height, bar_width, height),
+                radius: 0.0,
+                color: background,
+            });
+            commands.push(DrawCommand::FillRect {
+                rect: Rect::new(x, bottom - done, bar_width, done),
+                radius: 0.0,
+                color: foreground,
+            });
+        }
+
+        (20.0 + (spine.max_scale + 1) as f64 * ROW_HEIGHT, commands)
+    }
+}
+
+#[derive(Debug, Clone, Properties)]
+pub struct SpinePanelProps {
+    pub spines: Rc<SpineSummary>,
+}
+
+#[derive(Debug)]
+pub enum Message {
+    Select(usize),
+}
+
+/// A table of every arrangement's merges along with the selected arrangement's
+/// batches over time
+#[derive(Debug)]
+pub struct SpinePanel {
+    link: ComponentLink<Self>,
+    properties: SpinePanelProps,
+    /// An index into the summary's arrangements
+    selected: usize,
+    /// The selected arrangement's spine, drawn once as an svg image
+    chart: String,
+}
+
+impl SpinePanel {
+    fn draw(&mut self) {
+        let spines = &self.properties.spines;
+
+        self.chart = match spines.arrangements.get(self.selected) {
+            Some(spine) if spine.recorded > 0 => {
+                let (height, commands) = spines.spine_layout(spine, SPINE_WIDTH);
+                svg_url(&svg_document(SPINE_WIDTH, height, &commands))
+            }
+            _ => String::new(),
+        };
+    }
+
+    fn view_row(&self, index: usize, spine: &ArrangementSpine) -> Html {
+        let class = if index == self.selected {
+            "spine-row selected"
+        } else {
+            "spine-row"
+        };
+
+        html! {
+            <tr class=class onclick=self.link.callback(move |_| Message::Select(index))>
+                <td>{ format!("{} ({})", spine.name, spine.operator_id) }</td>
+                <td>{ spine.recorded + spine.unrecorded }</td>
+                <td>{ format_duration(spine.merge_time) }</td>
+                <td>{ spine.records_in }</td>
+                <td>{ spine.records_out }</td>
+                <td>{ format!("{:.1}%", spine.compaction() * 100.0) }</td>
+                <td>{ spine.in_progress }</td>
+                <td>{ spine.max_scale }</td>
+            </tr>
+        }
+    }
+}
+
+impl Component for SpinePanel {
+    type Message = Message;
+    type Properties = SpinePanelProps;
+
+    fn create(properties: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let mut panel = Self {
+            link,
+            properties,
+            selected: 0,
+            chart: String::new(),
+        };
+        panel.draw();
+
+        panel
+    }
+
+    fn update(&mut self, message: Self::Message) -> ShouldRender {
+        match message {
+            Message::Select(index) => {
+                if index == self.selected {
+                    return false;
+                }
+
+                self.selected = index;
+                self.draw();
+            }
+        }
+
+        true
+    }
+
+    fn change(&mut self, properties: Self::Properties) -> ShouldRender {
+        if Rc::ptr_eq(&self.properties.spines, &properties.spines) {
+            return false;
+        }
+
+        self.properties = properties;
+        self.selected = 0;
+        self.draw();
+
+        true
+    }
+
+    fn view(&self) -> Html {
+        let spines = &self.properties.spines;
+        if spines.is_empty() {
+            return html! {
+                <details id="spines">
+                    <summary>{ "Arrangement spines" }</summary>
+                    <p>{ "This profile doesn't record the batches its merges combined" }</p>
+                </details>
+            };
+        }
+
+        let rows = spines
+            .arrangements
+            .iter()
+            .enumerate()
+            .map(|(index, spine)| self.view_row(index, spine));
+
+        let chart = match spines.arrangements.get(self.selected) {
+            Some(spine) if spine.recorded > 0 => {
+                let mut shown = if spine.recorded > spine.merges.len() {
+                    format!(
+                        "The {} largest of {} merges of {}'s batches by scale",
+                        spine.merges.len(),
+                        spine.recorded,
+                        spine.name,
+                    )
+                } else {
+                    format!(
+                        "{} merges of {}'s batches by scale",
+                        spine.recorded, spine.name,
+                    )
+                };
+                if spine.unrecorded > 0 {
+                    shown.push_str(&format!(
+                        ", {} more didn't record their batches",
+                        spine.unrecorded,
+                    ));
+                }
+
+                html! {
+                    <>
+                        <p>{ shown }</p>
+                        <img class="spine-chart" src=self.chart.clone() />
+                    </>
+                }
+            }
+            _ => {
+                html! { <p>{ "The selected arrangement's merges didn't record their batches" }</p> }
+            }
+        };
+
+        html! {
+            <details id="spines" open=true>
+                <summary>{ "Arrangement spines" }</summary>
+                <table class="spine-table">
+                    <thead>
+                        <tr>
+                            <th>{ "Arrangement" }</th>
+                            <th>{ "Merges" }</th>
+                            <th>{ "Time merging" }</th>
+                            <th>{ "Updates in" }</th>
+                            <th>{ "Updates out" }</th>
+                            <th>{ "Compacted" }</th>
+                            <th>{ "In progress" }</th>
+                            <th>{ "Largest scale" }</th>
+                        </tr>
+                    </thead>
+                    <tbody>{ for rows }</tbody>
+                </table>
+                { chart }
+            </details>
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpineSummary;
+    use crate::{
+        data::{MergeInfo, TimelineEvent, WorkerTimelineEvent},
+        demo::loaded_demo,
+        view::svg_document,
+    };
+
+    #[test]
+    fn summarizes_merges() {
+        let profile = loaded_demo();
+        let merges = profile
+            .events
+            .iter()
+            .filter(|event| matches!(event.event, TimelineEvent::Merge { .. }))
+            .count();
+        let spines = &profile.spines;
+
+        assert!(!spines.is_empty());
+        assert_eq!(
+            spines
+                .arrangements
+                .iter()
+                .map(|spine| spine.recorded)
+                .sum::<usize>(),
+            merges,
+        );
+        assert!(spines
+            .arrangements
+            .windows(2)
+            .all(|spines| spines[0].merge_time >= spines[1].merge_time));
+
+        for spine in &spines.arrangements {
+            assert_eq!(spine.unrecorded, 0);
+            assert!(spine.records_out <= spine.records_in);
+            assert!((0.0..=1.0).contains(&spine.compaction()));
+            assert!(spine
+                .merges
+                .iter()
+                .all(|merge| merge.info.scale <= spine.max_scale && merge.start <= merge.end));
+            assert!(spine
+                .merges
+                .windows(2)
+                .all(|merges| merges[0].start <= merges[1].start));
+        }
+        assert!(spines
+            .arrangements
+            .iter()
+            .any(|spine| spine.in_progress > 0));
+    }
+
+    #[test]
+    fn draws_merges_by_scale() {
+        let profile = loaded_demo();
+
+        let spine = &profile.spines.arrangements[0];
+        let (height, commands) = profile.spines.spine_layout(spine, 1000.0);
+        let svg = svg_document(1000.0, height, &commands);
+
+        let scales = spine.max_scale + 1;
+        assert!(svg.contains(&format!(">Scale {}</text>", spine.max_scale)));
+        assert_eq!(svg.matches("<line").count(), scales);
+        // The background and two rects per merge
+        assert_eq!(svg.matches("<rect").count(), 1 + spine.merges.len() * 2);
+    }
+
+    #[test]
+    fn counts_merges_without_batches() {
+        let merge = |start_time, merge| WorkerTimelineEvent {
+            event_id: start_time,
+            worker: 0,
+            event: TimelineEvent::Merge {
+                operator_id: 3,
+                operator_name: "Arrange".to_owned(),
+            },
+            start_time,
+            duration: 10,
+            collapsed_events: 1,
+            traffic: None,
+            merge,
+        };
+        let batches = |complete| MergeInfo {
+            scale: 2,
+            length1: 4,
+            length2: 3,
+            complete,
+            effort: 5,
+        };
+
+        // Older profiles don't record batches, their merges still take time
+        let unrecorded = SpineSummary::new(&[merge(0, None), merge(20, None)]);
+        assert!(unrecorded.is_empty());
+        assert_eq!(unrecorded.arrangements[0].unrecorded, 2);
+        assert_eq!(unrecorded.arrangements[0].merge_time, 20);
+
+        let events = [
+            merge(0, None),
+            merge(20, Some(batches(Some(6)))),
+            merge(40, Some(batches(None))),
+        ];
+        let spines = SpineSummary::new(&events);
+        let spine = &spines.arrangements[0];
+
+        assert!(!spines.is_empty());
+        assert_eq!((spine.recorded, spine.unrecorded), (2, 1));
+        assert_eq!(spine.merges.len(), 2);
+        assert_eq!(spine.merge_time, 30);
+        // Only completed merges count towards compaction
+        assert_eq!((spine.records_in, spine.records_out), (7, 6));
+        assert_eq!((spine.in_progress, spine.max_scale), (1, 2));
+    }
+}
@@ -0,0 +1,97 @@
+use crate::timeline::utils::draw_rounded_rect;
+use gigatrace::render::{DrawCommand, Renderer, TextAlign};
+use wasm_bindgen::JsValue;
+use web_sys::CanvasRenderingContext2d;
+
+/// Draws timeline draw commands onto a canvas
+#[derive(Debug)]
+pub(super) struct CanvasRenderer<'a> {
+    ctx: &'a CanvasRenderingContext2d,
+    no_line_dash: JsValue,
+}
+
+impl<'a> CanvasRenderer<'a> {
+    pub fn new(ctx: &'a CanvasRenderingContext2d) -> Self {
+        Self {
+            ctx,
+            no_line_dash: JsValue::from_serde(&[] as &[f64]).unwrap(),
+        }
+    }
+}
+
+impl Renderer for CanvasRenderer<'_> {
+    type Error = JsValue;
+
+    fn draw(&mut self, command: &DrawCommand) -> Result<(), JsValue> {
+        let ctx = self.ctx;
+
+        match command {
+            DrawCommand::FillRect {
+                rect,
+                radius,
+                color,
+            } => {
+                ctx.set_fill_style(&JsValue::from_str(&color.to_string()));
+
+                if *radius > 0.0 {
+                    draw_rounded_rect(ctx, rect.x, rect.y, rect.width, rect.height, *radius)?;
+                    ctx.fill();
+                } else {
+                    ctx.fill_rect(rect.x, rect.y, rect.width, rect.height);
+                }
+            }
+
+            DrawCommand::Line {
+                from,
+                to,
+                color,
+                width,
+                dash,
+            } => {
+                ctx.set_stroke_style(&JsValue::from_str(&color.to_string()));
+                ctx.set_line_width(*width);
+
+                match dash {
+                    Some((dash, gap)) => {
+                        ctx.set_line_dash(&JsValue::from_serde(&[*dash, *gap]).unwrap())?
+                    }
+                    None => ctx.set_line_dash(&self.no_line_dash)?,
+                }
+
+                ctx.begin_path();
+                ctx.move_to(from.x, from.y);
+                ctx.line_to(to.x, to.y);
+                ctx.stroke();
+            }
+
+            DrawCommand::Text {
+                text,
+                at,
+                size,
+                color,
+                align,
+                angle,
+            } => {
+                ctx.save();
+                ctx.set_fill_style(&JsValue::from_str(&color.to_string()));
+                ctx.set_font(&format!("{}px sans-serif", size));
+                ctx.set_text_baseline("hanging");
+                ctx.set_text_align(match align {
+                    TextAlign::Start => "start",
+                    TextAlign::Center => "center",
+                    TextAlign::End => "end",
+                });
+
+                ctx.translate(at.x, at.y)?;
+                if *angle != 0.0 {
+                    ctx.rotate(*angle)?;
+                }
+                ctx.fill_text(text, 0.0, 0.0)?;
+
+                ctx.restore();
+            }
+        }
+
+        Ok(())
+    }
+}
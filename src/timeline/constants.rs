@@ -16,6 +16,9 @@ pub const BOX_HEIGHT: f64 = 25.0;
 /// Distance between Y tick marks on the unit graph
 pub const Y_TICK_DIST: f64 = BOX_HEIGHT + 2.0;
 
+/// Height of a lane the user has collapsed
+pub const COLLAPSED_LANE_HEIGHT: f64 = 10.0;
+
 pub const NS_MARGIN: u64 = 500_000;
 
 pub const NS_TO_MS: f64 = 1_000_000.0; // ns->sec = 1_000_000_000.0;
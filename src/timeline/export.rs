@@ -0,0 +1,94 @@
+use crate::{
+    download::{download, download_url, js_error},
+    timeline::{canvas_renderer::CanvasRenderer, constants::X_LINE, Timeline},
+    view::{background, export_svg, timeline_layout},
+};
+use anyhow::{anyhow, bail, Context, Result};
+use gigatrace::render::{Renderer, TimelineLayout};
+use wasm_bindgen::JsCast;
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement};
+
+/// The width of exports until the user picks one, in css pixels
+pub(super) const DEFAULT_EXPORT_WIDTH: u32 = 1600;
+
+/// The narrowest export that still leaves room for the graph
+pub(super) const MIN_EXPORT_WIDTH: u32 = X_LINE as u32 + 100;
+
+/// The device pixel ratios a png can be exported at
+pub(super) const EXPORT_SCALES: [u32; 4] = [1, 2, 3, 4];
+
+/// Browsers refuse to draw canvases larger than this in either dimension
+const MAX_CANVAS_SIZE: f64 = 16_384.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Svg,
+    Png,
+}
+
+impl Timeline {
+    /// Lays out every visible lane at the export width, regardless of the canvas' size
+    fn export_layout(&self) -> TimelineLayout {
+        timeline_layout(self.export_width as f64, self.graph_height)
+    }
+
+    /// Offers the current view as a download named after the profile
+    pub(super) fn export(&self, format: ExportFormat) -> Result<()> {
+        let layout = self.export_layout();
+        let scene = self.scene(&layout);
+        let stem = self.profile.strip_suffix(".json").unwrap_or(&self.profile);
+
+        match format {
+            ExportFormat::Svg => {
+                let svg = export_svg(&layout, &scene.commands);
+                download(&format!("{}.timeline.svg", stem), "image/svg+xml", &svg)
+            }
+
+            ExportFormat::Png => {
+                let scale = self.export_scale as f64;
+                let (width, height) = (
+                    (layout.width * scale).ceil(),
+                    (layout.height * scale).ceil(),
+                );
+                if width.max(height) > MAX_CANVAS_SIZE {
+                    bail!(
+                        "a {}x{} image is larger than browsers can draw, try a smaller width or scale",
+                        width,
+                        height,
+                    );
+                }
+
+                let canvas: HtmlCanvasElement = web_sys::window()
+                    .and_then(|window| window.document())
+                    .context("failed to get the document")?
+                    .create_element("canvas")
+                    .map_err(js_error)?
+                    .dyn_into()
+                    .map_err(|_| anyhow!("created element wasn't a canvas"))?;
+                canvas.set_width(width as u32);
+                canvas.set_height(height as u32);
+
+                let ctx: CanvasRenderingContext2d = canvas
+                    .get_context("2d")
+                    .map_err(js_error)?
+                    .context("the canvas has no 2d context")?
+                    .dyn_into()
+                    .map_err(|_| anyhow!("the canvas' context wasn't a 2d context"))?;
+                ctx.scale(scale, scale).map_err(js_error)?;
+
+                let mut renderer = CanvasRenderer::new(&ctx);
+                renderer
+                    .draw(&background(&layout))
+                    .and_then(|()| renderer.render(&scene.commands))
+                    .map_err(js_error)
+                    .context("failed to draw the timeline")?;
+
+                let url = canvas
+                    .to_data_url_with_type("image/png")
+                    .map_err(js_error)
+                    .context("failed to encode the png")?;
+                download_url(&format!("{}.timeline.png", stem), &url)
+            }
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use crate::data::TimelineEvent;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// The user's arrangement of timeline lanes, lanes are identified by their
+/// `TimelineEvent` so that layouts can be reused across loads of a profile
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct LaneLayout {
+    /// Lanes in the order the user arranged them, lanes that aren't
+    /// listed keep their default order after the listed ones
+    order: Vec<TimelineEvent>,
+    hidden: BTreeSet<TimelineEvent>,
+    pinned: BTreeSet<TimelineEvent>,
+    collapsed: BTreeSet<TimelineEvent>,
+}
+
+impl LaneLayout {
+    /// Returns the indices of `lanes` in display order, pinned lanes come first.
+    /// Hidden lanes are included so that they can still be listed
+    pub fn order(&self, lanes: &[TimelineEvent]) -> Vec<usize> {
+        let mut order: Vec<usize> = self
+            .order
+            .iter()
+            .filter_map(|lane| lanes.iter().position(|other| other == lane))
+            .collect();
+
+        for lane in 0..lanes.len() {
+            if !order.contains(&lane) {
+                order.push(lane);
+            }
+        }
+
+        // Stable, so pinned lanes keep their relative order
+        order.sort_by_key(|&lane| !self.is_pinned(&lanes[lane]));
+        order
+    }
+
+    pub fn is_hidden(&self, lane: &TimelineEvent) -> bool {
+        self.hidden.contains(lane)
+    }
+
+    pub fn is_pinned(&self, lane: &TimelineEvent) -> bool {
+        self.pinned.contains(lane)
+    }
+
+    pub fn is_collapsed(&self, lane: &TimelineEvent) -> bool {
+        self.collapsed.contains(lane)
+    }
+
+    pub fn toggle_hidden(&mut self, lane: &TimelineEvent) {
+        toggle(&mut self.hidden, lane);
+    }
+
+    pub fn toggle_pinned(&mut self, lane: &TimelineEvent) {
+        toggle(&mut self.pinned, lane);
+    }
+
+    pub fn toggle_collapsed(&mut self, lane: &TimelineEvent) {
+        toggle(&mut self.collapsed, lane);
+    }
+
+    /// Moves `lane` so that it's displayed directly before `target`
+    pub fn move_before(&mut self, lanes: &[TimelineEvent], lane: usize, target: usize) {
+        if lane == target {
+            return;
+        }
+
+        let mut order = self.order(lanes);
+        order.retain(|&other| other != lane);
+
+        let position = order
+            .iter()
+            .position(|&other| other == target)
+            .unwrap_or(order.len());
+        order.insert(position, lane);
+
+        self.order = order.into_iter().map(|lane| lanes[lane].clone()).collect();
+    }
+}
+
+fn toggle(set: &mut BTreeSet<TimelineEvent>, lane: &TimelineEvent) {
+    if !set.remove(lane) {
+        set.insert(lane.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LaneLayout;
+    use crate::data::TimelineEvent;
+
+    fn lanes() -> Vec<TimelineEvent> {
+        vec![
+            TimelineEvent::OperatorActivation {
+                operator_id: 2,
+                operator_name: "Join".to_owned(),
+            },
+            TimelineEvent::Progress,
+            TimelineEvent::Message,
+            TimelineEvent::Parked,
+        ]
+    }
+
+    #[test]
+    fn default_order() {
+        assert_eq!(LaneLayout::default().order(&lanes()), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn reorder_and_pin() {
+        let lanes = lanes();
+        let mut layout = LaneLayout::default();
+
+        layout.move_before(&lanes, 3, 1);
+        assert_eq!(layout.order(&lanes), vec![0, 3, 1, 2]);
+
+        layout.toggle_pinned(&lanes[2]);
+        assert_eq!(layout.order(&lanes), vec![2, 0, 3, 1]);
+
+        // Moving past the end appends the lane
+        layout.toggle_pinned(&lanes[2]);
+        layout.move_before(&lanes, 0, lanes.len());
+        assert_eq!(layout.order(&lanes), vec![3, 1, 2, 0]);
+    }
+
+    #[test]
+    fn survives_changed_lanes() {
+        let lanes = lanes();
+        let mut layout = LaneLayout::default();
+        layout.move_before(&lanes, 2, 0);
+        layout.toggle_hidden(&lanes[1]);
+
+        // A profile with different lanes keeps the parts of the layout that still apply
+        let json = serde_json::to_string(&layout).unwrap();
+        let layout: LaneLayout = serde_json::from_str(&json).unwrap();
+        let new_lanes = vec![lanes[3].clone(), lanes[2].clone(), TimelineEvent::Input];
+
+        assert_eq!(layout.order(&new_lanes), vec![1, 0, 2]);
+        assert!(layout.is_hidden(&lanes[1]));
+        assert!(!layout.is_collapsed(&lanes[1]));
+    }
+}
@@ -1,33 +1,78 @@
 mod canvas;
+mod canvas_renderer;
 pub(crate) mod constants;
+mod export;
+mod layout;
 mod properties;
 mod render;
 mod required_lines;
+mod search;
+mod selection;
 mod utils;
 
 use crate::{
-    data::{EventId, TimelineEvent, WorkerTimelineEvent},
+    data::{TimelineEvent, WorkerTimelineEvent},
+    filter::EventFilter,
+    loader::ProfileTrace,
     timeline::{
-        canvas::Canvas, properties::TimelineProps, required_lines::RequiredLines,
+        canvas::Canvas,
+        constants::{COLLAPSED_LANE_HEIGHT, MARGIN, X_LINE, Y_LINE, Y_TICK_DIST},
+        export::{ExportFormat, DEFAULT_EXPORT_WIDTH, EXPORT_SCALES, MIN_EXPORT_WIDTH},
+        layout::LaneLayout,
+        properties::TimelineProps,
+        required_lines::RequiredLines,
+        search::{SearchIndex, SearchQuery, MAX_HITS},
+        selection::SelectionStats,
         utils::calculate_timeline_dimensions,
     },
+    view::{event_tooltip, format_duration},
 };
-use gigatrace::{
-    iforest::IForestIndex,
-    index::TrackIndex,
-    trace::{BlockPool, Nanos, PackedNanos, TraceEvent, Track},
-    Trace, TrackInfo,
-};
-use std::{borrow::Cow, collections::HashMap, ops::Range, rc::Rc, time::Duration};
+use gigatrace::{payload::PayloadId, trace::Nanos, TrackInfo};
+use humantime::Duration as HumanDuration;
+use std::{ops::Range, rc::Rc, time::Duration};
 use wasm_bindgen::JsValue;
-use web_sys::{CanvasRenderingContext2d, MouseEvent};
-use yew::{html, Component, ComponentLink, Html, NodeRef, ShouldRender};
+use web_sys::{CanvasRenderingContext2d, DragEvent, MouseEvent, WheelEvent};
+use yew::{
+    format::Json,
+    html,
+    services::{storage::Area, StorageService},
+    Callback, ChangeData, Component, ComponentLink, Html, InputData, NodeRef, ShouldRender,
+};
+
+/// The narrowest view (in ticks) that jumping to a search hit will zoom to
+const MIN_FOCUS_TICKS: Nanos = 10_000;
+
+/// The narrowest view (in ticks) that zooming in will allow
+const MIN_VIEW_TICKS: f64 = 100.0;
+
+/// How much each pixel scrolled zooms the view by
+const ZOOM_PER_PIXEL: f64 = 1.002;
+
+/// Lane layouts are stored under this key suffixed with the profile's name
+const LANE_LAYOUT_KEY: &str = "differential-dashboard.lane-layout";
 
 #[derive(Debug)]
 pub enum Message {
     RenderTimeline(CanvasRenderingContext2d, f64),
     RenderOverlay(CanvasRenderingContext2d, f64),
     MouseMove(MouseEvent),
+    Wheel(WheelEvent),
+    Search(String),
+    SelectHit(usize),
+    ToggleLaneHidden(usize),
+    ToggleLanePinned(usize),
+    ToggleLaneCollapsed(usize),
+    DragLane(usize),
+    DropLane(usize),
+    ResetLayout,
+    SetExportWidth(String),
+    SetExportScale(String),
+    Export(ExportFormat),
+    SelectStart(MouseEvent),
+    SelectMove(MouseEvent),
+    SelectEnd(MouseEvent),
+    ZoomToSelection,
+    ClearSelection,
     // CutoffPercent { percentage: usize },
 }
 
@@ -41,6 +86,16 @@ pub struct Hitbox {
     tooltip: String,
 }
 
+/// A lane's position on the timeline
+#[derive(Debug, Clone, Copy)]
+struct LaneRow {
+    /// The lane's index within `sorted_events` and the trace's tracks
+    lane: usize,
+    y: f64,
+    height: f64,
+    collapsed: bool,
+}
+
 #[derive(Debug)]
 pub struct Timeline {
     link: ComponentLink<Self>,
@@ -59,25 +114,43 @@ pub struct Timeline {
     canvas_width: f64,
     canvas_height: f64,
 
-    background_color: JsValue,
-    font: Cow<'static, str>,
-    text_align: Cow<'static, str>,
-    x_tick_color: JsValue,
-    vertical_stroke_style: JsValue,
-    vertical_line_dash: JsValue,
     black: JsValue,
-    no_line_dash: JsValue,
-    block_rect_fill: JsValue,
+    selection_fill: JsValue,
 
     hitboxes: Vec<Hitbox>,
     // TODO: Make this a struct
     current_hover: Option<(Hitbox, (i32, i32))>,
 
-    trace: Trace<EventId>,
+    trace: Rc<ProfileTrace>,
+    parks: Rc<[TrackInfo<PayloadId>]>,
     view_range: Range<Nanos>,
-    event_map: HashMap<EventId, WorkerTimelineEvent>,
-    event_cutoff: Nanos,
-    sorted_events: Vec<TimelineEvent>,
+    visible: bool,
+    /// The last view received from or sent to the parent, in nanoseconds since the trace's epoch
+    synced_view: Option<Range<Nanos>>,
+    onviewchange: Callback<Range<Nanos>>,
+    filter: Rc<EventFilter>,
+    sorted_events: Rc<[TimelineEvent]>,
+
+    search_index: SearchIndex,
+    search_hits: Vec<usize>,
+    search_error: Option<String>,
+
+    profile: Rc<str>,
+    layout: LaneLayout,
+    dragged_lane: Option<usize>,
+    storage: Option<StorageService>,
+
+    /// The width of exported images in css pixels
+    export_width: u32,
+    /// The device pixel ratio pngs are exported at
+    export_scale: u32,
+    export_error: Option<String>,
+
+    /// Where the selection being dragged out started, in ticks
+    selection_anchor: Option<Nanos>,
+    /// The selected span, in ticks
+    selection: Option<Range<Nanos>>,
+    selection_stats: Option<SelectionStats>,
 }
 
 impl Timeline {
@@ -96,6 +169,380 @@ impl Timeline {
 
         None
     }
+
+    fn search(&mut self, query: &str) {
+        match SearchQuery::parse(query) {
+            Ok(query) => {
+                self.search_hits = self.search_index.search(&self.events, &query);
+                self.search_error = None;
+            }
+
+            Err(error) => {
+                self.search_hits.clear();
+                self.search_error = Some(format!("{:#}", error));
+            }
+        }
+    }
+
+    /// Moves the view so that it's centered on the given event
+    fn focus_event(&mut self, event_idx: usize) {
+        let event = &self.events[event_idx];
+        let clock = self.trace.clock;
+
+        match clock.pack(event.start_time) {
+            Ok(start) => {
+                let start = start.unpack();
+                let duration = clock.ticks(event.duration).max(1);
+
+                // Leave some room on either side of the event
+                let padding = duration.max(MIN_FOCUS_TICKS) / 2;
+                self.view_range = start.saturating_sub(padding)..start + duration + padding;
+                self.view_changed();
+            }
+
+            Err(error) => tracing::warn!("failed to focus event {}: {}", event.event_id, error),
+        }
+    }
+
+    /// Zooms around the cursor, or pans when shift is held
+    fn scroll(&mut self, event: &WheelEvent) {
+        let bounds = match self.trace.time_bounds() {
+            Some(bounds) if self.graph_width > 0.0 => bounds,
+            _ => return,
+        };
+
+        let start = self.view_range.start as f64;
+        let width = (self.view_range.end - self.view_range.start) as f64;
+
+        let (start, width) = if event.shift_key() {
+            // Trackpads scroll horizontally, mice only have a vertical wheel
+            let delta = if event.delta_x() != 0.0 {
+                event.delta_x()
+            } else {
+                event.delta_y()
+            };
+
+            (start + delta / self.graph_width * width, width)
+        } else {
+            // Keep the time under the cursor in place
+            let anchor = ((event.offset_x() as f64 - X_LINE) / self.graph_width).clamp(0.0, 1.0);
+            let zoomed = (width * ZOOM_PER_PIXEL.powf(event.delta_y())).max(MIN_VIEW_TICKS);
+
+            (start + (width - zoomed) * anchor, zoomed)
+        };
+
+        let (min, max) = (bounds.start as f64, bounds.end as f64);
+        let width = width.min(max - min).max(1.0);
+        let start = start.clamp(min, (max - width).max(min));
+
+        self.view_range = start as Nanos..(start + width) as Nanos;
+        self.view_changed();
+    }
+
+    /// The time in ticks under a point on the canvas, clamped to the view
+    fn time_at(&self, x: f64) -> Nanos {
+        let fraction = ((x - X_LINE) / self.graph_width).clamp(0.0, 1.0);
+        let width = (self.view_range.end - self.view_range.start) as f64;
+
+        self.view_range.start + (fraction * width) as Nanos
+    }
+
+    /// Extends the selection being dragged out to the cursor
+    fn drag_selection(&mut self, event: &MouseEvent) {
+        if let Some(anchor) = self.selection_anchor {
+            let time = self.time_at(event.offset_x() as f64);
+            self.selection = Some(anchor.min(time)..anchor.max(time));
+        }
+    }
+
+    fn clear_selection(&mut self) {
+        self.selection_anchor = None;
+        self.selection = None;
+        self.selection_stats = None;
+    }
+
+    /// Tells the parent about a view change made by the user
+    fn view_changed(&mut self) {
+        let clock = self.trace.clock;
+        let view = clock.elapsed(self.view_range.start)..clock.elapsed(self.view_range.end);
+
+        self.synced_view = Some(view.clone());
+        self.onviewchange.emit(view);
+    }
+
+    /// Moves the view to one set by the parent
+    fn sync_view(&mut self, view: &Range<Nanos>) {
+        let clock = self.trace.clock;
+        let start = clock.ticks(view.start);
+
+        self.view_range = start..clock.ticks(view.end).max(start + 1);
+    }
+
+    /// The visible lanes in the order they're displayed
+    fn lane_rows(&self) -> Vec<LaneRow> {
+        let mut y = 0.0;
+
+        self.layout
+            .order(&self.sorted_events)
+            .into_iter()
+            .filter(|&lane| {
+                let event = &self.sorted_events[lane];
+                self.required_lines.contains(event) && !self.layout.is_hidden(event)
+            })
+            .map(|lane| {
+                let collapsed = self.layout.is_collapsed(&self.sorted_events[lane]);
+                let height = if collapsed {
+                    COLLAPSED_LANE_HEIGHT
+                } else {
+                    Y_TICK_DIST
+                };
+
+                let row = LaneRow {
+                    lane,
+                    y,
+                    height,
+                    collapsed,
+                };
+                y += height;
+
+                row
+            })
+            .collect()
+    }
+
+    /// Fits the graph's height to the visible lanes
+    fn resize_graph(&mut self) {
+        self.graph_height = self
+            .lane_rows()
+            .last()
+            .map_or(0.0, |row| row.y + row.height);
+        self.canvas_height = self.graph_height + MARGIN + Y_LINE;
+    }
+
+    fn load_layout(&self) -> LaneLayout {
+        self.storage
+            .as_ref()
+            .and_then(|storage| {
+                let Json(layout) = storage.restore(&layout_key(&self.profile));
+                layout.ok()
+            })
+            .unwrap_or_default()
+    }
+
+    fn update_layout<F>(&mut self, update: F)
+    where
+        F: FnOnce(&mut LaneLayout, &[TimelineEvent]),
+    {
+        update(&mut self.layout, &self.sorted_events);
+
+        if let Some(storage) = self.storage.as_mut() {
+            storage.store(&layout_key(&self.profile), Json(&self.layout));
+        }
+
+        self.resize_graph();
+    }
+
+    fn view_lanes(&self) -> Html {
+        let lanes = self
+            .layout
+            .order(&self.sorted_events)
+            .into_iter()
+            .filter(|&lane| self.required_lines.contains(&self.sorted_events[lane]))
+            .map(|lane| {
+                let event = &self.sorted_events[lane];
+                let (hidden, pinned, collapsed) = (
+                    self.layout.is_hidden(event),
+                    self.layout.is_pinned(event),
+                    self.layout.is_collapsed(event),
+                );
+
+                html! {
+                    <li
+                        draggable="true"
+                        class=if pinned { "pinned" } else { "" }
+                        ondragstart=self.link.callback(move |event: DragEvent| {
+                            // Firefox won't start dragging without some data
+                            if let Some(data) = event.data_transfer() {
+                                let _ = data.set_data("text/plain", "");
+                            }
+
+                            Message::DragLane(lane)
+                        })
+                        ondragover=self.link.batch_callback(|event: DragEvent| {
+                            // Allows dropping onto the lane
+                            event.prevent_default();
+                            Vec::new()
+                        })
+                        ondrop=self.link.callback(move |event: DragEvent| {
+                            event.prevent_default();
+                            Message::DropLane(lane)
+                        })
+                    >
+                        <input
+                            type="checkbox"
+                            title="Show lane"
+                            checked=!hidden
+                            onclick=self.link.callback(move |_| Message::ToggleLaneHidden(lane))
+                        />
+                        <button onclick=self.link.callback(move |_| Message::ToggleLanePinned(lane))>
+                            { if pinned { "Unpin" } else { "Pin" } }
+                        </button>
+                        <button onclick=self.link.callback(move |_| Message::ToggleLaneCollapsed(lane))>
+                            { if collapsed { "Expand" } else { "Collapse" } }
+                        </button>
+                        { event }
+                    </li>
+                }
+            });
+
+        html! {
+            <details id="timeline-lanes">
+                <summary>{ "Lanes" }</summary>
+                <button onclick=self.link.callback(|_| Message::ResetLayout)>{ "Reset layout" }</button>
+                <ul>{ for lanes }</ul>
+            </details>
+        }
+    }
+
+    fn view_export(&self) -> Html {
+        let scales = EXPORT_SCALES.iter().map(|&scale| {
+            html! {
+                <option value=scale.to_string() selected=scale == self.export_scale>
+                    { format!("{}x", scale) }
+                </option>
+            }
+        });
+
+        let error = match self.export_error.as_ref() {
+            Some(error) => html! { <p class="export-error">{ error }</p> },
+            None => html! {},
+        };
+
+        html! {
+            <details id="timeline-export">
+                <summary>{ "Export" }</summary>
+                <label>
+                    { "Width " }
+                    <input
+                        type="number"
+                        min=MIN_EXPORT_WIDTH.to_string()
+                        value=self.export_width.to_string()
+                        onchange=self.link.batch_callback(|change| match change {
+                            ChangeData::Value(width) => vec![Message::SetExportWidth(width)],
+                            _ => Vec::new(),
+                        })
+                    />
+                </label>
+                <label>
+                    { "PNG scale " }
+                    <select onchange=self.link.batch_callback(|change| match change {
+                        ChangeData::Select(select) => vec![Message::SetExportScale(select.value())],
+                        _ => Vec::new(),
+                    })>
+                        { for scales }
+                    </select>
+                </label>
+                <button onclick=self.link.callback(|_| Message::Export(ExportFormat::Svg))>{ "Export SVG" }</button>
+                <button onclick=self.link.callback(|_| Message::Export(ExportFormat::Png))>{ "Export PNG" }</button>
+                { error }
+            </details>
+        }
+    }
+
+    fn view_selection(&self) -> Html {
+        let (selection, stats) = match (self.selection.as_ref(), self.selection_stats.as_ref()) {
+            (Some(selection), Some(stats)) => (selection, stats),
+            _ => return html! {},
+        };
+        let clock = self.trace.clock;
+
+        let lanes = stats.lanes.iter().map(|lane| {
+            html! {
+                <tr>
+                    <td>{ &self.sorted_events[lane.lane] }</td>
+                    <td>{ lane.count }</td>
+                    <td>{ format_duration(lane.busy) }</td>
+                </tr>
+            }
+        });
+
+        let workers = stats
+            .utilization
+            .iter()
+            .enumerate()
+            .map(|(worker, utilization)| {
+                html! {
+                    <li>
+                        { format!("Worker {} ", worker) }
+                        <meter value=utilization.to_string()></meter>
+                        { format!(" {:.1}%", utilization * 100.0) }
+                    </li>
+                }
+            });
+
+        let longest = stats.longest.iter().map(|event| {
+            let worker = self.trace.payloads.worker(event.kind);
+            html! { <li>{ format!("{} on worker {}", event_tooltip(&self.trace, event), worker) }</li> }
+        });
+
+        html! {
+            <div id="timeline-selection">
+                <p>
+                    { format!(
+                        "{} events start within the selected {}",
+                        stats.count(),
+                        format_duration(clock.elapsed(selection.end - selection.start)),
+                    ) }
+                </p>
+                <button onclick=self.link.callback(|_| Message::ZoomToSelection)>{ "Zoom to selection" }</button>
+                <button onclick=self.link.callback(|_| Message::ClearSelection)>{ "Clear selection" }</button>
+
+                <table class="selection-lanes">
+                    <thead><tr><th>{ "Lane" }</th><th>{ "Events" }</th><th>{ "Busy" }</th></tr></thead>
+                    <tbody>{ for lanes }</tbody>
+                </table>
+
+                <p>{ "Worker utilization" }</p>
+                <ul class="selection-workers">{ for workers }</ul>
+
+                <p>{ "Longest events" }</p>
+                <ol class="selection-longest">{ for longest }</ol>
+            </div>
+        }
+    }
+
+    fn view_search(&self) -> Html {
+        let hits = self.search_hits.iter().map(|&idx| {
+            let event = &self.events[idx];
+            let duration = HumanDuration::from(Duration::from_nanos(event.duration));
+
+            html! {
+                <li onclick=self.link.callback(move |_| Message::SelectHit(idx))>
+                    { format!("{} on worker {} ran for {}", event.event, event.worker, duration) }
+                </li>
+            }
+        });
+
+        let summary = if let Some(error) = self.search_error.as_ref() {
+            html! { <p class="search-error">{ error }</p> }
+        } else if self.search_hits.len() == MAX_HITS {
+            html! { <p>{ format!("Showing the first {} matches", MAX_HITS) }</p> }
+        } else {
+            html! {}
+        };
+
+        html! {
+            <div id="timeline-search">
+                <input
+                    type="search"
+                    placeholder="Search events, e.g. name:join dur>5ms worker:2"
+                    oninput=self.link.callback(|input: InputData| Message::Search(input.value))
+                />
+                { summary }
+                <ul class="search-results">{ for hits }</ul>
+            </div>
+        }
+    }
 }
 
 impl Component for Timeline {
@@ -119,20 +566,12 @@ impl Component for Timeline {
         let window = web_sys::window().unwrap();
         let dpr = window.device_pixel_ratio();
 
-        let trace = build_trace(&*properties.events, &required_lines);
+        let sorted_events = properties.lanes.clone();
+        let trace = properties.trace.clone();
         let view_range = trace.time_bounds().unwrap_or(0..1000);
-        let event_map = build_event_map(&*properties.events);
+        let search_index = SearchIndex::new(&*properties.events);
 
-        let mut sorted_events: Vec<_> = properties
-            .events
-            .iter()
-            .map(|event| event.event.clone())
-            .collect();
-        sorted_events.sort_unstable();
-        sorted_events.dedup();
-        sorted_events.reverse();
-
-        Self {
+        let mut timeline = Self {
             link,
             events: properties.events,
 
@@ -149,31 +588,55 @@ impl Component for Timeline {
             canvas_width,
             canvas_height,
 
-            background_color: JsValue::from_str("#F7F7F7"),
-            font: "16px sans-serif".into(),
-            text_align: "center".into(),
-            x_tick_color: JsValue::from_str("#303030"),
-            vertical_stroke_style: JsValue::from_str("#E6E6E6"),
-            vertical_line_dash: JsValue::from_serde(&[2.0f64, 4.0]).unwrap(),
             black: JsValue::from_str("#000"),
-            no_line_dash: JsValue::from_serde(&[] as &[f64]).unwrap(),
-            block_rect_fill: JsValue::from_str("#95CCE8"),
+            selection_fill: JsValue::from_str("rgba(66, 133, 244, 0.2)"),
 
             hitboxes: Vec::new(),
             current_hover: None,
 
             trace,
+            parks: properties.parks,
             view_range,
-            event_map,
-            event_cutoff: Duration::from_millis(properties.event_cutoff).as_nanos() as Nanos,
+            visible: properties.visible,
+            synced_view: None,
+            onviewchange: properties.onviewchange,
+            filter: properties.filter,
             sorted_events,
+
+            search_index,
+            search_hits: Vec::new(),
+            search_error: None,
+
+            profile: properties.profile,
+            layout: LaneLayout::default(),
+            dragged_lane: None,
+            storage: StorageService::new(Area::Local).ok(),
+
+            export_width: DEFAULT_EXPORT_WIDTH,
+            export_scale: 2,
+            export_error: None,
+
+            selection_anchor: None,
+            selection: None,
+            selection_stats: None,
+        };
+
+        timeline.layout = timeline.load_layout();
+        timeline.resize_graph();
+        if let Some(view) = properties.view {
+            timeline.sync_view(&view);
+            timeline.synced_view = Some(view);
         }
+        timeline
     }
 
     fn update(&mut self, message: Self::Message) -> ShouldRender {
         tracing::trace!("updating timeline");
 
         match message {
+            // Hidden timelines don't need to be drawn
+            Message::RenderTimeline(..) | Message::RenderOverlay(..) if !self.visible => {}
+
             Message::RenderTimeline(ref context, timestamp) => {
                 //tracing::debug!("rendering timeline");
                 self.render_timeline(context, timestamp);
@@ -188,6 +651,115 @@ impl Component for Timeline {
                 tracing::debug!("mouse move event");
                 self.current_hover = self.mouse_collision(&event);
             }
+
+            Message::Wheel(event) => self.scroll(&event),
+
+            Message::Search(query) => {
+                self.search(&query);
+                return true;
+            }
+
+            Message::SelectHit(event_idx) => self.focus_event(event_idx),
+
+            Message::ToggleLaneHidden(lane) => {
+                self.update_layout(|layout, lanes| layout.toggle_hidden(&lanes[lane]));
+                return true;
+            }
+
+            Message::ToggleLanePinned(lane) => {
+                self.update_layout(|layout, lanes| layout.toggle_pinned(&lanes[lane]));
+                return true;
+            }
+
+            Message::ToggleLaneCollapsed(lane) => {
+                self.update_layout(|layout, lanes| layout.toggle_collapsed(&lanes[lane]));
+                return true;
+            }
+
+            Message::DragLane(lane) => self.dragged_lane = Some(lane),
+
+            Message::DropLane(target) => {
+                if let Some(lane) = self.dragged_lane.take() {
+                    self.update_layout(|layout, lanes| layout.move_before(lanes, lane, target));
+                    return true;
+                }
+            }
+
+            Message::ResetLayout => {
+                self.update_layout(|layout, _| *layout = LaneLayout::default());
+                return true;
+            }
+
+            Message::SetExportWidth(width) => {
+                match width.trim().parse::<u32>() {
+                    Ok(width) if width >= MIN_EXPORT_WIDTH => {
+                        self.export_width = width;
+                        self.export_error = None;
+                    }
+                    _ => {
+                        self.export_error = Some(format!(
+                            "The width must be a whole number of at least {} pixels",
+                            MIN_EXPORT_WIDTH,
+                        ));
+                    }
+                }
+
+                return true;
+            }
+
+            Message::SetExportScale(scale) => {
+                if let Ok(scale) = scale.parse() {
+                    self.export_scale = scale;
+                }
+            }
+
+            Message::SelectStart(event) => {
+                let time = self.time_at(event.offset_x() as f64);
+                self.selection_anchor = Some(time);
+                self.selection = Some(time..time);
+                self.selection_stats = None;
+
+                // Installs the handlers that follow the drag
+                return true;
+            }
+
+            Message::SelectMove(event) => self.drag_selection(&event),
+
+            Message::SelectEnd(event) => {
+                self.drag_selection(&event);
+                self.selection_anchor = None;
+
+                match self.selection.clone() {
+                    Some(selection) if selection.start < selection.end => {
+                        self.selection_stats =
+                            Some(SelectionStats::new(&self.trace, &self.parks, selection));
+                    }
+                    _ => self.clear_selection(),
+                }
+
+                return true;
+            }
+
+            Message::ZoomToSelection => {
+                if let Some(selection) = self.selection.clone() {
+                    self.view_range = selection;
+                    self.view_changed();
+                }
+            }
+
+            Message::ClearSelection => {
+                self.clear_selection();
+                return true;
+            }
+
+            Message::Export(format) => {
+                self.export_error = self
+                    .export(format)
+                    .err()
+                    .map(|error| format!("Failed to export the timeline: {:#}", error));
+
+                return true;
+            }
         }
 
         false
@@ -211,22 +783,18 @@ impl Component for Timeline {
             required_lines,
         ) = calculate_timeline_dimensions(&properties);
 
-        // TODO: Buffer these
-        self.trace = build_trace(&*properties.events, &required_lines);
-        self.event_map = build_event_map(&*properties.events);
-        // TODO: Attempt to preserve view range?
-        self.view_range = self.trace.time_bounds().unwrap_or(0..1000);
-
-        let mut sorted_events: Vec<_> = properties
-            .events
-            .iter()
-            .map(|event| event.event.clone())
-            .collect();
-        sorted_events.sort_unstable();
-        sorted_events.dedup();
-        sorted_events.reverse();
-
-        self.sorted_events = sorted_events;
+        // Filter changes don't require rebuilding the trace
+        let events_changed = !Rc::ptr_eq(&self.events, &properties.events);
+        if events_changed {
+            self.sorted_events = properties.lanes.clone();
+            self.trace = properties.trace.clone();
+            self.parks = properties.parks.clone();
+            self.clear_selection();
+            self.search_index = SearchIndex::new(&*properties.events);
+            self.search_hits.clear();
+            // TODO: Attempt to preserve view range?
+            self.view_range = self.trace.time_bounds().unwrap_or(0..1000);
+        }
 
         self.scale = scale;
         self.duration = duration;
@@ -237,7 +805,22 @@ impl Component for Timeline {
         self.canvas_height = canvas_height;
         self.required_lines = required_lines;
         self.events = properties.events;
-        self.event_cutoff = Duration::from_millis(properties.event_cutoff).as_nanos() as Nanos;
+        self.filter = properties.filter;
+        self.visible = properties.visible;
+        self.onviewchange = properties.onviewchange;
+
+        if let Some(view) = properties.view.as_ref() {
+            if events_changed || self.synced_view.as_ref() != Some(view) {
+                self.sync_view(view);
+            }
+        }
+        self.synced_view = properties.view;
+
+        if self.profile != properties.profile {
+            self.profile = properties.profile;
+            self.layout = self.load_layout();
+        }
+        self.resize_graph();
 
         tracing::info!(?self.trace);
 
@@ -265,34 +848,79 @@ impl Component for Timeline {
             })
         };
 
-        html! {
-            <div id="timeline" ref=self.graph_div.clone()> // onmousemove=self.link.callback(Message::MouseMove)
-                // TODO: Allow configuring the cutoff percent of events
-                // <input
-                //     type="range"
-                //     min="0"
-                //     max="100"
-                //     value="10"
-                //     id="timeline-cutoff-percentage"
-                //     onchange=self.link.callback(|value| Message::CutoffPercent)
-                // />
-
-                <Canvas
-                    id={ Rc::from("timeline-canvas") }
-                    class={ Rc::from("canvas-layer") }
-                    width={ self.canvas_width.ceil() as u32 }
-                    height={ self.canvas_height.ceil() as u32 }
-                    render={ render_timeline }
-                />
+        let selecting = self.selection_anchor.is_some();
 
-                <Canvas
-                    id={ Rc::from("timeline-overlay-canvas") }
-                    class={ Rc::from("canvas-layer") }
-                    width={ self.canvas_width.ceil() as u32 }
-                    height={ self.canvas_height.ceil() as u32 }
-                    render={ render_overlay }
-                />
-            </div>
+        html! {
+            <>
+                { self.view_search() }
+                { self.view_lanes() }
+                { self.view_export() }
+                { self.view_selection() }
+
+                <div
+                    id="timeline"
+                    ref=self.graph_div.clone()
+                    // onmousemove=self.link.callback(Message::MouseMove)
+                    onmousedown=self.link.batch_callback(|event: MouseEvent| {
+                        // Shift-dragging selects a span of time
+                        if event.shift_key() {
+                            event.prevent_default();
+                            vec![Message::SelectStart(event)]
+                        } else {
+                            Vec::new()
+                        }
+                    })
+                    onmousemove=self.link.batch_callback(move |event| {
+                        if selecting {
+                            vec![Message::SelectMove(event)]
+                        } else {
+                            Vec::new()
+                        }
+                    })
+                    onmouseup=self.link.batch_callback(move |event| {
+                        if selecting {
+                            vec![Message::SelectEnd(event)]
+                        } else {
+                            Vec::new()
+                        }
+                    })
+                    onwheel=self.link.batch_callback(|event: WheelEvent| {
+                        // Plain scrolling is left to the page
+                        if event.ctrl_key() || event.shift_key() {
+                            event.prevent_default();
+                            vec![Message::Wheel(event)]
+                        } else {
+                            Vec::new()
+                        }
+                    })
+                >
+                    // TODO: Allow configuring the cutoff percent of events
+                    // <input
+                    //     type="range"
+                    //     min="0"
+                    //     max="100"
+                    //     value="10"
+                    //     id="timeline-cutoff-percentage"
+                    //     onchange=self.link.callback(|value| Message::CutoffPercent)
+                    // />
+
+                    <Canvas
+                        id={ Rc::from("timeline-canvas") }
+                        class={ Rc::from("canvas-layer") }
+                        width={ self.canvas_width.ceil() as u32 }
+                        height={ self.canvas_height.ceil() as u32 }
+                        render={ render_timeline }
+                    />
+
+                    <Canvas
+                        id={ Rc::from("timeline-overlay-canvas") }
+                        class={ Rc::from("canvas-layer") }
+                        width={ self.canvas_width.ceil() as u32 }
+                        height={ self.canvas_height.ceil() as u32 }
+                        render={ render_overlay }
+                    />
+                </div>
+            </>
         }
     }
 
@@ -302,43 +930,6 @@ impl Component for Timeline {
     }
 }
 
-fn build_trace(events: &[WorkerTimelineEvent], required_lines: &RequiredLines) -> Trace<EventId> {
-    let mut pool = BlockPool::new();
-    let mut event_tracks: HashMap<_, _> = required_lines
-        .events()
-        .map(|event| (event, Track::new()))
-        .collect();
-
-    for event in events {
-        let track = event_tracks.get_mut(&event.event).unwrap();
-
-        track.push(
-            &mut pool,
-            TraceEvent {
-                kind: event.event_id,
-                timestamp: PackedNanos::new(event.start_time),
-                duration: PackedNanos::new(event.duration),
-            },
-        );
-    }
-
-    let mut event_tracks = event_tracks.into_iter().collect::<Vec<_>>();
-    event_tracks.sort_unstable_by_key(|&(idx, _)| idx);
-
-    let mut tracks = Vec::with_capacity(event_tracks.len());
-    for (_event, track) in event_tracks {
-        let index = IForestIndex::build(&track, &pool);
-        tracks.push(TrackInfo::new(track, index));
-    }
-
-    let trace = Trace { pool, tracks };
-    tracing::debug!(trace = ?trace);
-    trace
-}
-
-fn build_event_map(events: &[WorkerTimelineEvent]) -> HashMap<EventId, WorkerTimelineEvent> {
-    events
-        .iter()
-        .map(|event| (event.event_id, event.clone()))
-        .collect()
+fn layout_key(profile: &str) -> String {
+    format!("{}.{}", LANE_LAYOUT_KEY, profile)
 }
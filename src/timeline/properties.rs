@@ -1,11 +1,34 @@
-use crate::data::WorkerTimelineEvent;
-use std::rc::Rc;
-use yew::Properties;
+use crate::{
+    data::{TimelineEvent, WorkerTimelineEvent},
+    filter::EventFilter,
+    loader::ProfileTrace,
+};
+use gigatrace::{payload::PayloadId, TrackInfo};
+use std::{ops::Range, rc::Rc};
+use yew::{Callback, Properties};
 
 #[derive(Debug, Clone, Properties)]
 pub struct TimelineProps {
+    /// The name of the loaded profile, lane layouts are saved per profile
+    pub profile: Rc<str>,
     pub events: Rc<[WorkerTimelineEvent]>,
+    /// Every distinct lane, `trace` has a track for each of them
+    pub lanes: Rc<[TimelineEvent]>,
+    pub trace: Rc<ProfileTrace>,
+    /// Every worker's parks, for working out how busy they were
+    pub parks: Rc<[TrackInfo<PayloadId>]>,
     pub duration: f64,
     pub scale: f64,
-    pub event_cutoff: u64,
+    pub filter: Rc<EventFilter>,
+    /// Hidden timelines keep their state but skip rendering
+    #[prop_or(true)]
+    pub visible: bool,
+    /// Overrides the visible time range, in nanoseconds since the profile's first event.
+    /// Used to keep stacked timelines' time axes in sync
+    #[prop_or_default]
+    pub view: Option<Range<u64>>,
+    /// Called whenever the user zooms or pans the timeline, with the
+    /// new view in nanoseconds since the profile's first event
+    #[prop_or_default]
+    pub onviewchange: Callback<Range<u64>>,
 }
@@ -1,14 +1,17 @@
 use crate::{
-    data::TimelineEvent,
     timeline::{
-        constants::{BOX_HEIGHT, MARGIN, RADIUS, X_LINE, Y_LINE, Y_TICK_DIST},
+        canvas_renderer::CanvasRenderer,
+        constants::{BOX_HEIGHT, MARGIN, RADIUS, X_LINE},
         utils::draw_rounded_rect,
         Hitbox, Timeline,
     },
+    view::{event_tooltip, format_duration},
+};
+use gigatrace::{
+    payload::PayloadId,
+    render::{LaneRow as TrackRow, Rect, Renderer, Scene, TimelineLayout, TimelineStyle},
+    trace::{Nanos, TraceEvent},
 };
-use gigatrace::trace::Nanos;
-use humantime::Duration as HumanDuration;
-use std::{convert::identity, f64::consts::PI, ops::Range, time::Duration};
 use web_sys::{CanvasRenderingContext2d, HtmlDivElement};
 
 impl Timeline {
@@ -24,103 +27,28 @@ impl Timeline {
             return;
         }
 
-        // ctx.save();
-        // ctx.set_fill_style(&self.background_color);
-        // ctx.fill_rect(0.0, 0.0, self.canvas_width, self.canvas_height);
-        // ctx.restore();
-
-        let view = ViewMap::new(&self.view_range, self.graph_width);
-        let quant = ViewQuant::new(&self.view_range, self.graph_width);
-
-        // Draw the graph's x axis
-        self.draw_x_axis(ctx, &view, &quant);
-
-        // Draw the graph's y axis
-        let max_idx = self.required_lines.required_lines().saturating_sub(1);
-        for event in self.required_lines.events() {
-            let idx = self.sorted_events.iter().position(|x| x == event).unwrap();
-            self.draw_y_line(event, idx, max_idx, ctx);
-        }
-
-        ctx.save();
-        ctx.translate(X_LINE, MARGIN).unwrap();
-
         tracing::debug!(
             view_range = ?self.view_range,
-            quantized_range = ?quant.quantize(&self.view_range),
-            time_step = quant.time_step,
             time_bounds = ?self.trace.time_bounds(),
         );
 
-        for track in self.trace.tracks.iter() {
-            // TODO: Buffer this
-            let visible_events = gigatrace::aggregate_by_steps(
-                &self.trace.pool,
-                &track.track.block_locations,
-                &track.zoom_index,
-                quant.quantize(&self.view_range),
-                quant.time_step,
-                |event| {
-                    event
-                        .0
-                        .map(|event| event.duration.unpack() >= self.event_cutoff)
-                        .unwrap_or_default()
-                },
-            );
-
-            for event in visible_events.iter().filter_map(|event| event.0) {
-                let timeline_event = self.event_map.get(&event.kind).unwrap();
-                let track_idx = self
-                    .sorted_events
-                    .iter()
-                    .position(|x| x == &timeline_event.event)
-                    .unwrap();
-
-                let timestamp = event.timestamp.unpack();
-                let duration = event.duration.unpack();
-
-                let (start, width) = if duration > quant.time_step {
-                    (timestamp, duration)
-                } else {
-                    (quant.round_down(timestamp), quant.time_step)
-                };
-                let (start, width) = (view.to_x(start), view.to_x(width));
+        let scene = self.scene(&self.timeline_layout());
 
-                let y = track_idx as f64 * Y_TICK_DIST + 1.0;
-                let height = BOX_HEIGHT;
-
-                ctx.begin_path();
-                ctx.set_fill_style(&self.block_rect_fill);
-                draw_rounded_rect(ctx, start, y, width, height, RADIUS).unwrap();
-                ctx.fill();
-
-                ctx.set_fill_style(&self.black);
-                ctx.set_text_align("start");
-                ctx.set_text_baseline("hanging");
-                ctx.set_font("14px sans-serif");
-
-                // TODO: Format buffer
-                let label = format!(
-                    "{}",
-                    HumanDuration::from(Duration::from_nanos(event.duration.unpack())),
-                );
-                let text_info = ctx.measure_text(&label).unwrap();
-                let label_x = (start + 5.0).min(self.canvas_width - text_info.width() - X_LINE);
-
-                ctx.fill_text(&label, label_x, y + height / 2.0 - 5.0)
-                    .unwrap();
-
-                self.hitboxes.push(Hitbox {
-                    x: start + X_LINE,
-                    y: y + MARGIN,
-                    width,
-                    height,
-                    tooltip: format!("{} ran for {}", &timeline_event.event, &label),
-                });
-            }
+        if let Err(error) = CanvasRenderer::new(ctx).render(&scene.commands) {
+            tracing::error!("failed to render the timeline: {:?}", error);
         }
 
-        ctx.restore();
+        self.hitboxes = scene
+            .events
+            .iter()
+            .map(|event| Hitbox {
+                x: event.rect.x,
+                y: event.rect.y,
+                width: event.rect.width,
+                height: event.rect.height,
+                tooltip: event_tooltip(&self.trace, &event.event),
+            })
+            .collect();
 
         tracing::trace!(
             timestamp = %timestamp,
@@ -128,6 +56,60 @@ impl Timeline {
         );
     }
 
+    /// Lays out the current view of the visible lanes, skipping filtered out events
+    pub(super) fn scene(&self, layout: &TimelineLayout) -> Scene<PayloadId> {
+        let clock = self.trace.clock;
+        let matches_span = |event: &TraceEvent<PayloadId>| {
+            self.filter.matches_span(
+                self.trace.payloads.worker(event.kind) as usize,
+                clock.absolute(event.timestamp.unpack()),
+                clock.unpack_duration(event.duration),
+            )
+        };
+
+        // A minimum duration can be checked against each step's longest event
+        layout.layout(
+            &self.trace,
+            &self.track_rows(),
+            &self.view_range,
+            |event| {
+                self.filter
+                    .min_duration
+                    .is_none_or(|min| clock.unpack_duration(event.duration) >= min)
+            },
+            if self.filter.filters_each_event() {
+                Some(&matches_span)
+            } else {
+                None
+            },
+            |event| format_duration(clock.unpack_duration(event.duration)),
+        )
+    }
+
+    /// Where the timeline's parts are drawn on the canvas
+    pub(super) fn timeline_layout(&self) -> TimelineLayout {
+        TimelineLayout {
+            width: self.canvas_width,
+            height: self.canvas_height,
+            graph: Rect::new(X_LINE, MARGIN, self.graph_width, self.graph_height),
+            style: TimelineStyle::default(),
+        }
+    }
+
+    /// The visible lanes along with their labels
+    pub(super) fn track_rows(&self) -> Vec<TrackRow> {
+        self.lane_rows()
+            .into_iter()
+            .map(|row| TrackRow {
+                track: row.lane,
+                y: row.y,
+                height: row.height,
+                label: self.sorted_events[row.lane].to_string(),
+                collapsed: row.collapsed,
+            })
+            .collect()
+    }
+
     pub(super) fn render_overlay(&self, ctx: &CanvasRenderingContext2d, timestamp: f64) {
         tracing::trace!(
             timestamp = %timestamp,
@@ -137,6 +119,18 @@ impl Timeline {
 
         ctx.clear_rect(0.0, 0.0, self.canvas_width, self.canvas_height);
 
+        if let Some(selection) = self.selection.as_ref() {
+            let width = (self.view_range.end - self.view_range.start).max(1) as f64;
+            let to_x = |time: Nanos| {
+                let fraction = (time as f64 - self.view_range.start as f64) / width;
+                X_LINE + fraction.clamp(0.0, 1.0) * self.graph_width
+            };
+            let (start, end) = (to_x(selection.start), to_x(selection.end));
+
+            ctx.set_fill_style(&self.selection_fill);
+            ctx.fill_rect(start, MARGIN, (end - start).max(1.0), self.graph_height);
+        }
+
         if let Some(&(ref hitbox, (x, y))) = self.current_hover.as_ref() {
             ctx.save();
             ctx.begin_path();
@@ -172,78 +166,6 @@ impl Timeline {
         );
     }
 
-    fn draw_x_axis(&self, ctx: &CanvasRenderingContext2d, view: &ViewMap, quant: &ViewQuant) {
-        ctx.set_fill_style(&self.background_color);
-        ctx.fill_rect(0.0, 0.0, self.canvas_width, self.canvas_height);
-
-        ctx.set_line_width(2.0);
-        ctx.set_font(&*self.font);
-        ctx.set_text_align(&*self.text_align);
-
-        // Draw main axes
-        ctx.begin_path();
-        ctx.move_to(X_LINE, MARGIN);
-        ctx.line_to(X_LINE, self.graph_height + MARGIN);
-        ctx.line_to(X_LINE + self.graph_width + 20.0, self.graph_height + MARGIN);
-        ctx.stroke();
-
-        // Draw the x tick marks
-        ctx.set_fill_style(&self.x_tick_color);
-
-        ctx.begin_path();
-        for time in self
-            .view_range
-            .clone()
-            .step_by(quant.time_step as usize * 25)
-            .skip(1)
-        {
-            let x = X_LINE + view.to_x(time);
-
-            ctx.move_to(x, self.canvas_height - Y_LINE);
-            ctx.line_to(x, self.canvas_height - Y_LINE + 7.0);
-
-            ctx.save();
-            ctx.translate(x, self.canvas_height - Y_LINE + 20.0)
-                .unwrap();
-
-            // Rotate the text so it doesn't overlap
-            ctx.rotate(PI / 4.0).unwrap();
-
-            // Remove trailing nanoseconds
-            let time = Duration::from_nanos(time);
-            // Totally not a hack, I swear
-            let time = (time - Duration::from_nanos(time.subsec_nanos() as u64))
-                + Duration::from_micros(time.subsec_micros() as u64);
-
-            ctx.set_text_align("start");
-            ctx.fill_text(
-                // TODO: Use a format buffer
-                &format!("{}", HumanDuration::from(time)),
-                0.0,
-                0.0,
-            )
-            .unwrap();
-
-            ctx.restore();
-        }
-        ctx.stroke();
-
-        // Draw vertical lines
-        ctx.set_stroke_style(&self.vertical_stroke_style);
-        ctx.set_line_dash(&self.vertical_line_dash).unwrap();
-
-        ctx.begin_path();
-        for time in self.view_range.clone().step_by(quant.time_step as usize) {
-            let x = X_LINE + view.to_x((time + 1) * quant.time_step);
-            ctx.move_to(x, MARGIN);
-            ctx.line_to(x, MARGIN + self.graph_height);
-        }
-        ctx.stroke();
-
-        ctx.set_stroke_style(&self.black);
-        ctx.set_line_dash(&self.no_line_dash).unwrap();
-    }
-
     pub(super) fn scale_timeline(&self) {
         // TODO: Use a format buffer
         let (canvas_width, canvas_height) = (
@@ -261,78 +183,4 @@ impl Timeline {
             .set_property("height", &canvas_height)
             .unwrap();
     }
-
-    fn draw_y_line(
-        &self,
-        event: &TimelineEvent,
-        idx: usize,
-        max_idx: usize,
-        ctx: &CanvasRenderingContext2d,
-    ) {
-        // Draw Y tick marks
-        let y = self.graph_height - ((idx + 1) as f64 * Y_TICK_DIST);
-
-        if idx != max_idx {
-            ctx.begin_path();
-            ctx.move_to(X_LINE, y);
-            ctx.line_to(X_LINE - 5.0, y);
-            ctx.stroke();
-        }
-
-        // Draw Y labels
-        let y = MARGIN + (Y_TICK_DIST * (idx + 1) as f64) - 13.0;
-
-        ctx.set_text_align("end");
-        // TODO: Use a format buffer
-        ctx.fill_text(&format!("{}", event), X_LINE - 4.0, y)
-            .unwrap();
-    }
-}
-
-#[derive(Debug)]
-struct ViewMap {
-    start: f64,
-    scale: f64,
-}
-
-impl ViewMap {
-    pub fn new(range: &Range<Nanos>, width: f64) -> Self {
-        Self {
-            start: range.start as f64,
-            scale: width / ((range.end - range.start) as f64),
-        }
-    }
-
-    pub fn to_x(&self, time: Nanos) -> f64 {
-        ((time as f64) - self.start) * self.scale
-    }
-
-    // pub fn to_ns(&self, x: f64) -> f64 {
-    //     self.start + (x / self.scale)
-    // }
-}
-
-#[derive(Debug)]
-struct ViewQuant {
-    pub time_step: Nanos,
-}
-
-impl ViewQuant {
-    pub fn new(range: &Range<Nanos>, width: f64) -> Self {
-        let ns_per_px = (range.end - range.start) / (width as u64);
-        let min_event_px = 2;
-        let step = 1 << (64 - (ns_per_px * min_event_px).leading_zeros());
-
-        Self {
-            time_step: u64::max(1, step),
-        }
-    }
-
-    pub fn round_down(&self, x: Nanos) -> Nanos {
-        x - (x % self.time_step)
-    }
-
-    pub fn quantize(&self, range: &Range<Nanos>) -> Range<Nanos> {
-        self.round_down(range.start)..(self.round_down(range.end) + self.time_step)
-    }
 }
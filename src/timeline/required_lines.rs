@@ -1,4 +1,7 @@
-use crate::data::{TimelineEvent, WorkerTimelineEvent};
+use crate::{
+    data::{TimelineEvent, WorkerTimelineEvent},
+    filter::EventFilter,
+};
 use std::collections::HashMap;
 
 type MultiSet<K, V> = HashMap<K, V>;
@@ -9,10 +12,11 @@ pub(super) struct RequiredLines {
 }
 
 impl RequiredLines {
-    pub fn new(events: &[WorkerTimelineEvent]) -> Self {
+    /// Collects the lanes containing events that pass `filter`
+    pub fn new(events: &[WorkerTimelineEvent], filter: &EventFilter) -> Self {
         let mut this = Self::with_capacity(events.len() / 2);
 
-        for event in events {
+        for event in events.iter().filter(|event| filter.matches(event)) {
             this.add(event);
         }
 
@@ -29,8 +33,10 @@ impl RequiredLines {
         self.unique_events.len()
     }
 
-    pub fn events(&self) -> impl Iterator<Item = &TimelineEvent> + '_ {
-        self.unique_events.keys()
+    pub fn contains(&self, event: &TimelineEvent) -> bool {
+        self.unique_events
+            .get(event)
+            .is_some_and(|&count| count != 0)
     }
 
     pub fn add(&mut self, event: &WorkerTimelineEvent) {
@@ -0,0 +1,238 @@
+use crate::data::WorkerTimelineEvent;
+use anyhow::{Context, Result};
+use std::{collections::HashMap, time::Duration};
+
+/// The maximum number of hits returned from a single search
+pub(crate) const MAX_HITS: usize = 250;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Comparison {
+    Less,
+    LessEq,
+    Equal,
+    GreaterEq,
+    Greater,
+}
+
+impl Comparison {
+    fn matches(self, value: u64, target: u64) -> bool {
+        match self {
+            Self::Less => value < target,
+            Self::LessEq => value <= target,
+            Self::Equal => value == target,
+            Self::GreaterEq => value >= target,
+            Self::Greater => value > target,
+        }
+    }
+}
+
+/// A parsed search such as `name:join dur>5ms worker:2`, every term must match.
+/// Terms without a known key are matched against event names
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct SearchQuery {
+    names: Vec<String>,
+    operators: Vec<usize>,
+    workers: Vec<usize>,
+    durations: Vec<(Comparison, u64)>,
+}
+
+impl SearchQuery {
+    pub fn parse(query: &str) -> Result<Self> {
+        let mut parsed = Self::default();
+
+        for term in query.split_whitespace() {
+            if let Some((key, value)) = term.split_once(':') {
+                match key {
+                    "name" => parsed.names.push(value.to_lowercase()),
+                    "op" | "operator" => parsed.operators.push(
+                        value
+                            .parse()
+                            .with_context(|| format!("invalid operator id '{}'", value))?,
+                    ),
+                    "worker" => parsed.workers.push(
+                        value
+                            .parse()
+                            .with_context(|| format!("invalid worker '{}'", value))?,
+                    ),
+                    // Names can contain colons too
+                    _ => parsed.names.push(term.to_lowercase()),
+                }
+            } else if let Some((comparison, duration)) =
+                term.strip_prefix("dur").and_then(split_comparison)
+            {
+                let duration: Duration = humantime::parse_duration(duration)
+                    .with_context(|| format!("invalid duration '{}'", duration))?;
+                parsed
+                    .durations
+                    .push((comparison, duration.as_nanos() as u64));
+            } else {
+                parsed.names.push(term.to_lowercase());
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+            && self.operators.is_empty()
+            && self.workers.is_empty()
+            && self.durations.is_empty()
+    }
+
+    fn matches_name(&self, name: &str) -> bool {
+        self.names.iter().all(|term| name.contains(term.as_str()))
+    }
+
+    fn matches_event(&self, event: &WorkerTimelineEvent) -> bool {
+        let operator = event.event.operator_id();
+
+        (self.operators.is_empty() || self.operators.iter().any(|&op| operator == Some(op)))
+            && (self.workers.is_empty() || self.workers.contains(&event.worker))
+            && self
+                .durations
+                .iter()
+                .all(|&(comparison, target)| comparison.matches(event.duration, target))
+    }
+}
+
+/// Splits the comparison off the front of a duration predicate such as `>5ms`,
+/// terms like `during` that don't start with one aren't predicates
+fn split_comparison(predicate: &str) -> Option<(Comparison, &str)> {
+    let comparisons = [
+        (">=", Comparison::GreaterEq),
+        ("<=", Comparison::LessEq),
+        (">", Comparison::Greater),
+        ("<", Comparison::Less),
+        ("=", Comparison::Equal),
+    ];
+
+    comparisons.iter().find_map(|&(prefix, comparison)| {
+        predicate
+            .strip_prefix(prefix)
+            .map(|duration| (comparison, duration))
+    })
+}
+
+/// Groups events by their lowercased names so that searches only have to
+/// check each distinct name once
+#[derive(Debug, Default)]
+pub(crate) struct SearchIndex {
+    names: HashMap<String, Vec<usize>>,
+}
+
+impl SearchIndex {
+    pub fn new(events: &[WorkerTimelineEvent]) -> Self {
+        let mut names: HashMap<String, Vec<usize>> = HashMap::new();
+        for (idx, event) in events.iter().enumerate() {
+            names
+                .entry(event.event.to_string().to_lowercase())
+                .or_default()
+                .push(idx);
+        }
+
+        for hits in names.values_mut() {
+            hits.sort_by_key(|&idx| events[idx].start_time);
+        }
+
+        Self { names }
+    }
+
+    /// Returns the indices of matching events ordered by their start time,
+    /// at most `MAX_HITS` are returned
+    pub fn search(&self, events: &[WorkerTimelineEvent], query: &SearchQuery) -> Vec<usize> {
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        let mut hits: Vec<usize> = self
+            .names
+            .iter()
+            .filter(|(name, _)| query.matches_name(name))
+            .flat_map(|(_, hits)| hits.iter().copied())
+            .filter(|&idx| query.matches_event(&events[idx]))
+            .collect();
+
+        hits.sort_by_key(|&idx| (events[idx].start_time, idx));
+        hits.truncate(MAX_HITS);
+        hits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Comparison, SearchIndex, SearchQuery};
+    use crate::data::{TimelineEvent, WorkerTimelineEvent};
+
+    fn event(
+        event_id: u64,
+        worker: usize,
+        event: TimelineEvent,
+        duration: u64,
+    ) -> WorkerTimelineEvent {
+        WorkerTimelineEvent {
+            event_id,
+            worker,
+            event,
+            start_time: event_id * 100,
+            duration,
+            collapsed_events: 0,
+            traffic: None,
+            merge: None,
+        }
+    }
+
+    fn operator(operator_id: usize, name: &str) -> TimelineEvent {
+        TimelineEvent::OperatorActivation {
+            operator_id,
+            operator_name: name.to_owned(),
+        }
+    }
+
+    #[test]
+    fn parse_query() {
+        let query = SearchQuery::parse("name:Join dur>5ms worker:2 op:12 Arrange").unwrap();
+
+        assert_eq!(query.names, vec!["join".to_owned(), "arrange".to_owned()]);
+        assert_eq!(query.workers, vec![2]);
+        assert_eq!(query.operators, vec![12]);
+        assert_eq!(query.durations, vec![(Comparison::Greater, 5_000_000)]);
+
+        assert!(SearchQuery::parse("").unwrap().is_empty());
+        assert!(SearchQuery::parse("dur>five").is_err());
+        assert!(SearchQuery::parse("worker:two").is_err());
+    }
+
+    #[test]
+    fn parse_text_terms() {
+        // Only a comparison makes `dur` a duration predicate
+        let query = SearchQuery::parse("during duration dur~5ms").unwrap();
+        assert_eq!(query.names, vec!["during", "duration", "dur~5ms"]);
+        assert!(query.durations.is_empty());
+
+        // Unknown keys are part of the name
+        let query = SearchQuery::parse("Color:Red worker:1").unwrap();
+        assert_eq!(query.names, vec!["color:red"]);
+        assert_eq!(query.workers, vec![1]);
+    }
+
+    #[test]
+    fn search_events() {
+        let events = vec![
+            event(3, 0, operator(1, "Join"), 10_000_000),
+            event(1, 2, operator(1, "Join"), 2_000_000),
+            event(2, 2, operator(4, "JoinCore"), 7_000_000),
+            event(4, 2, TimelineEvent::Parked, 9_000_000),
+        ];
+        let index = SearchIndex::new(&events);
+        let search = |query| index.search(&events, &SearchQuery::parse(query).unwrap());
+
+        assert_eq!(search("join"), vec![1, 2, 0]);
+        assert_eq!(search("name:join dur>5ms worker:2"), vec![2]);
+        assert_eq!(search("op:1 dur<=2ms"), vec![1]);
+        assert_eq!(search("parked"), vec![3]);
+        assert_eq!(search("4:"), vec![2]);
+        assert!(search("reduce").is_empty());
+        assert!(search("").is_empty());
+    }
+}
@@ -0,0 +1,222 @@
+use crate::loader::ProfileTrace;
+use gigatrace::{
+    payload::PayloadId,
+    trace::{Nanos, TraceEvent},
+    TrackInfo,
+};
+use std::{cmp::Reverse, ops::Range};
+
+/// The most events listed as the longest within a selection
+pub(crate) const MAX_LONGEST: usize = 10;
+
+/// A lane's activity within a selection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct LaneStats {
+    /// The lane's index within the trace's tracks
+    pub lane: usize,
+    pub count: u64,
+    /// How long the lane's events ran for altogether, in nanoseconds
+    pub busy: Nanos,
+}
+
+/// A summary of the events that start within a selected span of the timeline
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SelectionStats {
+    /// The selected span, in ticks
+    pub span: Range<Nanos>,
+    /// Every lane with events within the selection, busiest first
+    pub lanes: Vec<LaneStats>,
+    /// The fraction of the selection each worker spent unparked
+    pub utilization: Vec<f64>,
+    /// The longest event of each lane, longest first
+    pub longest: Vec<TraceEvent<PayloadId>>,
+}
+
+impl SelectionStats {
+    /// Aggregates the selection with range queries over each track's index
+    pub fn new(trace: &ProfileTrace, parks: &[TrackInfo<PayloadId>], span: Range<Nanos>) -> Self {
+        let clock = trace.clock;
+
+        let mut lanes = Vec::new();
+        let mut longest = Vec::new();
+        for (lane, track) in trace.tracks.iter().enumerate() {
+            let stats = track.stats(&trace.pool, span.clone());
+            if stats.count == 0 {
+                continue;
+            }
+
+            lanes.push(LaneStats {
+                lane,
+                count: stats.count,
+                busy: clock.elapsed(stats.busy),
+            });
+            longest.extend(stats.longest.0);
+        }
+
+        lanes.sort_by_key(|lane| Reverse(lane.busy));
+        longest.sort_by_key(|event| Reverse(event.duration));
+        longest.truncate(MAX_LONGEST);
+
+        // Parks running into or out of the selection are clipped to it
+        let selected = clock.elapsed(span.end - span.start).max(1) as f64;
+        let utilization = parks
+            .iter()
+            .map(|parks| {
+                let parked = clock.elapsed(parks.busy_within(&trace.pool, span.clone()));
+                1.0 - parked as f64 / selected
+            })
+            .collect();
+
+        Self {
+            span,
+            lanes,
+            utilization,
+            longest,
+        }
+    }
+
+    /// The number of events within the selection
+    pub fn count(&self) -> u64 {
+        self.lanes.iter().map(|lane| lane.count).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SelectionStats;
+    use crate::{
+        data::TimelineEvent,
+        demo::{loaded_demo, DemoConfig},
+        loader::LoadedProfile,
+    };
+    use gigatrace::{
+        payload::PayloadId,
+        trace::{Nanos, TraceEvent},
+    };
+    use std::ops::Range;
+
+    /// Counts and sums the durations of each lane's events by scanning them
+    fn scan(profile: &LoadedProfile, span: &Range<Nanos>) -> Vec<(u64, Nanos)> {
+        let clock = profile.trace.clock;
+        let mut lanes = vec![(0, 0); profile.lanes.len()];
+
+        for event in &profile.events {
+            let start = clock.pack(event.start_time).unwrap().unpack();
+            if span.contains(&start) {
+                let lane = profile.lanes.iter().position(|lane| *lane == event.event);
+                let stats = &mut lanes[lane.unwrap()];
+
+                stats.0 += 1;
+                stats.1 += clock.unpack_duration(clock.pack_duration(event.duration).unwrap());
+            }
+        }
+
+        lanes
+    }
+
+    #[test]
+    fn summarizes_the_whole_trace() {
+        let profile = loaded_demo();
+        let span = profile.trace.time_bounds().unwrap();
+        let stats = SelectionStats::new(&profile.trace, &profile.parks, span);
+
+        assert_eq!(stats.count(), profile.events.len() as u64);
+        assert_eq!(stats.utilization.len(), DemoConfig::default().workers);
+        assert!(stats
+            .utilization
+            .iter()
+            .all(|&utilization| utilization > 0.0 && utilization < 1.0));
+
+        assert!(stats
+            .lanes
+            .windows(2)
+            .all(|pair| pair[0].busy >= pair[1].busy));
+        let longest = stats.longest[0].duration.unpack();
+        assert!(profile
+            .trace
+            .tracks
+            .iter()
+            .flat_map(|track| track.track.events_in(&profile.trace.pool))
+            .all(|event| event.duration.unpack() <= longest));
+    }
+
+    #[test]
+    fn matches_scanning_events() {
+        let profile = loaded_demo();
+        let bounds = profile.trace.time_bounds().unwrap();
+        let length = bounds.end - bounds.start;
+
+        for &(from, to) in &[(0, 1), (1, 3), (2, 7), (5, 6), (9, 10)] {
+            let span = bounds.start + length * from / 10..bounds.start + length * to / 10;
+            let stats = SelectionStats::new(&profile.trace, &profile.parks, span.clone());
+
+            let mut expected: Vec<_> = scan(&profile, &span)
+                .into_iter()
+                .enumerate()
+                .filter(|&(_, (count, _))| count > 0)
+                .collect();
+            expected.sort_by_key(|&(lane, (_, busy))| (std::cmp::Reverse(busy), lane));
+
+            let mut lanes: Vec<_> = stats
+                .lanes
+                .iter()
+                .map(|lane| (lane.lane, (lane.count, lane.busy)))
+                .collect();
+            lanes.sort_by_key(|&(lane, (_, busy))| (std::cmp::Reverse(busy), lane));
+
+            assert_eq!(lanes, expected, "failed for {:?}", span);
+        }
+    }
+
+    #[test]
+    fn workers_are_idle_while_parked() {
+        let profile = loaded_demo();
+        let parked = profile
+            .lanes
+            .iter()
+            .position(|lane| *lane == TimelineEvent::Parked)
+            .unwrap();
+
+        // A span covering a single park
+        let park = profile.trace.tracks[parked]
+            .track
+            .events_in(&profile.trace.pool)
+            .next()
+            .unwrap();
+        let start = park.timestamp.unpack();
+        let span = start..start + park.duration.unpack();
+
+        let stats = SelectionStats::new(&profile.trace, &profile.parks, span);
+        let worker = profile.trace.payloads.worker(park.kind) as usize;
+        assert_eq!(stats.utilization[worker], 0.0);
+    }
+
+    #[test]
+    fn clips_parks_to_the_selection() {
+        let profile = loaded_demo();
+        let clock = profile.trace.clock;
+
+        for (worker, parks) in profile.parks.iter().enumerate() {
+            let parks: Vec<_> = parks.track.events_in(&profile.trace.pool).collect();
+            let (first, second) = (&parks[0], &parks[1]);
+            let end =
+                |park: &TraceEvent<PayloadId>| park.timestamp.unpack() + park.duration.unpack();
+
+            // Starts halfway through one park and ends halfway through the next
+            let start = first.timestamp.unpack() + first.duration.unpack() / 2;
+            let span = start..second.timestamp.unpack() + second.duration.unpack() / 2;
+            let parked = (end(first) - span.start) + (span.end - second.timestamp.unpack());
+
+            let stats = SelectionStats::new(&profile.trace, &profile.parks, span.clone());
+            let expected =
+                1.0 - clock.elapsed(parked) as f64 / clock.elapsed(span.end - span.start) as f64;
+            assert!(
+                (stats.utilization[worker] - expected).abs() < 1e-9,
+                "worker {} was {} utilized rather than {}",
+                worker,
+                stats.utilization[worker],
+                expected,
+            );
+        }
+    }
+}
@@ -47,7 +47,7 @@ pub(super) fn draw_rounded_rect(
 pub(super) fn calculate_timeline_dimensions(
     properties: &TimelineProps,
 ) -> (f64, f64, f64, f64, f64, f64, f64, RequiredLines) {
-    let required_lines = RequiredLines::new(&*properties.events);
+    let required_lines = RequiredLines::new(&*properties.events, &properties.filter);
 
     let scale = properties.scale.ceil();
     let duration = properties.duration.ceil();
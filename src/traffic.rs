@@ -0,0 +1,581 @@
+use crate::{
+    data::{Edge, EventKind, Node, Subgraph, WorkerTimelineEvent},
+    download::svg_url,
+    view::{format_duration, svg_document},
+};
+use gigatrace::render::{Color, DrawCommand, Point, Rect, TextAlign};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+    ops::Range,
+    rc::Rc,
+};
+use yew::{html, Component, ComponentLink, Html, Properties, ShouldRender};
+
+/// The number of bars each channel's throughput is split into
+pub const TRAFFIC_BUCKETS: usize = 100;
+
+/// The most sends drawn as arrows, the ones that sent the most records are kept
+pub const MAX_ARROWS: usize = 2_000;
+
+/// The most channels charted, the ones that sent the most records are kept
+pub const MAX_CHARTS: usize = 20;
+
+/// The width traffic is drawn at, images are scaled to fit the page
+const TRAFFIC_WIDTH: f64 = 1200.0;
+
+const LABEL_WIDTH: f64 = 80.0;
+const LANE_HEIGHT: f64 = 30.0;
+const CHART_HEIGHT: f64 = 60.0;
+
+const MESSAGE_COLOR: Color = Color::rgba8(106, 27, 154, 0x99);
+const PROGRESS_COLOR: Color = Color::rgba8(21, 101, 192, 0x99);
+const LANE_COLOR: Color = Color::rgb8(0xBD, 0xBD, 0xBD);
+
+/// A message or progress update sent from one worker to another
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Arrow {
+    pub source: usize,
+    pub target: usize,
+    /// When the send started and finished, in nanoseconds
+    pub start: u64,
+    pub end: u64,
+    pub records: usize,
+    pub progress: bool,
+}
+
+/// Everything sent over a single channel
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelTraffic {
+    pub channel_id: usize,
+    /// Progress channels aren't edges of the dataflow
+    pub progress: bool,
+    /// Describes the operators the channel connects
+    pub label: String,
+    pub sends: u64,
+    pub records: u64,
+    pub bytes: u64,
+    /// The records sent within each span of the profile
+    pub buckets: Vec<u64>,
+}
+
+/// The records sent between workers and over each channel
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrafficSummary {
+    /// The span of the profile's events, in nanoseconds
+    pub span: Range<u64>,
+    pub workers: usize,
+    /// Records sent between every pair of workers, indexed by source then target
+    pub matrix: Vec<Vec<u64>>,
+    /// Busiest first
+    pub channels: Vec<ChannelTraffic>,
+    /// Sends between different workers, ordered by when they started
+    pub arrows: Vec<Arrow>,
+    /// The number of sends between different workers, including those not drawn as arrows
+    pub remote_sends: usize,
+}
+
+impl TrafficSummary {
+    pub fn new(
+        events: &[WorkerTimelineEvent],
+        nodes: &[Node],
+        subgraphs: &[Subgraph],
+        edges: &[Edge],
+        buckets: usize,
+    ) -> Self {
+        let start = events.iter().map(|event| event.start_time).min();
+        let end = events.iter().map(WorkerTimelineEvent::end_time).max();
+        let span = start.unwrap_or(0)..end.unwrap_or(0);
+        let width = (span.end - span.start).max(1) as f64;
+
+        let workers = events
+            .iter()
+            .flat_map(|event| {
+                let sent = event
+                    .traffic
+                    .map(|traffic| traffic.source_worker.max(traffic.target_worker));
+                sent.into_iter().chain(Some(event.worker))
+            })
+            .max()
+            .map_or(0, |worker| worker + 1);
+
+        let names: HashMap<&[usize], &str> = nodes
+            .iter()
+            .map(|node| (&*node.addr, &*node.name))
+            .chain(
+                subgraphs
+                    .iter()
+                    .map(|subgraph| (&*subgraph.addr, &*subgraph.name)),
+            )
+            .collect();
+        let edges: HashMap<_, _> = edges.iter().map(|edge| (edge.channel_id, edge)).collect();
+
+        let mut matrix = vec![vec![0; workers]; workers];
+        let mut channels = BTreeMap::new();
+        let mut arrows = Vec::new();
+
+        for event in events {
+            let traffic = match event.traffic {
+                Some(traffic) => traffic,
+                None => continue,
+            };
+            let progress = event.event.kind() == EventKind::Progress;
+            let records = traffic.records as u64;
+
+            matrix[traffic.source_worker][traffic.target_worker] += records;
+
+            let channel = channels
+                .entry((progress, traffic.channel_id))
+                .or_insert_with(|| ChannelTraffic {
+                    channel_id: traffic.channel_id,
+                    progress,
+                    label: channel_label(traffic.channel_id, progress, &edges, &names),
+                    sends: 0,
+                    records: 0,
+                    bytes: 0,
+                    buckets: vec![0; buckets],
+                });
+            channel.sends += 1;
+            channel.records += records;
+            channel.bytes += traffic.bytes.unwrap_or(0) as u64;
+
+            let bucket = ((event.start_time - span.start) as f64 / width * buckets as f64) as usize;
+            if let Some(bucket) = channel
+                .buckets
+                .get_mut(bucket.min(buckets.saturating_sub(1)))
+            {
+                *bucket += records;
+            }
+
+            if traffic.source_worker != traffic.target_worker {
+                arrows.push(Arrow {
+                    source: traffic.source_worker,
+                    target: traffic.target_worker,
+                    start: event.start_time,
+                    end: event.end_time(),
+                    records: traffic.records,
+                    progress,
+                });
+            }
+        }
+
+        let remote_sends = arrows.len();
+        if arrows.len() > MAX_ARROWS {
+            arrows.select_nth_unstable_by_key(MAX_ARROWS, |arrow| Reverse(arrow.records));
+            arrows.truncate(MAX_ARROWS);
+        }
+        arrows.sort_by_key(|arrow| (arrow.start, arrow.source, arrow.target));
+
+        let mut channels: Vec<_> = channels.into_values().collect();
+        channels.sort_by_key(|channel| Reverse(channel.records));
+
+        Self {
+            span,
+            workers,
+            matrix,
+            channels,
+            arrows,
+            remote_sends,
+        }
+    }
+
+    /// Whether the profile recorded anything being sent
+    pub fn is_empty(&self) -> bool {
+        self.channels.is_empty()
+    }
+
+    /// Lays out a lane per worker with an arrow for every send between them,
+    /// returns the height of the layout along with its commands
+    pub fn arrows_layout(&self, width: f64) -> (f64, Vec<DrawCommand>) {
+        let lane_y = |worker: usize| 10.0 + (worker as f64 + 0.5) * LANE_HEIGHT;
+        let graph_width = (width - LABEL_WIDTH - 10.0).max(1.0);
+        let span_width = (self.span.end - self.span.start).max(1) as f64;
+        let time_x =
+            |time: u64| LABEL_WIDTH + (time - self.span.start) as f64 / span_width * graph_width;
+
+        let mut commands = Vec::new();
+        for worker in 0..self.workers {
+            let y = lane_y(worker);
+
+            commands.push(DrawCommand::Text {
+                text: format!("Worker {}", worker),
+                at: Point::new(5.0, y - 7.0),
+                size: 12.0,
+                color: Color::BLACK,
+                align: TextAlign::Start,
+                angle: 0.0,
+            });
+            commands.push(DrawCommand::Line {
+                from: Point::new(LABEL_WIDTH, y),
+                to: Point::new(LABEL_WIDTH + graph_width, y),
+                color: LANE_COLOR,
+                width: 1.0,
+                dash: None,
+            });
+        }
+
+        for arrow in &self.arrows {
+            let from = Point::new(time_x(arrow.start), lane_y(arrow.source));
+            let to = Point::new(time_x(arrow.end), lane_y(arrow.target));
+            let color = if arrow.progress {
+                PROGRESS_COLOR
+            } else {
+                MESSAGE_COLOR
+            };
+
+            commands.push(DrawCommand::Line {
+                from,
+                to,
+                color,
+                width: 1.0,
+                dash: None,
+            });
+
+            // The arrow's head points back along the line from its end
+            let angle = (to.y - from.y).atan2(to.x - from.x);
+            for &side in &[-0.5, 0.5] {
+                let back = angle + std::f64::consts::PI + side;
+                commands.push(DrawCommand::Line {
+                    from: to,
+                    to: Point::new(to.x + 6.0 * back.cos(), to.y + 6.0 * back.sin()),
+                    color,
+                    width: 1.0,
+                    dash: None,
+                });
+            }
+        }
+
+        (20.0 + self.workers as f64 * LANE_HEIGHT, commands)
+    }
+
+    /// Charts the records sent over a channel during each span of the profile,
+    /// every chart is `CHART_HEIGHT` pixels tall
+    pub fn throughput_layout(&self, channel: &ChannelTraffic, width: f64) -> Vec<DrawCommand> {
+        let mut title = format!(
+            "{}: {} records over {} sends",
+            channel.label, channel.records, channel.sends,
+        );
+        if channel.bytes > 0 {
+            title.push_str(&format!(", {}", format_bytes(channel.bytes)));
+        }
+
+        let mut commands = vec![DrawCommand::Text {
+            text: title,
+            at: Point::new(5.0, 2.0),
+            size: 12.0,
+            color: Color::BLACK,
+            align: TextAlign::Start,
+            angle: 0.0,
+        }];
+
+        let top = 18.0;
+        let max = channel.buckets.iter().copied().max().unwrap_or(0).max(1) as f64;
+        let bar_width = width / channel.buckets.len().max(1) as f64;
+        let color = if channel.progress {
+            PROGRESS_COLOR
+        } else {
+            MESSAGE_COLOR
+        };
+
+        for (bucket, &records) in channel.buckets.iter().enumerate() {
+            if records == 0 {
+                continue;
+            }
+
+            let height = records as f64 / max * (CHART_HEIGHT - top);
+            commands.push(DrawCommand::FillRect {
+                rect: Rect::new(
+                    bucket as f64 * bar_width,
+                    CHART_HEIGHT - height,
+                    (bar_width - 1.0).max(1.0),
+                    height,
+                ),
+                radius: 0.0,
+                color,
+            });
+        }
+
+        commands
+    }
+}
+
+fn channel_label(
+    channel_id: usize,
+    progress: bool,
+    edges: &HashMap<usize, &Edge>,
+    names: &HashMap<&[usize], &str>,
+) -> String {
+    let operator = |addr: &[usize]| {
+        let name = names.get(addr).copied().unwrap_or("Unknown");
+        format!("{} {:?}", name, addr)
+    };
+
+    match edges.get(&channel_id) {
+        _ if progress => format!("Progress channel {}", channel_id),
+        Some(edge) => format!(
+            "Channel {}: {} → {}",
+            channel_id,
+            operator(&edge.src),
+            operator(&edge.dest),
+        ),
+        None => format!("Channel {}", channel_id),
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+#[derive(Debug, Clone, Properties)]
+pub struct TrafficPanelProps {
+    pub traffic: Rc<TrafficSummary>,
+}
+
+/// Arrows between worker lanes for every send along with each channel's throughput
+#[derive(Debug)]
+pub struct TrafficPanel {
+    properties: TrafficPanelProps,
+    /// The arrows and charts are drawn once as svg images
+    arrows: String,
+    charts: Vec<String>,
+}
+
+impl TrafficPanel {
+    fn draw(&mut self) {
+        let traffic = &self.properties.traffic;
+        if traffic.is_empty() {
+            self.arrows.clear();
+            self.charts.clear();
+            return;
+        }
+
+        let (height, commands) = traffic.arrows_layout(TRAFFIC_WIDTH);
+        self.arrows = svg_url(&svg_document(TRAFFIC_WIDTH, height, &commands));
+
+        self.charts = traffic
+            .channels
+            .iter()
+            .take(MAX_CHARTS)
+            .map(|channel| {
+                let commands = traffic.throughput_layout(channel, TRAFFIC_WIDTH);
+                svg_url(&svg_document(TRAFFIC_WIDTH, CHART_HEIGHT, &commands))
+            })
+            .collect();
+    }
+
+    fn view_matrix(&self) -> Html {
+        let traffic = &self.properties.traffic;
+
+        let header =
+            (0..traffic.workers).map(|worker| html! { <th>{ format!("To {}", worker) }</th> });
+        let rows = traffic.matrix.iter().enumerate().map(|(source, targets)| {
+            html! {
+                <tr>
+                    <th>{ format!("From {}", source) }</th>
+                    { for targets.iter().map(|records| html! { <td>{ records }</td> }) }
+                </tr>
+            }
+        });
+
+        html! {
+            <table class="traffic-matrix">
+                <thead><tr><th>{ "Records" }</th>{ for header }</tr></thead>
+                <tbody>{ for rows }</tbody>
+            </table>
+        }
+    }
+}
+
+impl Component for TrafficPanel {
+    type Message = ();
+    type Properties = TrafficPanelProps;
+
+    fn create(properties: Self::Properties, _link: ComponentLink<Self>) -> Self {
+        let mut panel = Self {
+            properties,
+            arrows: String::new(),
+            charts: Vec::new(),
+        };
+        panel.draw();
+
+        panel
+    }
+
+    fn update(&mut self, _message: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, properties: Self::Properties) -> ShouldRender {
+        if Rc::ptr_eq(&self.properties.traffic, &properties.traffic) {
+            return false;
+        }
+
+        self.properties = properties;
+        self.draw();
+
+        true
+    }
+
+    fn view(&self) -> Html {
+        let traffic = &self.properties.traffic;
+        if traffic.is_empty() {
+            return html! {
+                <details id="traffic">
+                    <summary>{ "Traffic between workers" }</summary>
+                    <p>{ "This profile doesn't record what its messages and progress updates sent" }</p>
+                </details>
+            };
+        }
+
+        let span = format_duration(traffic.span.end - traffic.span.start);
+        let shown = if traffic.remote_sends > traffic.arrows.len() {
+            format!(
+                "Showing the {} largest of {} sends between workers over {}",
+                traffic.arrows.len(),
+                traffic.remote_sends,
+                span,
+            )
+        } else {
+            format!(
+                "{} sends between workers over {}",
+                traffic.remote_sends, span
+            )
+        };
+
+        let charts = self.charts.iter().map(|chart| {
+            html! { <img class="traffic-chart" src=chart.clone() /> }
+        });
+
+        html! {
+            <details id="traffic" open=true>
+                <summary>{ "Traffic between workers" }</summary>
+                <p>{ shown }</p>
+                <img class="traffic-arrows" src=self.arrows.clone() />
+                { self.view_matrix() }
+                <p>{ "Throughput by channel" }</p>
+                { for charts }
+            </details>
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TrafficSummary, MAX_ARROWS, TRAFFIC_BUCKETS};
+    use crate::{
+        data::{TimelineEvent, Traffic, WorkerTimelineEvent},
+        demo::{loaded_demo, DemoConfig},
+        view::svg_document,
+    };
+
+    #[test]
+    fn summarizes_sends() {
+        let profile = loaded_demo();
+        let sent: u64 = profile
+            .events
+            .iter()
+            .filter_map(|event| event.traffic)
+            .map(|traffic| traffic.records as u64)
+            .sum();
+        let traffic = &profile.traffic;
+
+        assert!(sent > 0);
+        assert_eq!(traffic.workers, DemoConfig::default().workers);
+        assert_eq!(traffic.matrix.iter().flatten().sum::<u64>(), sent);
+        assert_eq!(
+            traffic
+                .channels
+                .iter()
+                .map(|channel| channel.records)
+                .sum::<u64>(),
+            sent,
+        );
+        assert!(traffic
+            .channels
+            .iter()
+            .all(|channel| channel.buckets.len() == TRAFFIC_BUCKETS
+                && channel.buckets.iter().sum::<u64>() == channel.records));
+        assert!(traffic.channels.iter().any(|channel| channel.progress));
+        assert!(traffic
+            .channels
+            .iter()
+            .any(|channel| channel.label.contains(" → ")));
+
+        assert!(!traffic.arrows.is_empty() && traffic.arrows.len() <= MAX_ARROWS);
+        assert!(traffic
+            .arrows
+            .iter()
+            .all(|arrow| arrow.source != arrow.target));
+        assert!(traffic
+            .arrows
+            .windows(2)
+            .all(|arrows| arrows[0].start <= arrows[1].start));
+    }
+
+    #[test]
+    fn draws_arrows_between_lanes() {
+        let profile = loaded_demo();
+
+        let (height, commands) = profile.traffic.arrows_layout(1000.0);
+        let svg = svg_document(1000.0, height, &commands);
+        assert!(svg.contains(">Worker 3</text>"));
+        // A line and two for its head per arrow, along with a line per lane
+        assert_eq!(
+            svg.matches("<line").count(),
+            profile.traffic.arrows.len() * 3 + 4,
+        );
+
+        let channel = &profile.traffic.channels[0];
+        let chart = profile.traffic.throughput_layout(channel, 1000.0);
+        assert!(chart.len() > 1);
+    }
+
+    #[test]
+    fn keeps_the_largest_remote_sends() {
+        let send = |idx: usize, source_worker, target_worker| WorkerTimelineEvent {
+            event_id: idx as u64,
+            worker: source_worker,
+            event: TimelineEvent::Message,
+            start_time: (idx % 7) as u64 * 10,
+            duration: 5,
+            collapsed_events: 1,
+            traffic: Some(Traffic {
+                channel_id: 0,
+                source_worker,
+                target_worker,
+                records: idx,
+                bytes: None,
+            }),
+            merge: None,
+        };
+
+        // Sends within a worker aren't drawn but still count towards the matrix
+        let mut events: Vec<_> = (0..MAX_ARROWS + 10).map(|idx| send(idx, 0, 1)).collect();
+        events.push(send(1_000, 1, 1));
+        let traffic = TrafficSummary::new(&events, &[], &[], &[], TRAFFIC_BUCKETS);
+
+        assert_eq!(traffic.workers, 2);
+        assert_eq!(traffic.matrix[1][1], 1_000);
+        assert_eq!(traffic.remote_sends, MAX_ARROWS + 10);
+        assert_eq!(traffic.arrows.len(), MAX_ARROWS);
+        assert!(traffic.arrows.iter().all(|arrow| arrow.records >= 10));
+        assert!(traffic
+            .arrows
+            .windows(2)
+            .all(|arrows| arrows[0].start <= arrows[1].start));
+    }
+}
@@ -0,0 +1,147 @@
+//! Timeline layout shared by the web dashboard and the native viewer
+
+use crate::{
+    data::TimelineEvent,
+    timeline::constants::{MARGIN, X_LINE, Y_LINE, Y_TICK_DIST},
+};
+use gigatrace::{
+    payload::PayloadId,
+    render::{
+        Color, DrawCommand, LaneRow, Rect, Renderer, SvgRenderer, TimelineLayout, TimelineStyle,
+    },
+    trace::{Nanos, TraceEvent},
+    Trace,
+};
+use humantime::Duration as HumanDuration;
+use std::time::Duration;
+
+/// Lays out a timeline `width` pixels wide whose lanes take up `graph_height`
+/// pixels, lane labels go to the left of the graph and times go below it
+pub fn timeline_layout(width: f64, graph_height: f64) -> TimelineLayout {
+    TimelineLayout {
+        width,
+        height: graph_height + MARGIN + Y_LINE,
+        graph: Rect::new(
+            X_LINE,
+            MARGIN,
+            (width - X_LINE - 30.0).max(1.0),
+            graph_height,
+        ),
+        style: TimelineStyle::default(),
+    }
+}
+
+/// Gives every lane a full height row, in the order they're given
+pub fn lane_rows(lanes: &[TimelineEvent]) -> Vec<LaneRow> {
+    lanes
+        .iter()
+        .enumerate()
+        .map(|(track, lane)| LaneRow {
+            track,
+            y: track as f64 * Y_TICK_DIST,
+            height: Y_TICK_DIST,
+            label: lane.to_string(),
+            collapsed: false,
+        })
+        .collect()
+}
+
+pub fn format_duration(nanos: Nanos) -> String {
+    HumanDuration::from(Duration::from_nanos(nanos)).to_string()
+}
+
+/// The label shown when hovering over an event
+pub fn event_tooltip<P>(trace: &Trace<PayloadId, P>, event: &TraceEvent<PayloadId>) -> String {
+    let name = trace.event_name(event).unwrap_or_default();
+    let duration = format_duration(trace.clock.unpack_duration(event.duration));
+
+    format!("{} ran for {}", name, duration)
+}
+
+/// Fills the whole timeline so that exports don't depend on what they're pasted onto
+pub fn background(layout: &TimelineLayout) -> DrawCommand {
+    white_fill(layout.width, layout.height)
+}
+
+fn white_fill(width: f64, height: f64) -> DrawCommand {
+    DrawCommand::FillRect {
+        rect: Rect::new(0.0, 0.0, width, height),
+        radius: 0.0,
+        color: Color::WHITE,
+    }
+}
+
+/// Renders a laid out timeline to a standalone svg document
+pub fn export_svg(layout: &TimelineLayout, commands: &[DrawCommand]) -> String {
+    svg_document(layout.width, layout.height, commands)
+}
+
+/// Renders draw commands onto a white background in a standalone svg document
+pub fn svg_document(width: f64, height: f64, commands: &[DrawCommand]) -> String {
+    let mut svg = SvgRenderer::new(width, height);
+    svg.draw(&white_fill(width, height))
+        .and_then(|()| svg.render(commands))
+        .expect("writing to a string can't fail");
+
+    svg.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{event_tooltip, export_svg, lane_rows, timeline_layout};
+    use crate::loader::load_profile;
+    use gigatrace::render::Point;
+
+    #[test]
+    fn lays_out_every_lane() {
+        let content = include_bytes!("../test/data/example.json");
+        let profile = load_profile("example.json".to_owned(), content, |_| {}).unwrap();
+
+        let rows = lane_rows(&profile.lanes);
+        let graph_height = rows.last().map_or(0.0, |row| row.y + row.height);
+        let layout = timeline_layout(1200.0, graph_height);
+        let view = profile.trace.time_bounds().unwrap();
+
+        let scene = layout.layout(
+            &profile.trace,
+            &rows,
+            &view,
+            |_| true,
+            None,
+            |_| String::new(),
+        );
+        assert!(!scene.events.is_empty());
+        assert!(scene.events.iter().all(|event| layout
+            .graph
+            .contains(Point::new(event.rect.x, event.rect.y))));
+
+        let tooltip = event_tooltip(&profile.trace, &scene.events[0].event);
+        assert!(tooltip.contains(" ran for "), "{}", tooltip);
+    }
+
+    #[test]
+    fn exports_labeled_svgs() {
+        let content = include_bytes!("../test/data/example.json");
+        let profile = load_profile("example.json".to_owned(), content, |_| {}).unwrap();
+
+        let rows = lane_rows(&profile.lanes);
+        let graph_height = rows.last().map_or(0.0, |row| row.y + row.height);
+        let layout = timeline_layout(2000.0, graph_height);
+        let view = profile.trace.time_bounds().unwrap();
+        let scene = layout.layout(
+            &profile.trace,
+            &rows,
+            &view,
+            |_| true,
+            None,
+            |_| String::new(),
+        );
+
+        let svg = export_svg(&layout, &scene.commands);
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains(r#"width="2000""#));
+        assert!(svg.contains(r##"<rect x="0" y="0" width="2000""##));
+        assert!(svg.contains(">Operator 28: Probe</text>"));
+        assert!(svg.ends_with("</svg>\n"));
+    }
+}
@@ -21,12 +21,23 @@ module.exports = (_env, argv) => {
             new CopyWebpackPlugin({
                 patterns: [
                     { from: "./static", to: distPath },
+                    // yew's workers load `worker.js` and `worker_bg.wasm` from the site's root
+                    { from: "./pkg-worker/worker.js", to: distPath },
+                    { from: "./pkg-worker/worker_bg.wasm", to: distPath },
                 ],
             }),
             new WasmPackPlugin({
                 crateDirectory: ".",
                 extraArgs: "--no-typescript",
             }),
+            // The profile loader runs within a web worker, which needs
+            // the crate built without any module imports
+            new WasmPackPlugin({
+                crateDirectory: ".",
+                outDir: "pkg-worker",
+                outName: "worker",
+                extraArgs: "--no-typescript --target no-modules",
+            }),
         ],
         module: {
             rules: [